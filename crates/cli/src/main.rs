@@ -1,16 +1,34 @@
-use std::{collections::HashMap, error::Error, fs, path::PathBuf, time::Duration};
+use std::{
+  collections::{BTreeMap, HashMap},
+  error::Error,
+  ffi::OsString,
+  fmt::{self, Write as _},
+  fs::{self, File},
+  io::{self, BufWriter, Write},
+  path::{Path, PathBuf},
+  time::Duration
+};
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use glob::glob;
 use gta5_script_decompiler::{
-  decompiler::{get_functions, DecompilerData, ScriptGlobals, ScriptStatics},
-  disassembler::disassemble,
-  formatters::{AssemblyFormatter, CppFormatter},
-  resources::{CrossMap, Natives},
-  script::parse_ysc_file
+  decompiler::{
+    decompile_all_with_options, decompiled::build_native_xref, function_map, get_functions,
+    Confidence, DecompileOptions, DecompilerData, MultiScript, RelatedScripts, ScriptGlobals,
+    ScriptStatics, ScriptStats, DEFAULT_MAX_DEPTH
+  },
+  disassembler::{disassemble, InstructionInfo},
+  formatters::{
+    AssemblyFormatter, CppFormatter, CppFormatterOptions, FormatterData, IntegerDivisionStyle,
+    UnknownFunctionNaming, UnknownTypeStyle
+  },
+  resources::{CrossMap, HashNames, Natives, SignatureMap},
+  script::{parse_ysc_file_with_version, OpcodeVersion, Script}
 };
 use indicatif::{ProgressBar, ProgressStyle};
 
+const DEFAULT_CROSS_MAP: &str = "./resources/crossmap.json";
+
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), anyhow::Error>
 where
   T: std::str::FromStr,
@@ -24,57 +42,483 @@ where
   Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UnknownTypes {
+  /// Render unknown types as `any`
+  Any,
+  /// Render unknown types as `any` and emit `typedef int any;`
+  Typedef,
+  /// Render unknown types as `int /* unknown */`
+  Int
+}
+
+impl From<UnknownTypes> for UnknownTypeStyle {
+  fn from(value: UnknownTypes) -> Self {
+    match value {
+      UnknownTypes::Any => UnknownTypeStyle::Any,
+      UnknownTypes::Typedef => UnknownTypeStyle::Typedef,
+      UnknownTypes::Int => UnknownTypeStyle::Int
+    }
+  }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum IntegerDivision {
+  /// Render integer division like float division
+  Plain,
+  /// Annotate integer `/` and `%` with a `/* int */` comment
+  Comment,
+  /// Render integer `/` and `%` as `idiv(a, b)` and `imod(a, b)`
+  Helper
+}
+
+impl From<IntegerDivision> for IntegerDivisionStyle {
+  fn from(value: IntegerDivision) -> Self {
+    match value {
+      IntegerDivision::Plain => IntegerDivisionStyle::Plain,
+      IntegerDivision::Comment => IntegerDivisionStyle::Comment,
+      IntegerDivision::Helper => IntegerDivisionStyle::Helper
+    }
+  }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UnknownFunctions {
+  /// Name calls to unknown functions by their address
+  Address,
+  /// Name calls to unknown functions after the function containing the address
+  Containing
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GameVersion {
+  B2628,
+  B2699,
+  B2802
+}
+
+impl From<GameVersion> for OpcodeVersion {
+  fn from(value: GameVersion) -> Self {
+    match value {
+      GameVersion::B2628 => OpcodeVersion::B2628,
+      GameVersion::B2699 => OpcodeVersion::B2699,
+      GameVersion::B2802 => OpcodeVersion::B2802
+    }
+  }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum MinConfidence {
+  Low,
+  Medium,
+  High
+}
+
+impl From<MinConfidence> for Confidence {
+  fn from(value: MinConfidence) -> Self {
+    match value {
+      MinConfidence::Low => Confidence::Low,
+      MinConfidence::Medium => Confidence::Medium,
+      MinConfidence::High => Confidence::High
+    }
+  }
+}
+
+/// Lets the formatters write to files. `fmt::Error` carries no cause, the `io::Error` is kept
+/// aside and returned by [`FmtWriter::write`].
+struct FmtWriter<W> {
+  inner: W,
+  error: Option<io::Error>
+}
+
+impl<W: io::Write> FmtWriter<W> {
+  fn new(inner: W) -> Self {
+    Self { inner, error: None }
+  }
+
+  fn write(&mut self, f: impl FnOnce(&mut Self) -> fmt::Result) -> io::Result<()> {
+    f(self).map_err(|_| {
+      self
+        .error
+        .take()
+        .unwrap_or_else(|| io::Error::other("formatting failed"))
+    })?;
+    self.inner.flush()
+  }
+}
+
+impl<W: io::Write> fmt::Write for FmtWriter<W> {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    self.inner.write_all(s.as_bytes()).map_err(|error| {
+      self.error = Some(error);
+      fmt::Error
+    })
+  }
+}
+
 /// A YSC Decompiler for Grand Theft Auto 5
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
-pub struct Args {
+pub struct Cli {
+  #[command(subcommand)]
+  command: Command
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+  /// Decompile scripts to C++
+  Decompile(Box<DecompileArgs>),
+  /// Disassemble scripts
+  Disasm(DisasmArgs),
+  /// Write the control flow graphs of functions as dot and JSON files
+  Graph(GraphArgs),
+  /// Print the headers of scripts without decompiling them
+  Info(InfoArgs)
+}
+
+#[derive(clap::Args, Clone, Debug)]
+struct InputArgs {
   /// YSC input glob pattern
   #[arg(short, long)]
   input: String,
 
+  /// Remap opcodes for this game version instead of the one derived from the script header
+  #[arg(long, value_enum)]
+  version_override: Option<GameVersion>
+}
+
+impl InputArgs {
+  fn script_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+    Ok(
+      glob(&self.input)?
+        .filter_map(|file| file.ok())
+        .collect::<Vec<_>>()
+    )
+  }
+
+  fn parse(&self, file: &Path) -> anyhow::Result<Script> {
+    Ok(parse_ysc_file_with_version(
+      file,
+      self.version_override.map(Into::into)
+    )?)
+  }
+}
+
+#[derive(clap::Args, Clone, Debug)]
+struct AssemblyArgs {
+  /// Number of bytes to show in disassembly outputs
+  #[arg(short, long, default_value_t = 0)]
+  bytes: usize,
+
+  /// Show addresses in disassembly outputs
+  #[arg(short, long, default_value_t = false)]
+  addresses: bool,
+
+  /// Align the operands in disassembly outputs to a single column
+  #[arg(long, default_value_t = false)]
+  align_operands: bool
+}
+
+impl AssemblyArgs {
+  fn formatter<'s>(
+    &self,
+    disassembly: &[InstructionInfo],
+    script: &'s Script
+  ) -> AssemblyFormatter<'s> {
+    AssemblyFormatter::new(disassembly, self.addresses, self.bytes, &script.strings)
+      .align_operands(self.align_operands)
+  }
+}
+
+#[derive(clap::Args, Debug)]
+struct DisasmArgs {
+  #[command(flatten)]
+  input: InputArgs,
+
   /// Output directory
   #[arg(short, long)]
   output: PathBuf,
 
-  /// crossmap.json file override
-  #[arg(short, long)]
-  xmap: Option<PathBuf>,
+  #[command(flatten)]
+  assembly: AssemblyArgs,
+
+  /// Omit the function banners
+  #[arg(long, default_value_t = false)]
+  no_function_separators: bool,
+
+  /// Start the disassembly with a header describing the script
+  #[arg(long, default_value_t = false)]
+  header: bool,
 
-  /// natives.json file override
+  /// Write the disassembly of every function to its own file in a `functions` directory
+  #[arg(long, default_value_t = false)]
+  split: bool
+}
+
+#[derive(clap::Args, Debug)]
+struct GraphArgs {
+  #[command(flatten)]
+  input: InputArgs,
+
+  /// Output directory
   #[arg(short, long)]
-  natives: Option<PathBuf>,
+  output: PathBuf,
+
+  #[command(flatten)]
+  assembly: AssemblyArgs,
 
   /// A comma separated list of functions to generate function graphs for
   /// The functions should be formatted as a key-value pair indicating the script, and the function index
   /// Example: freemode:123,abigail:10
-  #[arg(short, long, value_delimiter = ',', value_parser = parse_key_val::<String, usize>, verbatim_doc_comment)]
-  graphs: Option<Vec<(String, usize)>>,
+  #[arg(short, long, required = true, value_delimiter = ',', value_parser = parse_key_val::<String, usize>, verbatim_doc_comment)]
+  functions: Vec<(String, usize)>
+}
 
-  /// Enables disassembly output
-  #[arg(short, long, default_value_t = false)]
+#[derive(clap::Args, Debug)]
+struct InfoArgs {
+  #[command(flatten)]
+  input: InputArgs,
+
+  /// Also print opcode and function size statistics for every script and in total
+  #[arg(long, default_value_t = false)]
+  stats: bool,
+
+  /// Print the header of every script as a JSON object on its own line
+  #[arg(long, default_value_t = false, conflicts_with = "stats")]
+  json: bool
+}
+
+#[derive(clap::Args, Debug)]
+struct DecompileArgs {
+  #[command(flatten)]
+  input: InputArgs,
+
+  /// Output directory
+  #[arg(short, long)]
+  output: PathBuf,
+
+  /// Write the decompiled code of all scripts to this file instead of one file per script
+  #[arg(long)]
+  single_file: Option<PathBuf>,
+
+  /// crossmap.json file override, later files take precedence when passed multiple times
+  #[arg(short, long)]
+  xmap: Vec<PathBuf>,
+
+  /// natives.json file override, later files take precedence when passed multiple times
+  #[arg(short, long)]
+  natives: Vec<PathBuf>,
+
+  /// A file with one string per line, used to show hashed switch cases by name
+  #[arg(long)]
+  hash_names: Option<PathBuf>,
+
+  /// A JSON file with known parameter and return types of functions, see `SignatureMap`
+  #[arg(long)]
+  signatures: Option<PathBuf>,
+
+  /// Only output the function with this index and the functions reachable from it
+  #[arg(long)]
+  entrypoint: Option<usize>,
+
+  /// How types that couldn't be inferred are rendered
+  #[arg(long, value_enum, default_value_t = UnknownTypes::Any)]
+  unknown_types: UnknownTypes,
+
+  /// How truncating integer division and modulo are rendered
+  #[arg(long, value_enum, default_value_t = IntegerDivision::Plain)]
+  integer_division: IntegerDivision,
+
+  /// End every statement in the C++ output with the positions of its instructions
+  #[arg(long, default_value_t = false)]
+  annotate_offsets: bool,
+
+  /// Start the statements of every basic block with a comment naming its graph node
+  #[arg(long, default_value_t = false)]
+  block_comments: bool,
+
+  /// Write the inferred types of every function to a JSON file
+  #[arg(long, default_value_t = false)]
+  types_report: bool,
+
+  /// Replace arithmetic on constants with its result
+  #[arg(long, default_value_t = false)]
+  fold_constants: bool,
+
+  /// Follow every function name with a comment holding its location in the bytecode
+  #[arg(long, default_value_t = false)]
+  function_offsets: bool,
+
+  /// Precede native call arguments with the names of their parameters
+  #[arg(long, default_value_t = false)]
+  native_param_names: bool,
+
+  /// Render single bit tests as has_flag(value, flag)
+  #[arg(long, default_value_t = false)]
+  flag_tests: bool,
+
+  /// Wrap lines wider than this many columns at the arguments of calls
+  #[arg(long)]
+  max_line_width: Option<usize>,
+
+  /// Write the struct layouts of globals, merged over all scripts, to globals.h
+  #[arg(long, default_value_t = false)]
+  pretty_globals: bool,
+
+  /// Decompile the input scripts as the parts of one split script, calls that don't land
+  /// on a function of their own script are named after the function of another one
+  #[arg(long, default_value_t = false)]
+  related: bool,
+
+  /// Write the functions calling each native to a JSON file
+  #[arg(long, default_value_t = false)]
+  native_xref: bool,
+
+  /// Write the string literals used by each function to a JSON file
+  #[arg(long, default_value_t = false)]
+  referenced_strings: bool,
+
+  /// Print the places where type inference likely went wrong
+  #[arg(long, default_value_t = false)]
+  type_warnings: bool,
+
+  /// Render primitive types inferred with a lower confidence as unknown types
+  #[arg(long, value_enum)]
+  min_confidence: Option<MinConfidence>,
+
+  /// Inline calls to functions with at most this many statements, 0 disables inlining
+  #[arg(long, default_value_t = 0)]
+  inline_threshold: usize,
+
+  /// Fail functions nested deeper than this instead of risking a stack overflow
+  #[arg(long, default_value_t = DEFAULT_MAX_DEPTH)]
+  max_depth: usize,
+
+  /// Stack slots passed to calls of addresses that aren't the start of a function
+  #[arg(long, default_value_t = 0)]
+  unknown_call_args: usize,
+
+  /// How calls to addresses that aren't the start of a function are named
+  #[arg(long, value_enum, default_value_t = UnknownFunctions::Address)]
+  unknown_functions: UnknownFunctions,
+
+  /// Prefix of calls to unknown functions named by their address, or outside of all
+  /// functions with `--unknown-functions containing`
+  #[arg(long, default_value = "unk_fn")]
+  unknown_function_prefix: String,
+
+  #[command(flatten)]
+  legacy: LegacyArgs
+}
+
+/// The flags of the `disasm`, `graph` and `info` modes from before they became commands of
+/// their own. They're hidden from the help and run the commands after decompiling.
+#[derive(clap::Args, Default, Debug)]
+struct LegacyArgs {
+  #[arg(short, long, hide = true, default_value_t = false)]
   disassemble: bool,
 
-  /// Number of bytes to show in disassembly outputs
-  #[arg(short, long, default_value_t = 0)]
+  #[arg(long, hide = true, default_value_t = false)]
+  split_disassembly: bool,
+
+  #[arg(long, hide = true, default_value_t = false)]
+  disassembly_header: bool,
+
+  #[arg(long, hide = true, default_value_t = false)]
+  no_function_separators: bool,
+
+  #[arg(short, long, hide = true, default_value_t = 0)]
   bytes: usize,
 
-  /// Show addresses in disassembly outputs
-  #[arg(short, long, default_value_t = false)]
-  addresses: bool
+  #[arg(short, long, hide = true, default_value_t = false)]
+  addresses: bool,
+
+  #[arg(long, hide = true, default_value_t = false)]
+  align_operands: bool,
+
+  #[arg(short, long, hide = true, value_delimiter = ',', value_parser = parse_key_val::<String, usize>)]
+  graphs: Option<Vec<(String, usize)>>,
+
+  #[arg(long, hide = true, default_value_t = false)]
+  stats: bool
+}
+
+impl LegacyArgs {
+  fn run(self, input: InputArgs, output: PathBuf) -> anyhow::Result<()> {
+    let assembly = AssemblyArgs {
+      bytes:          self.bytes,
+      addresses:      self.addresses,
+      align_operands: self.align_operands
+    };
+
+    if self.disassemble {
+      eprintln!("warning: --disassemble is deprecated, use the disasm command");
+      disasm(DisasmArgs {
+        input:                  input.clone(),
+        output:                 output.clone(),
+        assembly:               assembly.clone(),
+        no_function_separators: self.no_function_separators,
+        header:                 self.disassembly_header,
+        split:                  self.split_disassembly
+      })?;
+    }
+    if let Some(functions) = self.graphs {
+      eprintln!("warning: --graphs is deprecated, use the graph command");
+      graph(GraphArgs {
+        input: input.clone(),
+        output,
+        assembly,
+        functions
+      })?;
+    }
+    if self.stats {
+      eprintln!("warning: --stats is deprecated, use the info command");
+      info(InfoArgs {
+        input,
+        stats: true,
+        json: false
+      })?;
+    }
+
+    Ok(())
+  }
 }
 
 fn main() -> anyhow::Result<()> {
-  let args = Args::parse();
+  run(Cli::parse_from(with_command(std::env::args_os())))
+}
 
-  let globals = ScriptGlobals::default();
-  let natives = Natives::from_json_file("./resources/natives.json")?;
-  let cross_map = CrossMap::from_json_file("./resources/crossmap.json")?;
+/// Invocations from before the commands started with the flags, they were decompiling.
+fn with_command(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+  let mut args = args.into_iter().collect::<Vec<_>>();
+  let legacy = match args.get(1).and_then(|arg| arg.to_str()) {
+    Some("-h" | "--help" | "-V" | "--version") => false,
+    Some(arg) => arg.starts_with('-'),
+    None => false
+  };
+  if legacy {
+    eprintln!("warning: running without a command is deprecated, use the decompile command");
+    args.insert(1, "decompile".into());
+  }
+  args
+}
 
-  let script_files = glob(&args.input)?
-    .filter_map(|file| file.ok())
-    .collect::<Vec<_>>();
+fn run(cli: Cli) -> anyhow::Result<()> {
+  match cli.command {
+    Command::Decompile(mut args) => {
+      let legacy = std::mem::take(&mut args.legacy);
+      let (input, output) = (args.input.clone(), args.output.clone());
+      decompile(*args)?;
+      legacy.run(input, output)
+    }
+    Command::Disasm(args) => disasm(args),
+    Command::Graph(args) => graph(args),
+    Command::Info(args) => info(args)
+  }
+}
 
-  let pb = ProgressBar::new(script_files.len().try_into().unwrap());
+fn progress_bar(len: usize) -> ProgressBar {
+  let pb = ProgressBar::new(len as u64);
   pb.set_style(
     ProgressStyle::with_template(
       "{spinner:.green} [{elapsed_precise}] [{bar:40.blue}] {pos}/{len} {msg}"
@@ -82,85 +526,709 @@ fn main() -> anyhow::Result<()> {
     .unwrap()
   );
   pb.enable_steady_tick(Duration::from_millis(50));
+  pb
+}
+
+fn disasm(args: DisasmArgs) -> anyhow::Result<()> {
+  let script_files = args.input.script_files()?;
+  let pb = progress_bar(script_files.len());
 
   for file in &script_files {
     pb.set_message("");
 
-    let script = parse_ysc_file(&file)?;
+    let script = args.input.parse(file)?;
 
     pb.set_message(script.header.name.clone());
 
     let disassembly = disassemble(&script.code)?;
+    let assembly_formatter = args.assembly.formatter(&disassembly, &script);
 
     let output_folder = args.output.join(&script.header.name);
-
     fs::create_dir_all(&output_folder)?;
 
-    let assembly_formatter =
-      AssemblyFormatter::new(&disassembly, args.addresses, args.bytes, &script.strings);
+    let mut output = assembly_formatter.format(&disassembly, !args.no_function_separators);
+    if args.header {
+      output.insert_str(0, &assembly_formatter.format_header(&script.header));
+    }
+    let output_file = format!("{}.scasm", script.header.name);
+
+    fs::write(output_folder.join(output_file), output)?;
 
-    if args.disassemble {
-      let disassembly = assembly_formatter.format(&disassembly, true);
-      let output_file = format!("{}.scasm", script.header.name);
+    if args.split {
+      let functions_folder = output_folder.join("functions");
+      fs::create_dir_all(&functions_folder)?;
 
-      fs::write(output_folder.join(output_file), disassembly)?;
+      for function in &get_functions(&disassembly) {
+        let output = assembly_formatter.format(function.instructions, !args.no_function_separators);
+        let output_file = format!("{}.scasm", function.name);
+
+        fs::write(functions_folder.join(output_file), output)?;
+      }
     }
 
-    let statics = ScriptStatics::new(script.header.static_count.try_into().unwrap());
+    pb.inc(1);
+  }
+  pb.finish_with_message(format!("Disassembled {} scripts", script_files.len()));
 
-    let functions = get_functions(&disassembly);
-    let function_map = functions
+  Ok(())
+}
+
+fn graph(args: GraphArgs) -> anyhow::Result<()> {
+  for file in args.input.script_files()? {
+    let script = args.input.parse(&file)?;
+
+    let function_indices = args
+      .functions
       .iter()
-      .map(|f| (f.location, f.clone()))
-      .collect::<HashMap<_, _>>();
+      .filter_map(|(name, function)| (script.header.name == *name).then_some(*function))
+      .collect::<Vec<_>>();
+    if function_indices.is_empty() {
+      continue;
+    }
+
+    let disassembly = disassemble(&script.code)?;
+    let assembly_formatter = args.assembly.formatter(&disassembly, &script);
+    let functions = get_functions(&disassembly);
+
+    let output_folder = args.output.join(&script.header.name);
+    fs::create_dir_all(&output_folder)?;
+
+    for function_index in function_indices {
+      if let Some(function) = functions.get(function_index) {
+        let dot = function.structured_dot_string(&assembly_formatter);
+        let output_file = format!("{}.dot", function.name);
+
+        fs::write(output_folder.join(output_file), dot)?;
+
+        let output_file = format!("{}.cfg.json", function.name);
+
+        fs::write(output_folder.join(output_file), function.graph_json())?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn info(args: InfoArgs) -> anyhow::Result<()> {
+  let mut total_stats = ScriptStats::default();
+
+  for file in args.input.script_files()? {
+    let script = args.input.parse(&file)?;
+    if args.json {
+      println!("{}", serde_json::to_string(&script.summary())?);
+      continue;
+    }
+
+    let header = &script.header;
+
+    println!("{}:", header.name);
+    println!("  hash:            0x{:08X}", header.name_hash);
+    println!("  parameters:      {}", header.parameter_count);
+    println!("  statics:         {}", header.static_count);
+    println!("  globals:         {}", header.global_count);
+    println!("  globals version: {}", header.globals_version);
+    println!("  code size:       {}", script.code.len());
+    println!("  natives:         {}", script.natives.len());
+    println!("  strings:         {}", script.string_count());
+
+    if args.stats {
+      let disassembly = disassemble(&script.code)?;
+      let stats = ScriptStats::collect(&disassembly, &get_functions(&disassembly));
+      println!("{stats}");
+      total_stats.merge(&stats);
+    }
+  }
+
+  if args.stats {
+    println!("total:\n{total_stats}");
+  }
+
+  Ok(())
+}
 
-    if let Some(graphs) = &args.graphs {
-      let functions_to_generate_graphs_for = graphs
+fn decompile(args: DecompileArgs) -> anyhow::Result<()> {
+  let globals = ScriptGlobals::default();
+  let natives = match &args.natives[..] {
+    [] => Natives::from_json_file("./resources/natives.json")?,
+    [first, rest @ ..] => {
+      let mut natives = Natives::from_json_file(first)?;
+      for path in rest {
+        natives.merge(Natives::from_json_file(path)?);
+      }
+      natives
+    }
+  };
+  let cross_map = match &args.xmap[..] {
+    [first, rest @ ..] => {
+      let mut cross_map = CrossMap::from_json_file(first)?;
+      for path in rest {
+        cross_map.merge(CrossMap::from_json_file(path)?);
+      }
+      cross_map
+    }
+    [] if Path::new(DEFAULT_CROSS_MAP).exists() => CrossMap::from_json_file(DEFAULT_CROSS_MAP)?,
+    [] => {
+      eprintln!("warning: {DEFAULT_CROSS_MAP} not found, native hashes will not be translated");
+      CrossMap::identity()
+    }
+  };
+  let hash_names = args
+    .hash_names
+    .as_ref()
+    .map(HashNames::from_text_file)
+    .transpose()?;
+  let signatures = args
+    .signatures
+    .as_ref()
+    .map(SignatureMap::from_json_file)
+    .transpose()?;
+
+  let script_files = args.input.script_files()?;
+  // Related scripts are all parsed up front and kept for decompiling them one by one
+  let related = if args.related {
+    let scripts = MultiScript::new(
+      script_files
         .iter()
-        .filter_map(|(name, function)| (script.header.name == *name).then_some(*function));
+        .map(|file| args.input.parse(file))
+        .collect::<anyhow::Result<Vec<_>>>()?
+    );
+    let names = scripts.function_names()?;
+    Some((scripts, names))
+  } else {
+    None
+  };
 
-      for function_index in functions_to_generate_graphs_for {
-        if let Some(function) = functions.get(function_index) {
-          let dot = function.dot_string(&assembly_formatter);
-          let output_file = format!("{}.dot", function.name);
+  let pb = progress_bar(script_files.len());
 
-          fs::write(output_folder.join(output_file), dot)?;
-        }
+  let mut single_file = args
+    .single_file
+    .as_ref()
+    .map(|path| File::create(path).map(BufWriter::new))
+    .transpose()?;
+
+  for (script_index, file) in script_files.iter().enumerate() {
+    pb.set_message("");
+
+    let parsed;
+    let script = match &related {
+      Some((scripts, _)) => &scripts.scripts[script_index],
+      None => {
+        parsed = args.input.parse(file)?;
+        &parsed
+      }
+    };
+
+    pb.set_message(script.header.name.clone());
+
+    let disassembly = disassemble(&script.code)?;
+
+    // Only created once something is written to it, which is never the case for the
+    // decompiled code with `--single-file`
+    let output_folder = args.output.join(&script.header.name);
+    let write_output = |file_name: String, contents: String| -> io::Result<()> {
+      fs::create_dir_all(&output_folder)?;
+      fs::write(output_folder.join(file_name), contents)
+    };
+
+    let statics = ScriptStatics::with_values(&script.statics);
+
+    let functions = get_functions(&disassembly);
+    if let Some(entrypoint) = args.entrypoint {
+      anyhow::ensure!(
+        entrypoint < functions.len(),
+        "--entrypoint {entrypoint} is out of range, {} has functions 0..{}",
+        script.header.name,
+        functions.len()
+      );
+    }
+    if let Some(signatures) = &signatures {
+      for function in &functions {
+        signatures.apply(&script.header.name, function);
       }
     }
+    for function in functions.iter().filter(|function| function.malformed) {
+      pb.println(format!(
+        "warning: {} in {} has a frame too small for its parameters",
+        function.name, script.header.name
+      ));
+    }
+
+    let function_map = function_map(&functions);
     let data = DecompilerData {
-      statics:   &statics,
-      globals:   &globals,
-      natives:   &natives,
-      cross_map: &cross_map,
-      functions: &function_map
+      statics:    &statics,
+      globals:    &globals,
+      natives:    &natives,
+      cross_map:  &cross_map,
+      hash_names: hash_names.as_ref(),
+      functions:  &function_map
+    };
+
+    let options = DecompileOptions {
+      inline_threshold:  args.inline_threshold,
+      max_depth:         args.max_depth,
+      unknown_call_args: args.unknown_call_args
     };
+    let mut report = decompile_all_with_options(&functions, script, &data, &options);
 
-    let decompiled = functions
+    if args.fold_constants {
+      for function in report
+        .functions
+        .iter_mut()
+        .filter_map(|result| result.as_mut().ok())
+      {
+        function.fold_constants();
+      }
+    }
+
+    let cpp_formatter = CppFormatter::with_options(
+      FormatterData {
+        related: related.as_ref().map(|(_, names)| {
+          RelatedScripts {
+            names,
+            script: script_index
+          }
+        }),
+        ..data.into()
+      },
+      CppFormatterOptions {
+        unknown_types:      args.unknown_types.into(),
+        integer_division:   args.integer_division.into(),
+        annotate_offsets:   args.annotate_offsets,
+        unknown_functions:  match args.unknown_functions {
+          UnknownFunctions::Address => {
+            UnknownFunctionNaming::Address {
+              prefix: args.unknown_function_prefix.clone()
+            }
+          }
+          UnknownFunctions::Containing => {
+            UnknownFunctionNaming::Containing {
+              prefix: args.unknown_function_prefix.clone()
+            }
+          }
+        },
+        block_comments:     args.block_comments,
+        min_confidence:     args.min_confidence.map(Into::into),
+        function_offsets:   args.function_offsets,
+        native_param_names: args.native_param_names,
+        flag_tests:         args.flag_tests,
+        max_line_width:     args.max_line_width,
+        max_depth:          Some(args.max_depth)
+      }
+    );
+
+    let reachable = args
+      .entrypoint
+      .map(|entry| report.reachable_from(&functions, entry));
+
+    let mut out: Box<dyn io::Write + '_> = match &mut single_file {
+      Some(single_file) => {
+        writeln!(single_file, "// ===== {} =====\n", script.header.name)?;
+        Box::new(single_file)
+      }
+      None => {
+        let output_file = format!("{}.cpp", script.header.name);
+
+        fs::create_dir_all(&output_folder)?;
+        Box::new(BufWriter::new(File::create(
+          output_folder.join(output_file)
+        )?))
+      }
+    };
+    let formatted = report
+      .functions
       .iter()
-      .filter_map(|func| {
-        match func.decompile(&script, &data) {
-          Ok(d) => Some(d),
-          Err(_) => None
+      .enumerate()
+      .filter(|(index, _)| {
+        match &reachable {
+          Some(reachable) => reachable.contains(index),
+          None => true
         }
       })
-      .collect::<Vec<_>>();
+      .filter_map(|(_, result)| result.as_ref().ok());
+    FmtWriter::new(&mut out).write(|out| {
+      out.write_str(&cpp_formatter.format_header())?;
+      out.write_str(&cpp_formatter.format_statics())?;
 
-    let cpp_formatter = CppFormatter::new(data);
+      for (index, func) in formatted.enumerate() {
+        if index > 0 {
+          out.write_str("\n")?;
+        }
+        cpp_formatter.format_function_to(func, out)?;
+      }
+      if args.single_file.is_some() {
+        out.write_str("\n")?;
+      }
 
-    let code = decompiled
-      .iter()
-      .map(|func| cpp_formatter.format_function(func))
-      .collect::<Vec<_>>()
-      .join("\n");
+      Ok(())
+    })?;
 
-    let output_file = format!("{}.cpp", script.header.name);
+    if args.type_warnings {
+      for function in report.succeeded() {
+        for warning in function.type_warnings() {
+          pb.println(format!(
+            "warning: {} in {} at 0x{:08X}: {}",
+            function.name, script.header.name, warning.position, warning.message
+          ));
+        }
+      }
+    }
 
-    fs::write(output_folder.join(output_file), code)?;
+    if args.types_report {
+      let report = report
+        .succeeded()
+        .map(|func| func.type_report())
+        .collect::<Vec<_>>();
+      write_output(
+        format!("{}.types.json", script.header.name),
+        serde_json::to_string_pretty(&report)?
+      )?;
+    }
+
+    if args.native_xref {
+      let succeeded = report.succeeded().collect::<Vec<_>>();
+      let xref = build_native_xref(succeeded.iter().copied())
+        .into_iter()
+        .map(|(hash, callers)| {
+          let native = match natives.get_native_resolved(hash, &cross_map) {
+            Some(native) => native.name.clone(),
+            None => format!("0x{hash:016X}")
+          };
+          let callers = callers
+            .into_iter()
+            .map(|index| succeeded[index].name.as_str())
+            .collect::<Vec<_>>();
+          (native, callers)
+        })
+        .collect::<BTreeMap<_, _>>();
+      write_output(
+        format!("{}.natives.json", script.header.name),
+        serde_json::to_string_pretty(&xref)?
+      )?;
+    }
+
+    if args.referenced_strings {
+      let strings = report
+        .succeeded()
+        .map(|func| (func.name.as_str(), func.referenced_strings()))
+        .filter(|(_, strings)| !strings.is_empty())
+        .collect::<BTreeMap<_, _>>();
+      write_output(
+        format!("{}.strings.json", script.header.name),
+        serde_json::to_string_pretty(&strings)?
+      )?;
+    }
 
     pb.inc(1);
   }
   pb.finish_with_message(format!("Decompiled {} scripts", script_files.len()));
 
+  if args.pretty_globals {
+    let no_functions = HashMap::new();
+    let cpp_formatter = CppFormatter::new(FormatterData {
+      globals: Some(&globals),
+      ..FormatterData::new(&natives, &no_functions)
+    });
+
+    fs::write(
+      args.output.join("globals.h"),
+      cpp_formatter.format_globals()
+    )?;
+  }
+
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use gta5_script_decompiler::script::{write_ysc, ScriptInfo};
+
+  use super::*;
+
+  /// A script with two functions.
+  fn script(name: &str) -> Vec<u8> {
+    // ENTER 0 2 ""; CALL 12; LEAVE 0 0; ENTER 0 2 ""; LEAVE 0 0
+    script_with_code(
+      name,
+      vec![
+        0x2D, 0, 2, 0, 0, 0x5D, 12, 0, 0, 0x2E, 0, 0, 0x2D, 0, 2, 0, 0, 0x2E, 0, 0,
+      ]
+    )
+  }
+
+  fn script_with_code(name: &str, code: Vec<u8>) -> Vec<u8> {
+    write_ysc(&Script {
+      header: ScriptInfo {
+        magic:           0xB3A8,
+        name:            name.to_owned(),
+        name_hash:       0,
+        globals_version: 0,
+        parameter_count: 0,
+        static_count:    0,
+        global_count:    0
+      },
+      code,
+      strings: vec![],
+      natives: vec![],
+      statics: vec![]
+    })
+    .unwrap()
+  }
+
+  /// A fresh directory holding `fixture.ysc`, an empty `natives.json` and an empty
+  /// `crossmap.json`.
+  fn fixture(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ysc-decompiler-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    fs::write(dir.join("fixture.ysc"), script("fixture")).unwrap();
+    fs::write(dir.join("natives.json"), "{}").unwrap();
+    fs::write(dir.join("crossmap.json"), "[]").unwrap();
+
+    dir
+  }
+
+  fn run_with(args: &[&str]) -> anyhow::Result<()> {
+    run(Cli::try_parse_from(
+      ["ysc-decompiler"].into_iter().chain(args.iter().copied())
+    )?)
+  }
+
+  #[test]
+  fn disasm_split_writes_every_function() {
+    let dir = fixture("disasm-split");
+    let input = dir.join("*.ysc");
+    let output = dir.join("out");
+
+    run_with(&[
+      "disasm",
+      "-i",
+      input.to_str().unwrap(),
+      "-o",
+      output.to_str().unwrap(),
+      "--split"
+    ])
+    .unwrap();
+
+    let disassembly = fs::read_to_string(output.join("fixture/fixture.scasm")).unwrap();
+    assert_eq!(disassembly.matches("F U N C T I O N").count(), 2);
+    let mut functions = fs::read_dir(output.join("fixture/functions"))
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+      .collect::<Vec<_>>();
+    functions.sort();
+    assert_eq!(functions, vec!["func_0.scasm", "func_1.scasm"]);
+  }
+
+  #[test]
+  fn decompile_single_file_concatenates_scripts() {
+    let dir = fixture("decompile-single-file");
+    fs::write(dir.join("other.ysc"), script("other")).unwrap();
+    let output = dir.join("out");
+    let single_file = dir.join("all.cpp");
+
+    run_with(&[
+      "decompile",
+      "-i",
+      dir.join("*.ysc").to_str().unwrap(),
+      "-o",
+      output.to_str().unwrap(),
+      "-n",
+      dir.join("natives.json").to_str().unwrap(),
+      "-x",
+      dir.join("crossmap.json").to_str().unwrap(),
+      "--single-file",
+      single_file.to_str().unwrap()
+    ])
+    .unwrap();
+
+    let cpp = fs::read_to_string(single_file).unwrap();
+    assert!(cpp.contains("// ===== fixture =====\n"), "{cpp}");
+    assert!(cpp.contains("// ===== other =====\n"), "{cpp}");
+    assert_eq!(cpp.matches("void func_1()").count(), 2, "{cpp}");
+    assert!(!output.join("fixture").exists());
+    assert!(!output.join("other").exists());
+  }
+
+  #[test]
+  fn related_scripts_name_calls_into_each_other() {
+    let dir = fixture("decompile-related");
+    // ENTER 0 2 ""; CALL 20; LEAVE 0 0
+    fs::write(
+      dir.join("fixture.ysc"),
+      script_with_code("parent", vec![0x2D, 0, 2, 0, 0, 0x5D, 20, 0, 0, 0x2E, 0, 0])
+    )
+    .unwrap();
+    // ENTER 0 2 ""; NOP x12; LEAVE 0 0; ENTER 0 2 ""; LEAVE 0 0
+    let mut child = vec![0x2D, 0, 2, 0, 0];
+    child.extend([0; 12]);
+    child.extend([0x2E, 0, 0, 0x2D, 0, 2, 0, 0, 0x2E, 0, 0]);
+    fs::write(dir.join("child.ysc"), script_with_code("child", child)).unwrap();
+    let input = dir.join("*.ysc");
+    let output = dir.join("out");
+    let natives = dir.join("natives.json");
+    let cross_map = dir.join("crossmap.json");
+    let args = [
+      "decompile",
+      "-i",
+      input.to_str().unwrap(),
+      "-o",
+      output.to_str().unwrap(),
+      "-n",
+      natives.to_str().unwrap(),
+      "-x",
+      cross_map.to_str().unwrap()
+    ];
+
+    run_with(&args).unwrap();
+    let cpp = fs::read_to_string(output.join("parent/parent.cpp")).unwrap();
+    assert!(cpp.contains("unk_fn00000014();"), "{cpp}");
+
+    run_with(&[&args[..], &["--related"]].concat()).unwrap();
+    let cpp = fs::read_to_string(output.join("parent/parent.cpp")).unwrap();
+    assert!(cpp.contains("child::func_1();"), "{cpp}");
+  }
+
+  #[test]
+  fn decompile_writes_a_file_per_script() {
+    let dir = fixture("decompile");
+    let output = dir.join("out");
+
+    run_with(&[
+      "decompile",
+      "-i",
+      dir.join("*.ysc").to_str().unwrap(),
+      "-o",
+      output.to_str().unwrap(),
+      "-n",
+      dir.join("natives.json").to_str().unwrap(),
+      "-x",
+      dir.join("crossmap.json").to_str().unwrap()
+    ])
+    .unwrap();
+
+    let cpp = fs::read_to_string(output.join("fixture/fixture.cpp")).unwrap();
+    assert!(cpp.contains("void func_0()"), "{cpp}");
+    assert!(cpp.contains("func_1();"), "{cpp}");
+    assert!(!output.join("fixture/fixture.scasm").exists());
+  }
+
+  #[test]
+  fn graph_writes_the_requested_functions() {
+    let dir = fixture("graph");
+    let output = dir.join("out");
+
+    run_with(&[
+      "graph",
+      "-i",
+      dir.join("*.ysc").to_str().unwrap(),
+      "-o",
+      output.to_str().unwrap(),
+      "-f",
+      "fixture:1,other:0"
+    ])
+    .unwrap();
+
+    assert!(output.join("fixture/func_1.dot").exists());
+    assert!(output.join("fixture/func_1.cfg.json").exists());
+    assert!(!output.join("fixture/func_0.dot").exists());
+    assert!(!output.join("other").exists());
+  }
+
+  #[test]
+  fn info_reads_every_script() {
+    let dir = fixture("info");
+    let input = dir.join("*.ysc");
+
+    run_with(&["info", "-i", input.to_str().unwrap(), "--stats"]).unwrap();
+    run_with(&["info", "-i", input.to_str().unwrap(), "--json"]).unwrap();
+    assert!(run_with(&["info", "-i", input.to_str().unwrap(), "--stats", "--json"]).is_err());
+  }
+
+  #[test]
+  fn invocations_without_a_command_still_decompile() {
+    let dir = fixture("legacy");
+    let input = dir.join("*.ysc");
+    let output = dir.join("out");
+    let natives = dir.join("natives.json");
+    let cross_map = dir.join("crossmap.json");
+
+    let args = with_command(
+      [
+        "ysc-decompiler",
+        "-i",
+        input.to_str().unwrap(),
+        "-o",
+        output.to_str().unwrap(),
+        "-n",
+        natives.to_str().unwrap(),
+        "-x",
+        cross_map.to_str().unwrap(),
+        "-d",
+        "--split-disassembly",
+        "--graphs",
+        "fixture:0",
+        "--stats"
+      ]
+      .map(OsString::from)
+    );
+    run(Cli::try_parse_from(args).unwrap()).unwrap();
+
+    assert!(output.join("fixture/fixture.cpp").exists());
+    assert!(output.join("fixture/fixture.scasm").exists());
+    assert!(output.join("fixture/functions/func_1.scasm").exists());
+    assert!(output.join("fixture/func_0.dot").exists());
+    assert_eq!(
+      with_command(["ysc-decompiler", "--help"].map(OsString::from)).len(),
+      2
+    );
+  }
+
+  #[test]
+  fn entrypoints_past_the_last_function_are_rejected() {
+    let dir = fixture("decompile-entrypoint");
+
+    let error = run_with(&[
+      "decompile",
+      "-i",
+      dir.join("*.ysc").to_str().unwrap(),
+      "-o",
+      dir.join("out").to_str().unwrap(),
+      "-n",
+      dir.join("natives.json").to_str().unwrap(),
+      "-x",
+      dir.join("crossmap.json").to_str().unwrap(),
+      "--entrypoint",
+      "2"
+    ])
+    .unwrap_err();
+
+    assert_eq!(
+      error.to_string(),
+      "--entrypoint 2 is out of range, fixture has functions 0..2"
+    );
+  }
+
+  #[test]
+  fn fmt_writer_keeps_the_io_error() {
+    struct Full;
+
+    impl io::Write for Full {
+      fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::StorageFull, "disk is full"))
+      }
+
+      fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+      }
+    }
+
+    let error = FmtWriter::new(Full)
+      .write(|out| out.write_str("void func_0()"))
+      .unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+    assert_eq!(error.to_string(), "disk is full");
+  }
+}
@@ -0,0 +1,31 @@
+/// Jenkins one-at-a-time hash as used by the game, the input is lowercased first.
+pub fn joaat(input: &str) -> u32 {
+  let mut hash = 0u32;
+  for byte in input.bytes() {
+    hash = hash.wrapping_add(byte.to_ascii_lowercase() as u32);
+    hash = hash.wrapping_add(hash << 10);
+    hash ^= hash >> 6;
+  }
+  hash = hash.wrapping_add(hash << 3);
+  hash ^= hash >> 11;
+  hash.wrapping_add(hash << 15)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn known_game_hashes() {
+    assert_eq!(joaat(""), 0);
+    assert_eq!(joaat("PLAYER_ZERO"), 0x0D7114C9);
+    assert_eq!(joaat("adder"), 0xB779A091);
+    assert_eq!(joaat("WEAPON_PISTOL"), 0x1B06D571);
+  }
+
+  #[test]
+  fn hashes_are_case_insensitive() {
+    assert_eq!(joaat("player_zero"), joaat("PLAYER_ZERO"));
+    assert_eq!(joaat("Adder"), joaat("adder"));
+  }
+}
@@ -1,3 +1,5 @@
 mod bubble_sort;
+mod joaat;
 
 pub use bubble_sort::*;
+pub use joaat::*;
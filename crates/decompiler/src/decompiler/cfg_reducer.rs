@@ -199,28 +199,24 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
   fn reduce_switch(
     &self,
     switch_node: NodeIndex,
-    cases: &[(NodeIndex, &EdgeType)],
+    edges: &[(NodeIndex, &EdgeType)],
     parents: &[FlowType]
   ) -> Result<ControlFlow, NodeReductionError> {
-    let grouped = cases.iter().rev().group_by(|(dest, _)| *dest);
-
-    let mut cases = grouped
-      .into_iter()
-      .map(|(key, group)| {
-        (
-          key,
-          group
-            .map(|(_, e)| {
-              match e {
-                EdgeType::ConditionalFlow => CaseValue::Default,
-                EdgeType::Case(value) => CaseValue::Value(*value),
-                _ => panic!("unexpected switch flow")
-              }
-            })
-            .collect_vec()
-        )
-      })
-      .collect_vec();
+    // Cases sharing a body don't have to be adjacent, so every destination gets exactly
+    // one entry holding all of its values
+    let mut cases: Vec<(NodeIndex, Vec<CaseValue>)> = vec![];
+    for (dest, edge) in edges.iter().rev() {
+      let value = match edge {
+        EdgeType::ConditionalFlow => CaseValue::Default,
+        EdgeType::Case(value) => CaseValue::Value(*value),
+        _ => panic!("unexpected switch flow")
+      };
+
+      match cases.iter_mut().find(|(node, _)| node == dest) {
+        Some((_, values)) => values.push(value),
+        None => cases.push((*dest, vec![value]))
+      }
+    }
 
     try_bubble_sort_by(&mut cases, |(a, _), (b, _)| {
       let a_frontiers_b = self
@@ -519,12 +515,16 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
     parents: &[FlowType]
   ) -> Option<ControlFlow> {
     let mut iter = parents.iter().rev().peekable();
-    while let Some(FlowType::Loop { after, .. } | FlowType::Switch { after, .. }) =
+    let mut innermost = true;
+    while let Some(flow @ (FlowType::Loop { after, .. } | FlowType::Switch { after, .. })) =
       iter.find(|flow| matches!(flow, FlowType::Loop { .. } | FlowType::Switch { .. }))
     {
+      let flow = *flow;
       let mut after = *after;
 
-      while let Some(next) = iter.peek() {
+      // Without a node of its own, breaking out of the construct goes wherever the
+      // enclosing one continues
+      while let Some(next) = iter.peek().filter(|_| after.is_none()) {
         match next {
           FlowType::Loop { node, .. } => {
             after = Some(*node);
@@ -546,9 +546,12 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
       if after.is_some() && after.unwrap() == target {
         return Some(ControlFlow::Break {
           node,
-          breaks: target
+          breaks: target,
+          outer: (!innermost).then_some(flow)
         });
       }
+
+      innermost = false;
     }
 
     None
@@ -566,6 +569,7 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
 
     let mut loop_node = None;
     let mut after_node = None;
+    let mut innermost = true;
 
     for parent in parents.iter().rev() {
       match parent {
@@ -582,6 +586,7 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
             break;
           } else {
             after_node.get_or_insert(*parent_node);
+            innermost = false;
           }
         }
         FlowType::Switch {
@@ -599,7 +604,8 @@ impl<'g, 'i, 'b> CfgReducer<'g, 'i, 'b> {
     after_node.and(loop_node.map(|loop_node| {
       ControlFlow::Continue {
         node,
-        continues: loop_node
+        continues: loop_node,
+        outer: !innermost
       }
     }))
   }
@@ -34,11 +34,15 @@ pub enum ControlFlow {
   },
   Break {
     node:   NodeIndex,
-    breaks: NodeIndex
+    breaks: NodeIndex,
+    /// The loop or switch that is broken out of, if it isn't the innermost one
+    outer:  Option<FlowType>
   },
   Continue {
     node:      NodeIndex,
-    continues: NodeIndex
+    continues: NodeIndex,
+    /// Whether `continues` isn't the innermost loop
+    outer:     bool
   },
   Switch {
     node:  NodeIndex,
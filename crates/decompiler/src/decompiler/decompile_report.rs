@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{disassembler::InstructionInfo, script::ScriptTables};
+
+use super::{
+  cfg_reducer::NodeReductionError, decompiled::DecompiledFunction, function_map, get_functions,
+  inline_functions, propagate_types, stack::InvalidStackError, DecompileOptions, DecompilerData,
+  DefaultLowering, Function
+};
+
+#[derive(Debug, Error)]
+pub enum DecompileError {
+  #[error("{source}")]
+  InvalidStack {
+    #[from]
+    source: InvalidStackError
+  },
+
+  #[error("{source}")]
+  ControlFlow {
+    #[from]
+    source: NodeReductionError
+  },
+
+  #[error("Nested at least {depth} levels deep, the maximum is {max_depth}")]
+  TooDeep { depth: usize, max_depth: usize }
+}
+
+#[derive(Debug, Error)]
+#[error("Failed to decompile {name} at 0x{location:08X}: {source}")]
+pub struct FunctionDecompileError {
+  pub name:     String,
+  pub location: usize,
+  #[source]
+  pub source:   DecompileError
+}
+
+/// The result of decompiling all functions of a script, one entry per function.
+pub struct DecompileReport<'input, 'bytes> {
+  pub functions: Vec<Result<DecompiledFunction<'input, 'bytes>, FunctionDecompileError>>
+}
+
+impl<'input, 'bytes> DecompileReport<'input, 'bytes> {
+  pub fn succeeded(&self) -> impl Iterator<Item = &DecompiledFunction<'input, 'bytes>> {
+    self
+      .functions
+      .iter()
+      .filter_map(|result| result.as_ref().ok())
+  }
+
+  pub fn errors(&self) -> impl Iterator<Item = &FunctionDecompileError> {
+    self
+      .functions
+      .iter()
+      .filter_map(|result| result.as_ref().err())
+  }
+
+  /// Indices of all functions transitively called by the function at index `entry`,
+  /// including `entry` itself. `functions` are the functions this report was created
+  /// from. Calls made by functions that failed to decompile are not followed.
+  pub fn reachable_from(&self, functions: &[Function], entry: usize) -> HashSet<usize> {
+    let indices = functions
+      .iter()
+      .enumerate()
+      .map(|(index, function)| (function.location, index))
+      .collect::<HashMap<_, _>>();
+
+    let mut reachable = HashSet::new();
+    let mut queue = vec![entry];
+
+    while let Some(index) = queue.pop() {
+      if index >= self.functions.len() || !reachable.insert(index) {
+        continue;
+      }
+
+      if let Ok(function) = &self.functions[index] {
+        queue.extend(
+          function
+            .called_functions()
+            .iter()
+            .filter_map(|address| indices.get(address).copied())
+        );
+      }
+    }
+
+    reachable
+  }
+}
+
+/// Decompiles all `functions` and propagates types between the ones that succeeded.
+pub fn decompile_all<'input: 'bytes, 'bytes>(
+  functions: &[Function<'input, 'bytes>],
+  script: impl Into<ScriptTables<'input>>,
+  data: &DecompilerData
+) -> DecompileReport<'input, 'bytes> {
+  decompile_all_with_options(functions, script, data, &DecompileOptions::default())
+}
+
+/// Like [`decompile_all`], with the post processing described by `options` applied after
+/// types have been propagated.
+pub fn decompile_all_with_options<'input: 'bytes, 'bytes>(
+  functions: &[Function<'input, 'bytes>],
+  script: impl Into<ScriptTables<'input>>,
+  data: &DecompilerData,
+  options: &DecompileOptions
+) -> DecompileReport<'input, 'bytes> {
+  let script = script.into();
+  let decompiled = functions
+    .iter()
+    .map(|function| {
+      function
+        .decompile_with_options(script, data, &DefaultLowering, options)
+        .map_err(|source| {
+          FunctionDecompileError {
+            name: function.name.clone(),
+            location: function.location,
+            source
+          }
+        })
+    })
+    .collect::<Vec<_>>();
+
+  let mut report = DecompileReport {
+    functions: decompiled
+  };
+  propagate_types(&report.succeeded().collect::<Vec<_>>());
+  inline_functions(&mut report, functions, options);
+  report
+}
+
+/// Splits already disassembled `instructions` into functions and decompiles them. The
+/// `functions` of `data` are replaced with the ones found in `instructions`.
+pub fn decompile_instructions<'input: 'bytes, 'bytes>(
+  instructions: &'input [InstructionInfo<'bytes>],
+  tables: ScriptTables<'input>,
+  data: &DecompilerData
+) -> DecompileReport<'input, 'bytes> {
+  let functions = get_functions(instructions);
+  let functions_by_location = function_map(&functions);
+
+  let data = DecompilerData {
+    statics:    data.statics,
+    globals:    data.globals,
+    natives:    data.natives,
+    cross_map:  data.cross_map,
+    hash_names: data.hash_names,
+    functions:  &functions_by_location
+  };
+
+  decompile_all(&functions, tables, &data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::DecompilerDataBuilder,
+    disassembler::{disassemble, opcodes::Opcode, Instruction},
+    resources::{CrossMap, Natives},
+    test_utils::{Asm, NO_TABLES}
+  };
+
+  #[test]
+  fn report_keeps_functions_next_to_failures() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      // Drops a value that was never pushed
+      .enter(0, 2)
+      .op(Opcode::Drop)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+    let data = builder.build();
+
+    let report = decompile_all(&functions, NO_TABLES, &data);
+
+    assert_eq!(report.functions.len(), 2);
+    assert_eq!(
+      report
+        .succeeded()
+        .map(|f| f.name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["func_0"]
+    );
+    let errors = report.errors().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].name, "func_1");
+    assert_eq!(errors[0].location, 8);
+    assert!(matches!(
+      errors[0].source,
+      DecompileError::InvalidStack { .. }
+    ));
+  }
+
+  #[test]
+  fn decompiles_patched_instructions_without_a_script() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConst0)
+      .op(Opcode::String)
+      .leave(0, 1)
+      .build();
+    let mut instructions = disassemble(&code).unwrap();
+    instructions[1].instruction = Instruction::PushConstU8 { c1: 6 };
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity());
+
+    let report = decompile_instructions(
+      &instructions,
+      ScriptTables {
+        strings: b"first\0second\0",
+        natives: &[]
+      },
+      &builder.build()
+    );
+
+    let functions = report.succeeded().collect::<Vec<_>>();
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0].referenced_strings(), vec!["second"]);
+  }
+
+  #[test]
+  fn reachable_functions_skip_unreachable_ones() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::FunctionCall)
+      .u24(20)
+      .leave(0, 0)
+      // 12, never called
+      .enter(0, 2)
+      .leave(0, 0)
+      // 20
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+
+    let report = decompile_all(&functions, NO_TABLES, &builder.build());
+
+    let mut reachable = report
+      .reachable_from(&functions, 0)
+      .into_iter()
+      .collect::<Vec<_>>();
+    reachable.sort();
+    assert_eq!(reachable, vec![0, 2]);
+  }
+}
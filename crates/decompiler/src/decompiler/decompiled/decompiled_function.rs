@@ -1,14 +1,259 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+  cell::RefCell,
+  collections::{HashMap, HashSet},
+  ops::Range,
+  rc::Rc
+};
 
-use crate::decompiler::LinkedValueType;
+use crate::decompiler::{LinkedValueType, StackEntry, StackEntryInfo};
 
-use super::StatementInfo;
+use super::{Statement, StatementInfo};
 
 #[derive(Debug)]
 pub struct DecompiledFunction<'input, 'bytes> {
   pub name:       String,
+  /// Position of the function's `ENTER` instruction
+  pub location:   usize,
   pub params:     Vec<Rc<RefCell<LinkedValueType>>>,
   pub locals:     Vec<Rc<RefCell<LinkedValueType>>>,
   pub returns:    Option<Rc<RefCell<LinkedValueType>>>,
-  pub statements: Vec<StatementInfo<'input, 'bytes>>
+  pub statements: Vec<StatementInfo<'input, 'bytes>>,
+  /// The instruction positions of each basic block, indexed by its node in the function graph
+  pub blocks:     Vec<Range<usize>>
+}
+
+impl<'input, 'bytes> DecompiledFunction<'input, 'bytes> {
+  /// The node index of the basic block containing the instruction at `pos`.
+  pub fn block_at(&self, pos: usize) -> Option<usize> {
+    self.blocks.iter().position(|block| block.contains(&pos))
+  }
+
+  /// Addresses of all functions called by this function.
+  pub fn called_functions(&self) -> HashSet<usize> {
+    let mut result = HashSet::new();
+    self.visit(|visited| {
+      match visited {
+        Visited::Statement(Statement::FunctionCall {
+          function_address, ..
+        })
+        | Visited::Entry(StackEntry::FunctionCallResult {
+          function_address, ..
+        }) => {
+          result.insert(*function_address);
+        }
+        _ => {}
+      }
+    });
+    result
+  }
+
+  /// Hashes of all natives called by this function.
+  pub fn called_natives(&self) -> HashSet<u64> {
+    let mut result = HashSet::new();
+    self.visit(|visited| {
+      match visited {
+        Visited::Statement(Statement::NativeCall { native_hash, .. })
+        | Visited::Entry(StackEntry::NativeCallResult { native_hash, .. }) => {
+          result.insert(*native_hash);
+        }
+        _ => {}
+      }
+    });
+    result
+  }
+
+  /// All string literals used by this function in the order they first appear, without
+  /// duplicates.
+  pub fn referenced_strings(&self) -> Vec<&'input str> {
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    self.visit(|visited| {
+      if let Visited::Entry(StackEntry::String(string)) = visited {
+        if seen.insert(*string) {
+          result.push(*string);
+        }
+      }
+    });
+    result
+  }
+
+  /// Walks all statements and the stack entries used by them, including nested ones.
+  pub fn visit(&self, mut cb: impl FnMut(Visited<'_, 'input, 'bytes>)) {
+    let mut statements = self.statements.iter().rev().collect::<Vec<_>>();
+    while let Some(info) = statements.pop() {
+      cb(Visited::Statement(&info.statement));
+
+      let mut entries: Vec<&StackEntryInfo> = info.statement.entries();
+      while let Some(entry) = entries.pop() {
+        cb(Visited::Entry(&entry.entry));
+        entries.extend(entry.entry.children());
+      }
+
+      for body in info.statement.bodies().into_iter().rev() {
+        statements.extend(body.iter().rev());
+      }
+    }
+  }
+
+  /// Calls `cb` with every entry used directly by a statement, including statements in
+  /// nested blocks. Children of the entries are left to `cb`.
+  pub fn for_each_entry_mut(&mut self, mut cb: impl FnMut(&mut StackEntryInfo<'input>)) {
+    let mut blocks = vec![&mut self.statements[..]];
+    while let Some(block) = blocks.pop() {
+      for info in block {
+        for entry in info.statement.entries_mut() {
+          cb(entry);
+        }
+        blocks.extend(
+          info
+            .statement
+            .bodies_mut()
+            .into_iter()
+            .map(|body| &mut body[..])
+        );
+      }
+    }
+  }
+
+  /// Replaces arithmetic on literals with its result, see [`StackEntryInfo::fold_constants`].
+  pub fn fold_constants(&mut self) {
+    self.for_each_entry_mut(|entry| entry.fold_constants());
+  }
+}
+
+/// Maps the hash of every called native to the indices of the `functions` calling it, in
+/// ascending order.
+pub fn build_native_xref<'f, 'bytes: 'input, 'input: 'f>(
+  functions: impl IntoIterator<Item = &'f DecompiledFunction<'input, 'bytes>>
+) -> HashMap<u64, Vec<usize>> {
+  let mut xref = HashMap::<u64, Vec<usize>>::new();
+  for (index, function) in functions.into_iter().enumerate() {
+    for native_hash in function.called_natives() {
+      xref.entry(native_hash).or_default().push(index);
+    }
+  }
+  xref
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Visited<'a, 'input, 'bytes> {
+  Statement(&'a Statement<'input, 'bytes>),
+  Entry(&'a StackEntry<'input>)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    disassembler::opcodes::Opcode,
+    formatters::CppFormatterOptions,
+    script::ScriptTables,
+    test_utils::{decompile_with, Asm, NO_TABLES}
+  };
+
+  #[test]
+  fn collects_called_functions_and_natives() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::FunctionCall)
+      .u24(16)
+      .op(Opcode::NativeCall)
+      .u8(0)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      // 16
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+
+    let (functions, natives) = decompile_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x4EDE34FBADD967A6]
+      },
+      "{}",
+      CppFormatterOptions::default(),
+      |_, functions| {
+        (
+          functions[0].called_functions(),
+          functions[0].called_natives()
+        )
+      }
+    );
+
+    assert_eq!(functions.into_iter().collect::<Vec<_>>(), vec![16]);
+    assert_eq!(
+      natives.into_iter().collect::<Vec<_>>(),
+      vec![0x4EDE34FBADD967A6]
+    );
+  }
+
+  #[test]
+  fn native_xrefs_list_every_caller() {
+    let native_call = |asm: Asm, index: u8| asm.op(Opcode::NativeCall).u8(0).u8(0).u8(index);
+    let code = native_call(Asm::default().enter(0, 2), 0).leave(0, 0);
+    let code = native_call(native_call(code.enter(0, 2), 1), 0).leave(0, 0);
+    let code = code.enter(0, 2).leave(0, 0).build();
+
+    let mut xref = decompile_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0xA, 0xB]
+      },
+      "{}",
+      CppFormatterOptions::default(),
+      |_, functions| build_native_xref(functions.iter().copied())
+    );
+    for callers in xref.values_mut() {
+      callers.sort();
+    }
+
+    assert_eq!(xref.len(), 2);
+    assert_eq!(xref[&0xA], vec![0, 1]);
+    assert_eq!(xref[&0xB], vec![1]);
+  }
+
+  #[test]
+  fn referenced_strings_are_listed_once_in_order() {
+    let code = Asm::default()
+      .enter(0, 5)
+      .op(Opcode::PushConstU8)
+      .u8(6)
+      .op(Opcode::String)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::PushConst0)
+      .op(Opcode::String)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .op(Opcode::PushConstU8)
+      .u8(6)
+      .op(Opcode::String)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .leave(0, 0)
+      .build();
+
+    let strings = decompile_with(
+      &code,
+      ScriptTables {
+        strings: b"first\0second\0",
+        ..NO_TABLES
+      },
+      "{}",
+      CppFormatterOptions::default(),
+      |_, functions| {
+        functions[0]
+          .referenced_strings()
+          .into_iter()
+          .map(str::to_owned)
+          .collect::<Vec<_>>()
+      }
+    );
+
+    assert_eq!(strings, vec!["second", "first"]);
+  }
 }
@@ -1,5 +1,9 @@
 mod decompiled_function;
 mod statement;
+mod type_lint;
+mod type_report;
 
 pub use decompiled_function::*;
 pub use statement::*;
+pub use type_lint::*;
+pub use type_report::*;
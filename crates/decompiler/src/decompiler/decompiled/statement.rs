@@ -1,9 +1,11 @@
+use std::ops::Range;
+
 use crate::{
   decompiler::{control_flow::CaseValue, StackEntryInfo},
   disassembler::InstructionInfo
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement<'i, 'b> {
   Nop,
   Assign {
@@ -14,7 +16,9 @@ pub enum Statement<'i, 'b> {
     values: Vec<StackEntryInfo<'i>>
   },
   Throw {
-    value: StackEntryInfo<'i>
+    value:    StackEntryInfo<'i>,
+    /// No catch handler exists in the script, the throw terminates it
+    uncaught: bool
   },
   FunctionCall {
     args:             Vec<StackEntryInfo<'i>>,
@@ -67,12 +71,211 @@ pub enum Statement<'i, 'b> {
     buffer_size: StackEntryInfo<'i>,
     count:       usize
   },
-  Break,
-  Continue
+  Break {
+    /// The loop or switch that is broken out of, if it isn't the innermost one
+    outer: Option<OuterTarget>
+  },
+  Continue {
+    /// The position of the continued loop, if it isn't the innermost one
+    outer: Option<usize>
+  },
+  TryCatch {
+    body:      Vec<StatementInfo<'i, 'b>>,
+    handler:   Vec<StatementInfo<'i, 'b>>,
+    /// Where the thrown value is stored, if it is stored at all
+    error_var: Option<StackEntryInfo<'i>>
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OuterTarget {
+  Loop(usize),
+  Switch(usize)
 }
 
-#[derive(Debug)]
+impl<'i, 'b> Statement<'i, 'b> {
+  /// The stack entries used directly by this statement, excluding nested statements.
+  pub fn entries(&self) -> Vec<&StackEntryInfo<'i>> {
+    match self {
+      Statement::Nop | Statement::Break { .. } | Statement::Continue { .. } => vec![],
+      Statement::Assign {
+        destination,
+        source
+      } => vec![destination, source],
+      Statement::Return { values: entries }
+      | Statement::FunctionCall { args: entries, .. }
+      | Statement::NativeCall { args: entries, .. } => entries.iter().collect(),
+      Statement::Throw { value, .. } => vec![value],
+      Statement::If { condition, .. }
+      | Statement::IfElse { condition, .. }
+      | Statement::WhileLoop { condition, .. }
+      | Statement::Switch { condition, .. } => vec![condition],
+      Statement::TryCatch { error_var, .. } => error_var.iter().collect(),
+      Statement::StringCopy {
+        destination,
+        string: value,
+        ..
+      }
+      | Statement::IntToString {
+        destination,
+        int: value,
+        ..
+      }
+      | Statement::StringConcat {
+        destination,
+        string: value,
+        ..
+      }
+      | Statement::StringIntConcat {
+        destination,
+        int: value,
+        ..
+      } => vec![destination, value],
+      Statement::MemCopy {
+        destination,
+        source,
+        buffer_size,
+        ..
+      } => {
+        let mut entries = vec![destination, buffer_size];
+        entries.extend(source);
+        entries
+      }
+    }
+  }
+
+  /// The statement blocks nested in this statement.
+  pub fn bodies(&self) -> Vec<&[StatementInfo<'i, 'b>]> {
+    match self {
+      Statement::If { then, .. } => vec![then],
+      Statement::IfElse { then, els, .. } => vec![then, els],
+      Statement::WhileLoop { body, .. } => vec![body],
+      Statement::Switch { cases, .. } => cases.iter().map(|(body, _)| &body[..]).collect(),
+      Statement::TryCatch { body, handler, .. } => vec![body, handler],
+      _ => vec![]
+    }
+  }
+
+  /// Mutable version of [`Self::entries`].
+  pub fn entries_mut(&mut self) -> Vec<&mut StackEntryInfo<'i>> {
+    match self {
+      Statement::Nop | Statement::Break { .. } | Statement::Continue { .. } => vec![],
+      Statement::Assign {
+        destination,
+        source
+      } => vec![destination, source],
+      Statement::Return { values: entries }
+      | Statement::FunctionCall { args: entries, .. }
+      | Statement::NativeCall { args: entries, .. } => entries.iter_mut().collect(),
+      Statement::Throw { value, .. } => vec![value],
+      Statement::If { condition, .. }
+      | Statement::IfElse { condition, .. }
+      | Statement::WhileLoop { condition, .. }
+      | Statement::Switch { condition, .. } => vec![condition],
+      Statement::TryCatch { error_var, .. } => error_var.iter_mut().collect(),
+      Statement::StringCopy {
+        destination,
+        string: value,
+        ..
+      }
+      | Statement::IntToString {
+        destination,
+        int: value,
+        ..
+      }
+      | Statement::StringConcat {
+        destination,
+        string: value,
+        ..
+      }
+      | Statement::StringIntConcat {
+        destination,
+        int: value,
+        ..
+      } => vec![destination, value],
+      Statement::MemCopy {
+        destination,
+        source,
+        buffer_size,
+        ..
+      } => {
+        let mut entries = vec![destination, buffer_size];
+        entries.extend(source);
+        entries
+      }
+    }
+  }
+
+  /// Mutable version of [`Self::bodies`].
+  pub fn bodies_mut(&mut self) -> Vec<&mut Vec<StatementInfo<'i, 'b>>> {
+    match self {
+      Statement::If { then, .. } => vec![then],
+      Statement::IfElse { then, els, .. } => vec![then, els],
+      Statement::WhileLoop { body, .. } => vec![body],
+      Statement::Switch { cases, .. } => cases.iter_mut().map(|(body, _)| body).collect(),
+      Statement::TryCatch { body, handler, .. } => vec![body, handler],
+      _ => vec![]
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct StatementInfo<'input, 'bytes> {
   pub instructions: &'input [InstructionInfo<'bytes>],
   pub statement:    Statement<'input, 'bytes>
 }
+
+impl<'input, 'bytes> StatementInfo<'input, 'bytes> {
+  /// The bytecode range of the statement's instructions, empty when it has none.
+  pub fn span(&self) -> Range<usize> {
+    match (self.instructions.first(), self.instructions.last()) {
+      (Some(first), Some(last)) => first.pos..last.pos + last.bytes.len(),
+      _ => 0..0
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    disassembler::opcodes::Opcode,
+    formatters::CppFormatterOptions,
+    test_utils::{decompile_with, Asm, NO_TABLES}
+  };
+
+  #[test]
+  fn spans_cover_the_bytes_of_the_instructions() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::PushConstU8)
+      .u8(7)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .build();
+
+    let (span, positions) = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      CppFormatterOptions::default(),
+      |_, functions| {
+        let assignment = &functions[0].statements[0];
+        assert!(matches!(assignment.statement, Statement::Assign { .. }));
+        (
+          assignment.span(),
+          assignment
+            .instructions
+            .iter()
+            .map(|info| info.pos)
+            .collect::<Vec<_>>()
+        )
+      }
+    );
+
+    // LOCAL_U8_STORE 2
+    assert_eq!(positions, vec![7]);
+    assert_eq!(span, 7..9);
+  }
+}
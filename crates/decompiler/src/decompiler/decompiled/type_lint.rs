@@ -0,0 +1,146 @@
+use serde::Serialize;
+
+use crate::decompiler::{Primitives, StackEntry, StackEntryInfo, ValueType};
+
+use super::DecompiledFunction;
+
+/// Struct fields past this index are more likely a misread offset than a real field.
+const SUSPICIOUS_FIELD: usize = 0x400;
+
+/// A place where type inference likely went wrong, `position` is the position of the
+/// first instruction of the statement it was found in.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeWarning {
+  pub position: usize,
+  pub message:  String
+}
+
+impl<'input, 'bytes> DecompiledFunction<'input, 'bytes> {
+  pub fn type_warnings(&self) -> Vec<TypeWarning> {
+    let mut warnings = vec![];
+
+    let mut statements = self.statements.iter().collect::<Vec<_>>();
+    while let Some(info) = statements.pop() {
+      let position = info
+        .instructions
+        .first()
+        .map(|instruction| instruction.pos)
+        .unwrap_or_default();
+
+      let mut entries = info.statement.entries();
+      while let Some(entry) = entries.pop() {
+        if let Some(message) = lint_entry(entry) {
+          warnings.push(TypeWarning { position, message });
+        }
+        entries.extend(entry.entry.children());
+      }
+
+      for body in info.statement.bodies() {
+        statements.extend(body);
+      }
+    }
+
+    warnings.sort_by_key(|warning| warning.position);
+    warnings
+  }
+}
+
+fn lint_entry(entry: &StackEntryInfo) -> Option<String> {
+  match &entry.entry {
+    StackEntry::BinaryOperator { lhs, rhs, .. }
+      if matches!(primitive(entry), Some(Primitives::Float))
+        && (matches!(primitive(lhs), Some(Primitives::Unknown))
+          || matches!(primitive(rhs), Some(Primitives::Unknown))) =>
+    {
+      Some("value of unknown type used in float arithmetic".to_owned())
+    }
+    StackEntry::StructField { field, .. } if *field >= SUSPICIOUS_FIELD => {
+      Some(format!("access to struct field {field}"))
+    }
+    StackEntry::Offset { source, offset } => {
+      match offset.entry {
+        // Constant offsets are fields of the struct `source` points to
+        StackEntry::Int(field) => {
+          (field >= SUSPICIOUS_FIELD as i64).then(|| format!("access to struct field {field}"))
+        }
+        _ => {
+          let target = match source.ty.borrow().get_concrete().ty {
+            ValueType::Ref(target) => target.borrow().get_concrete().ty,
+            ty => ty
+          };
+          matches!(target, ValueType::Primitive(_))
+            .then(|| "dynamic offset into a primitive".to_owned())
+        }
+      }
+    }
+    _ => None
+  }
+}
+
+fn primitive(entry: &StackEntryInfo) -> Option<Primitives> {
+  match entry.ty.borrow().get_concrete().ty {
+    ValueType::Primitive(primitive) => Some(primitive),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    disassembler::opcodes::Opcode,
+    test_utils::{decompile_with, Asm, NO_TABLES}
+  };
+
+  fn messages(code: &[u8]) -> Vec<(usize, String)> {
+    decompile_with(code, NO_TABLES, "{}", Default::default(), |_, functions| {
+      functions[0]
+        .type_warnings()
+        .into_iter()
+        .map(|warning| (warning.position, warning.message))
+        .collect()
+    })
+  }
+
+  #[test]
+  fn far_struct_fields_are_flagged() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::OffsetS16Load)
+      .u16(0x500)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(1, 0)
+      .build();
+
+    assert_eq!(
+      messages(&code),
+      vec![(10, "access to struct field 1280".to_owned())]
+    );
+  }
+
+  #[test]
+  fn dynamic_offsets_into_primitives_are_flagged() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::LocalU8)
+      .u8(3)
+      .op(Opcode::Offset)
+      .op(Opcode::Load)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(1, 0)
+      .build();
+
+    assert_eq!(
+      messages(&code),
+      vec![(14, "dynamic offset into a primitive".to_owned())]
+    );
+  }
+}
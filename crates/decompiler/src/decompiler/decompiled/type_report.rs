@@ -0,0 +1,186 @@
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::decompiler::{Confidence, LinkedValueType, Primitives, ValueType};
+
+use super::DecompiledFunction;
+
+/// The inferred types of a function's parameters, locals and return value.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeReport {
+  pub name:    String,
+  pub params:  Vec<ReportedType>,
+  pub locals:  Vec<ReportedType>,
+  pub returns: Option<ReportedType>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportedType {
+  pub ty:         String,
+  pub confidence: Confidence
+}
+
+impl ReportedType {
+  fn new(ty: &LinkedValueType) -> Self {
+    let ty = ty.get_concrete();
+    Self {
+      ty:         describe(&ty.ty),
+      confidence: ty.confidence
+    }
+  }
+}
+
+impl<'input, 'bytes> DecompiledFunction<'input, 'bytes> {
+  pub fn type_report(&self) -> TypeReport {
+    TypeReport {
+      name:    self.name.clone(),
+      params:  self
+        .params
+        .iter()
+        .map(|ty| ReportedType::new(&ty.borrow()))
+        .collect(),
+      locals:  self
+        .locals
+        .iter()
+        .map(|ty| ReportedType::new(&ty.borrow()))
+        .collect(),
+      returns: self
+        .returns
+        .as_ref()
+        .map(|ty| ReportedType::new(&ty.borrow()))
+    }
+  }
+}
+
+fn describe(ty: &ValueType) -> String {
+  match ty {
+    ValueType::Struct { fields } => {
+      format!(
+        "struct<{}>",
+        fields
+          .iter()
+          .map(|field| describe(&field.borrow().get_concrete().ty))
+          .join(", ")
+      )
+    }
+    ValueType::Array { item_type } => {
+      format!("{}[]", describe(&item_type.borrow().get_concrete().ty))
+    }
+    ValueType::Function { .. } => "function".to_owned(),
+    ValueType::Primitive(primitive) => {
+      match primitive {
+        Primitives::Float => "float",
+        Primitives::Int => "int",
+        Primitives::String => "string",
+        Primitives::Bool => "bool",
+        Primitives::Unknown => "unknown"
+      }
+      .to_owned()
+    }
+    ValueType::Ref(ty) => format!("{}*", describe(&ty.borrow().get_concrete().ty))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    decompiler::Confidence,
+    disassembler::opcodes::Opcode,
+    test_utils::{decompile_with, Asm, NO_TABLES}
+  };
+
+  #[test]
+  fn float_arithmetic_is_reported_with_high_confidence() {
+    let code = Asm::default()
+      .enter(2, 5)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::LocalU8Load)
+      .u8(1)
+      .op(Opcode::FloatAdd)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .op(Opcode::LocalU8Load)
+      .u8(4)
+      .leave(2, 1)
+      .build();
+
+    let report = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      Default::default(),
+      |_, functions| functions[0].type_report()
+    );
+
+    for ty in report
+      .params
+      .iter()
+      .chain(&report.locals)
+      .chain(&report.returns)
+    {
+      assert_eq!(ty.ty, "float");
+      assert_eq!(ty.confidence, Confidence::High);
+    }
+    assert_eq!((report.params.len(), report.locals.len()), (2, 1));
+    let json = serde_json::to_string(&report).unwrap();
+    assert!(
+      json.contains(r#""returns":{"ty":"float","confidence":"High"}"#),
+      "{json}"
+    );
+  }
+
+  #[test]
+  fn struct_array_items_are_sized_structs() {
+    let code = Asm::default()
+      .enter(2, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(1)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::ArrayU8)
+      .u8(3)
+      .op(Opcode::OffsetU8Load)
+      .u8(2)
+      .leave(2, 1)
+      .build();
+
+    let report = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      Default::default(),
+      |_, functions| functions[0].type_report()
+    );
+
+    assert_eq!(report.params[0].ty, "struct<unknown, unknown, unknown>[]");
+  }
+
+  #[test]
+  fn negative_offsets_do_not_grow_structs() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::OffsetS16Load)
+      .u16((-2i16) as u16)
+      .leave(1, 1)
+      .build();
+
+    let (cpp, report) = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      Default::default(),
+      |formatter, functions| {
+        (
+          formatter.format_function(functions[0]),
+          functions[0].type_report()
+        )
+      }
+    );
+
+    assert!(cpp.contains("return *(parameter_0 - 2);"), "{cpp}");
+    assert!(!report.params[0].ty.contains("struct"), "{report:?}");
+  }
+}
@@ -1,14 +1,229 @@
 use std::collections::HashMap;
 
-use crate::resources::{CrossMap, Natives};
+use crate::{
+  resources::{CrossMap, HashNames, NativeInfo, Natives},
+  script::ScriptTables
+};
 
 use super::{Function, ScriptGlobals, ScriptStatics};
 
+/// Functions by their location, borrowed from the list they were found in.
+pub type FunctionMap<'f, 'i, 'b> = HashMap<usize, &'f Function<'i, 'b>>;
+
 #[derive(Clone, Copy)]
 pub struct DecompilerData<'d, 'i, 'b> {
-  pub statics:   &'d ScriptStatics,
-  pub globals:   &'d ScriptGlobals,
-  pub natives:   &'d Natives,
-  pub cross_map: &'d CrossMap,
-  pub functions: &'d HashMap<usize, Function<'i, 'b>>
+  pub statics:    &'d ScriptStatics,
+  pub globals:    &'d ScriptGlobals,
+  pub natives:    &'d Natives,
+  pub cross_map:  &'d CrossMap,
+  pub hash_names: Option<&'d HashNames>,
+  pub functions:  &'d FunctionMap<'d, 'i, 'b>
+}
+
+impl<'d, 'i, 'b> DecompilerData<'d, 'i, 'b> {
+  /// The hash of the native in slot `index` of the script's natives table, translated to
+  /// the hash used by [`Self::natives`].
+  pub fn resolve_native_hash(&self, index: usize, script: ScriptTables) -> Option<u64> {
+    script
+      .get_native(index)
+      .map(|hash| self.cross_map.get_original_hash(hash))
+  }
+
+  /// The native in slot `index` of the script's natives table.
+  pub fn resolve_native(&self, index: usize, script: ScriptTables) -> Option<&'d NativeInfo> {
+    self
+      .resolve_native_hash(index, script)
+      .and_then(|hash| self.natives.get_native_resolved(hash, self.cross_map))
+  }
+}
+
+/// Owns everything a [`DecompilerData`] borrows.
+///
+/// ```
+/// use gta5_script_decompiler::{
+///   decompiler::{decompile_all, get_functions, DecompilerDataBuilder, ScriptStatics},
+///   disassembler::disassemble,
+///   resources::{CrossMap, Natives},
+///   script::ScriptTables
+/// };
+///
+/// // ENTER 0 2 ""; LEAVE 0 0
+/// let code = [45, 0, 2, 0, 0, 46, 0, 0];
+/// let instructions = disassemble(&code).unwrap();
+/// let functions = get_functions(&instructions);
+///
+/// let builder = DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+///   .statics(ScriptStatics::new(4))
+///   .functions(&functions);
+/// let data = builder.build();
+///
+/// let tables = ScriptTables {
+///   strings: &[],
+///   natives: &[]
+/// };
+/// let report = decompile_all(&functions, tables, &data);
+/// assert_eq!(report.succeeded().count(), 1);
+/// ```
+pub struct DecompilerDataBuilder<'f, 'i, 'b> {
+  statics:    ScriptStatics,
+  globals:    ScriptGlobals,
+  natives:    Natives,
+  cross_map:  CrossMap,
+  hash_names: Option<HashNames>,
+  functions:  FunctionMap<'f, 'i, 'b>
+}
+
+impl<'f, 'i, 'b> DecompilerDataBuilder<'f, 'i, 'b> {
+  pub fn new(natives: Natives, cross_map: CrossMap) -> Self {
+    Self {
+      statics: ScriptStatics::new(0),
+      globals: Default::default(),
+      natives,
+      cross_map,
+      hash_names: None,
+      functions: Default::default()
+    }
+  }
+
+  pub fn statics(mut self, statics: ScriptStatics) -> Self {
+    self.statics = statics;
+    self
+  }
+
+  pub fn globals(mut self, globals: ScriptGlobals) -> Self {
+    self.globals = globals;
+    self
+  }
+
+  pub fn hash_names(mut self, hash_names: HashNames) -> Self {
+    self.hash_names = Some(hash_names);
+    self
+  }
+
+  pub fn functions(mut self, functions: &'f [Function<'i, 'b>]) -> Self {
+    self.functions = function_map(functions);
+    self
+  }
+
+  pub fn build(&self) -> DecompilerData<'_, 'i, 'b> {
+    DecompilerData {
+      statics:    &self.statics,
+      globals:    &self.globals,
+      natives:    &self.natives,
+      cross_map:  &self.cross_map,
+      hash_names: self.hash_names.as_ref(),
+      functions:  &self.functions
+    }
+  }
+}
+
+/// Maps the functions by their location, as expected by [`DecompilerData::functions`].
+pub fn function_map<'f, 'i, 'b>(functions: &'f [Function<'i, 'b>]) -> FunctionMap<'f, 'i, 'b> {
+  functions
+    .iter()
+    .map(|function| (function.location, function))
+    .collect()
+}
+
+/// Maps the names `ENTER` instructions kept in debug builds to the index of their function.
+pub fn source_name_map<'f>(functions: &'f [Function]) -> HashMap<&'f str, usize> {
+  functions
+    .iter()
+    .enumerate()
+    .filter_map(|(index, function)| Some((function.source_name.as_deref()?, index)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{decompiler::get_functions, disassembler::disassemble, test_utils::Asm};
+
+  #[test]
+  fn builder_lends_its_resources() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+
+    let builder = DecompilerDataBuilder::new(
+      Natives::from_json(r#"{ "natives": { "0x4EDE34FBADD967A6": { "name": "WAIT" } } }"#).unwrap(),
+      CrossMap::identity()
+    )
+    .statics(ScriptStatics::new(4))
+    .hash_names(HashNames::from_text("PLAYER_ZERO"))
+    .functions(&functions);
+    let data = builder.build();
+
+    assert_eq!(data.statics.len(), 4);
+    assert_eq!(
+      data.natives.get_native(0x4EDE34FBADD967A6).unwrap().name,
+      "WAIT"
+    );
+    assert!(data.hash_names.is_some());
+    let mut locations = data.functions.keys().copied().collect::<Vec<_>>();
+    locations.sort();
+    assert_eq!(locations, vec![0, 8]);
+    assert_eq!(data.functions[&8].location, 8);
+  }
+
+  #[test]
+  fn function_maps_borrow_instead_of_cloning() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+
+    let map = function_map(&functions);
+    for function in &functions {
+      assert!(std::ptr::eq(map[&function.location], function));
+    }
+  }
+
+  #[test]
+  fn resolves_natives_through_the_cross_map() {
+    let natives =
+      Natives::from_json(r#"{ "natives": { "0x4EDE34FBADD967A6": { "name": "WAIT" } } }"#).unwrap();
+    let cross_map = CrossMap::from_json(r#"[["0x4EDE34FBADD967A6", "0x1234"]]"#).unwrap();
+    let builder = DecompilerDataBuilder::new(natives, cross_map);
+    let data = builder.build();
+    let script = ScriptTables {
+      strings: &[],
+      natives: &[0x1234, 0x5678]
+    };
+
+    assert_eq!(
+      data.resolve_native_hash(0, script),
+      Some(0x4EDE34FBADD967A6)
+    );
+    assert_eq!(data.resolve_native(0, script).unwrap().name, "WAIT");
+    assert!(data.resolve_native(1, script).is_none());
+    assert!(data.resolve_native(2, script).is_none());
+  }
+
+  #[test]
+  fn source_names_are_kept_for_named_functions_only() {
+    let code = Asm::default()
+      .enter_named(0, 2, "main")
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let mut functions = get_functions(&instructions);
+    functions[0].name = "friendly".to_owned();
+
+    assert_eq!(functions[0].source_name.as_deref(), Some("main"));
+    assert_eq!(functions[1].name, "func_1");
+    assert_eq!(functions[1].source_name, None);
+    assert_eq!(source_name_map(&functions), HashMap::from([("main", 0)]));
+  }
 }
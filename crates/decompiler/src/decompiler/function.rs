@@ -3,34 +3,76 @@ use std::{backtrace::Backtrace, cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
   decompiler::{
-    decompiled::Statement,
+    decompiled::{OuterTarget, Statement},
     stack_entry::{BinaryOperator, UnaryOperator}
   },
   disassembler::{Instruction, InstructionInfo},
   formatters::AssemblyFormatter,
-  script::Script
+  script::ScriptTables
 };
 
 use super::{
   decompiled::{DecompiledFunction, StatementInfo},
   function_graph::FunctionGraph,
   stack::{InvalidStackError, Stack},
-  Confidence, ControlFlow, DecompilerData, LinkedValueType, Primitives, StackEntry, StackEntryInfo,
-  ValueType, ValueTypeInfo
+  Confidence, ControlFlow, DecompileError, DecompileOptions, DecompilerData, DefaultLowering,
+  FlowType, LinkedValueType, LoweringContext, OpcodeLowering, Primitives, StackEntry,
+  StackEntryInfo, ValueType, ValueTypeInfo
 };
 
 pub struct FunctionInfo<'input, 'bytes> {
-  pub name:         String,
-  pub location:     usize,
-  pub parameters:   u32,
-  pub returns:      u32,
-  pub locals:       u32,
-  pub instructions: &'input [InstructionInfo<'bytes>]
+  pub name:           String,
+  /// The name embedded in the `ENTER` instruction, empty in production builds
+  pub source_name:    Option<String>,
+  pub location:       usize,
+  pub parameters:     u32,
+  pub returns:        u32,
+  pub locals:         u32,
+  pub instructions:   &'input [InstructionInfo<'bytes>],
+  /// The `ENTER` frame is too small for the parameters, `locals` is 0 instead
+  pub malformed:      bool,
+  /// Whether any function of the script registers a catch handler
+  pub script_catches: bool
+}
+
+/// The stack frame of a function: its parameters, the return address and frame pointer, and
+/// its locals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionFrame {
+  pub parameters: u32,
+  pub locals:     u32,
+  pub returns:    u32
+}
+
+impl FunctionFrame {
+  /// The size of the frame as encoded in `ENTER`.
+  pub fn size(&self) -> u32 {
+    self.parameters + 2 + self.locals
+  }
+}
+
+/// The number of locals of a function with an `ENTER` of `frame_size` and `arg_count`.
+/// Returns `None` if the frame is too small to hold the parameters, return address and frame
+/// pointer.
+pub fn compute_local_count(frame_size: u16, arg_count: u8) -> Option<u32> {
+  (frame_size as u32).checked_sub(arg_count as u32 + 2)
+}
+
+impl<'input, 'bytes> FunctionInfo<'input, 'bytes> {
+  pub fn frame(&self) -> FunctionFrame {
+    FunctionFrame {
+      parameters: self.parameters,
+      locals:     self.locals,
+      returns:    self.returns
+    }
+  }
 }
 
 #[derive(Clone, Debug)]
 pub struct Function<'input, 'bytes> {
   pub name:            String,
+  /// See [`FunctionInfo::source_name`], kept when `name` is replaced by a friendlier one
+  pub source_name:     Option<String>,
   pub location:        usize,
   pub parameters:      Vec<Rc<RefCell<LinkedValueType>>>,
   pub parameter_count: usize,
@@ -38,7 +80,12 @@ pub struct Function<'input, 'bytes> {
   pub returns:         Option<Rc<RefCell<LinkedValueType>>>,
   pub return_count:    usize,
   pub instructions:    &'input [InstructionInfo<'bytes>],
-  pub graph:           FunctionGraph<'input, 'bytes>
+  pub graph:           FunctionGraph<'input, 'bytes>,
+  /// See [`FunctionInfo::malformed`]
+  pub malformed:       bool,
+  /// Whether any function of the script registers a catch handler, without one a
+  /// throw terminates the script
+  pub script_catches:  bool
 }
 
 impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
@@ -46,6 +93,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
     let graph = FunctionGraph::generate(&info);
     Self {
       name: info.name,
+      source_name: info.source_name,
       location: info.location,
       parameter_count: info.parameters as usize,
       return_count: info.returns as usize,
@@ -99,7 +147,9 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
       },
       instructions: info.instructions,
-      graph
+      graph,
+      malformed: info.malformed,
+      script_catches: info.script_catches
     }
   }
 
@@ -108,33 +158,333 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
     self.graph.to_dot_string(&formatter)
   }
 
+  /// A graph with nodes colored by their reduced control flow, or the plain graph if
+  /// the control flow can't be reduced.
+  pub fn structured_dot_string(&self, formatter: &AssemblyFormatter) -> String {
+    match self.graph.reduce_control_flow() {
+      Ok(flow) => self.graph.to_structured_dot_string(formatter, &flow),
+      Err(_) => self.graph.to_dot_string(formatter)
+    }
+  }
+
+  /// Positions of the instructions in basic blocks that can't be reached from the start
+  /// of the function, these are left out of the function graph.
+  pub fn dead_instructions(&self) -> Vec<usize> {
+    let live = self.graph.node_ranges();
+
+    self
+      .instructions
+      .iter()
+      .map(|info| info.pos)
+      .filter(|pos| !live.iter().any(|block| block.contains(pos)))
+      .collect()
+  }
+
+  /// The basic blocks and edges of the function graph as JSON.
+  pub fn graph_json(&self) -> String {
+    self.graph.to_json()
+  }
+
   pub fn decompile(
     &self,
-    script: &'input Script,
+    script: impl Into<ScriptTables<'input>>,
     data: &DecompilerData
-  ) -> Result<DecompiledFunction<'input, 'bytes>, InvalidStackError> {
-    let nodes = self.graph.reduce_control_flow().unwrap();
+  ) -> Result<DecompiledFunction<'input, 'bytes>, DecompileError> {
+    self.decompile_with_lowering(script, data, &DefaultLowering)
+  }
+
+  /// Decompiles the function, giving `lowering` the first chance to handle each instruction.
+  pub fn decompile_with_lowering(
+    &self,
+    script: impl Into<ScriptTables<'input>>,
+    data: &DecompilerData,
+    lowering: &dyn OpcodeLowering
+  ) -> Result<DecompiledFunction<'input, 'bytes>, DecompileError> {
+    self.decompile_with_options(script, data, lowering, &DecompileOptions::default())
+  }
+
+  /// Like [`Self::decompile_with_lowering`], failing with [`DecompileError::TooDeep`] when
+  /// the function is nested deeper than `options` allow. Inlining is done by
+  /// [`super::decompile_all_with_options`] and not here.
+  pub fn decompile_with_options(
+    &self,
+    script: impl Into<ScriptTables<'input>>,
+    data: &DecompilerData,
+    lowering: &dyn OpcodeLowering,
+    options: &DecompileOptions
+  ) -> Result<DecompiledFunction<'input, 'bytes>, DecompileError> {
+    let script = script.into();
+    let nodes = self.graph.reduce_control_flow()?;
+
+    let mut statements = self.decompile_iteratively(
+      nodes.get(&(0.into())).unwrap(),
+      &nodes,
+      script,
+      data,
+      lowering,
+      options
+    )?;
+
+    // Everything after this point recurses into nested statements and entries
+    if let Some(depth) = Self::exceeded_depth(&statements, options.max_depth) {
+      return Err(DecompileError::TooDeep {
+        depth,
+        max_depth: options.max_depth
+      });
+    }
 
-    let statements =
-      self.decompile_iteratively(nodes.get(&(0.into())).unwrap(), &nodes, script, data)?;
+    if self.returns.is_none() {
+      Self::remove_tail_return(&mut statements);
+    }
 
     self.add_statement_types(&statements);
+    Self::simplify_statements(&mut statements);
+    Self::remove_self_assignments(&mut statements);
+    Self::rewrite_infinite_loops(&mut statements);
+    Self::recover_try_catch(&mut statements);
 
     Ok(DecompiledFunction {
       name: self.name.clone(),
+      location: self.location,
       params: self.parameters.clone(),
       returns: self.returns.clone(),
       locals: self.locals.clone(),
-      statements
+      statements,
+      blocks: self.graph.node_ranges()
     })
   }
 
+  /// The first depth past `max_depth` reached by nested statements and the entries in
+  /// them, found without recursing.
+  fn exceeded_depth(statements: &[StatementInfo], max_depth: usize) -> Option<usize> {
+    let mut pending = statements.iter().map(|info| (info, 1)).collect::<Vec<_>>();
+
+    while let Some((info, depth)) = pending.pop() {
+      let mut entries = info
+        .statement
+        .entries()
+        .into_iter()
+        .map(|entry| (entry, depth + 1))
+        .collect::<Vec<_>>();
+      while let Some((entry, depth)) = entries.pop() {
+        if depth > max_depth {
+          return Some(depth);
+        }
+        entries.extend(
+          entry
+            .entry
+            .children()
+            .into_iter()
+            .map(|child| (child, depth + 1))
+        );
+      }
+
+      if depth > max_depth {
+        return Some(depth);
+      }
+      for body in info.statement.bodies() {
+        pending.extend(body.iter().map(|info| (info, depth + 1)));
+      }
+    }
+
+    None
+  }
+
+  /// Cancels out `*&x` and `&*x` in all entries of `statements` and their nested blocks.
+  fn simplify_statements(statements: &mut [StatementInfo]) {
+    for info in statements {
+      for entry in info.statement.entries_mut() {
+        entry.simplify();
+      }
+      for body in info.statement.bodies_mut() {
+        Self::simplify_statements(body);
+      }
+    }
+  }
+
+  /// Drops `x = x;` left behind by stores of values that were just loaded. Stores to globals,
+  /// statics and pointers are kept since other threads may observe them.
+  fn remove_self_assignments(statements: &mut Vec<StatementInfo>) {
+    statements.retain(|info| {
+      match &info.statement {
+        Statement::Assign {
+          destination,
+          source
+        } => {
+          !destination.entry.is_same(&source.entry) || destination.entry.accesses_shared_memory()
+        }
+        _ => true
+      }
+    });
+
+    for info in statements {
+      for body in info.statement.bodies_mut() {
+        Self::remove_self_assignments(body);
+      }
+    }
+  }
+
+  /// Turns `while (true) { if (c) break; ... }` into `while (!c) { ... }`, also when the
+  /// rest of the body is the else branch of the `if`.
+  fn rewrite_infinite_loops(statements: &mut [StatementInfo]) {
+    for info in statements {
+      for body in info.statement.bodies_mut() {
+        Self::rewrite_infinite_loops(body);
+      }
+
+      let Statement::WhileLoop { condition, body } = &mut info.statement else {
+        continue;
+      };
+      if !matches!(condition.entry, StackEntry::Int(value) if value != 0) {
+        continue;
+      }
+
+      let Some(first) = body
+        .iter()
+        .position(|info| !matches!(info.statement, Statement::Nop))
+      else {
+        continue;
+      };
+      let is_guard = match &body[first].statement {
+        Statement::If { then, .. } | Statement::IfElse { then, .. } => {
+          matches!(
+            then
+              .iter()
+              .filter(|info| !matches!(info.statement, Statement::Nop))
+              .collect::<Vec<_>>()[..],
+            [StatementInfo {
+              statement: Statement::Break { outer: None },
+              ..
+            }]
+          )
+        }
+        _ => false
+      };
+      if !is_guard {
+        continue;
+      }
+
+      match body.remove(first).statement {
+        Statement::If {
+          condition: guard, ..
+        } => *condition = guard.negated(),
+        // The else branch is the rest of the iteration
+        Statement::IfElse {
+          condition: guard,
+          els,
+          ..
+        } => {
+          *condition = guard.negated();
+          body.splice(first..first, els);
+        }
+        _ => {}
+      }
+    }
+  }
+
+  /// Turns `err = CATCH; if (err == -1) { ... } else { ... }` into a try/catch. `CATCH`
+  /// pushes -1 when it is reached normally and the thrown value when a `THROW` jumps to it.
+  fn recover_try_catch(statements: &mut Vec<StatementInfo>) {
+    for info in statements.iter_mut() {
+      for body in info.statement.bodies_mut() {
+        Self::recover_try_catch(body);
+      }
+    }
+
+    let mut index = 0;
+    while index < statements.len() {
+      let error_var = match &statements[index].statement {
+        Statement::Assign {
+          destination,
+          source:
+            StackEntryInfo {
+              entry: StackEntry::CatchValue,
+              ..
+            }
+        } => Some(destination.clone()),
+        _ => None
+      };
+      let guard = match &error_var {
+        Some(_) => {
+          statements
+            .iter()
+            .skip(index + 1)
+            .position(|info| !matches!(info.statement, Statement::Nop))
+            .map(|offset| index + 1 + offset)
+        }
+        None => Some(index)
+      };
+
+      let Some(guard) = guard else {
+        index += 1;
+        continue;
+      };
+      let checked = match &statements[guard].statement {
+        Statement::If { condition, .. } | Statement::IfElse { condition, .. } => {
+          catch_check(condition)
+        }
+        _ => None
+      };
+      let no_error_first = match (checked, &error_var) {
+        (Some((checked, no_error_first)), Some(error_var))
+          if error_var.entry.is_same(&checked.entry) =>
+        {
+          no_error_first
+        }
+        (Some((checked, no_error_first)), None)
+          if matches!(checked.entry, StackEntry::CatchValue) =>
+        {
+          no_error_first
+        }
+        _ => {
+          index += 1;
+          continue;
+        }
+      };
+
+      let (then, els) = match std::mem::replace(&mut statements[guard].statement, Statement::Nop) {
+        Statement::If { then, .. } => (then, vec![]),
+        Statement::IfElse { then, els, .. } => (then, els),
+        _ => unreachable!()
+      };
+      let (body, handler) = if no_error_first {
+        (then, els)
+      } else {
+        (els, then)
+      };
+      statements[guard].statement = Statement::TryCatch {
+        body,
+        handler,
+        error_var
+      };
+
+      statements.drain(index..guard);
+      index += 1;
+    }
+  }
+
+  /// Removes the `return;` every void function ends with, only looks at the top level block
+  /// so early returns are kept.
+  fn remove_tail_return(statements: &mut Vec<StatementInfo>) {
+    let last = statements
+      .iter()
+      .rposition(|info| !matches!(info.statement, Statement::Nop));
+
+    if let Some(last) = last {
+      if matches!(&statements[last].statement, Statement::Return { values } if values.is_empty()) {
+        statements.remove(last);
+      }
+    }
+  }
+
   pub fn decompile_iteratively(
     &self,
     root: &ControlFlow,
     nodes: &HashMap<NodeIndex, ControlFlow>,
-    script: &'input Script,
-    data: &DecompilerData
+    script: ScriptTables<'input>,
+    data: &DecompilerData,
+    lowering: &dyn OpcodeLowering,
+    options: &DecompileOptions
   ) -> Result<Vec<StatementInfo<'input, 'bytes>>, InvalidStackError> {
     let mut statements: HashMap<
       NodeIndex,
@@ -151,10 +501,23 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         (
           Default::default(),
           Default::default(),
-          &self.instructions[0..0]
+          // Control flow statements are attributed to the jump ending their node
+          self
+            .graph
+            .get_node(flow.node())
+            .map(|node| &node.instructions[node.instructions.len().saturating_sub(1)..])
+            .unwrap_or(&self.instructions[0..0])
         )
       });
-      *conditional = self.decompile_node(node_statements, &mut stack, script, flow, data)?;
+      *conditional = self.decompile_node(
+        node_statements,
+        &mut stack,
+        script,
+        flow,
+        data,
+        lowering,
+        options
+      )?;
       Ok(())
     })?;
 
@@ -297,27 +660,55 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
     }
   }
 
+  #[allow(clippy::too_many_arguments)]
   fn decompile_node(
     &self,
     statements: &mut Vec<StatementInfo<'input, 'bytes>>,
     stack: &mut Stack<'input>,
-    script: &'input Script,
+    script: ScriptTables<'input>,
     flow: &ControlFlow,
-    DecompilerData {
+    data: &DecompilerData,
+    lowering: &dyn OpcodeLowering,
+    options: &DecompileOptions
+  ) -> Result<Option<StackEntryInfo<'input>>, InvalidStackError> {
+    let DecompilerData {
       functions,
       statics,
       globals,
-      cross_map,
       ..
-    }: &DecompilerData
-  ) -> Result<Option<StackEntryInfo<'input>>, InvalidStackError> {
+    } = data;
     let node = self.graph.get_node(flow.node()).unwrap();
 
     for (index, info) in node.instructions.iter().enumerate() {
+      let is_control_flow = matches!(
+        info.instruction,
+        Instruction::Jump { .. }
+          | Instruction::JumpZero { .. }
+          | Instruction::IfEqualJumpZero { .. }
+          | Instruction::IfNotEqualJumpZero { .. }
+          | Instruction::IfGreaterThanJumpZero { .. }
+          | Instruction::IfGreaterOrEqualJumpZero { .. }
+          | Instruction::IfLowerThanJumpZero { .. }
+          | Instruction::IfLowerOrEqualJumpZero { .. }
+          | Instruction::Switch { .. }
+      );
+      if !is_control_flow {
+        let mut context = LoweringContext {
+          stack: &mut *stack,
+          statements: &mut *statements,
+          instructions: &node.instructions[index..=index],
+          script,
+          data
+        };
+        if lowering.lower(&mut context)? {
+          continue;
+        }
+      }
+
       match &info.instruction {
         Instruction::Nop => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Nop
           })
         }
@@ -411,34 +802,8 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
             UnaryOperator::Negate
           )?
         }
-        Instruction::IntegerEquals => {
-          stack.push_binary_operator(
-            Primitives::Bool,
-            ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::Medium
-            },
-            ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::Medium
-            },
-            BinaryOperator::Equal
-          )?
-        }
-        Instruction::IntegerNotEquals => {
-          stack.push_binary_operator(
-            Primitives::Bool,
-            ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::Medium
-            },
-            ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::Medium
-            },
-            BinaryOperator::NotEqual
-          )?
-        }
+        Instruction::IntegerEquals => stack.push_equality(BinaryOperator::Equal)?,
+        Instruction::IntegerNotEquals => stack.push_equality(BinaryOperator::NotEqual)?,
         Instruction::IntegerGreaterThan => {
           stack.push_binary_operator(
             Primitives::Bool,
@@ -750,45 +1115,56 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
           return_count,
           native_index
         } => {
-          let hash = cross_map.get_original_hash(script.natives[*native_index as usize]);
+          let hash = data
+            .resolve_native_hash(*native_index as usize, script)
+            .ok_or(InvalidStackError {
+              backtrace: Backtrace::capture()
+            })?;
+          let params = data
+            .resolve_native(*native_index as usize, script)
+            .map(|native| &native.params[..])
+            .unwrap_or_default();
+          let args = stack.pop_native_args(*arg_count as usize, params)?;
           if *return_count == 0 {
             statements.push(StatementInfo {
-              instructions: &self.instructions[index..=index],
+              instructions: &node.instructions[index..=index],
               statement:    Statement::NativeCall {
-                args:        {
-                  let mut args = stack.pop_n(*arg_count as usize)?;
-                  args.reverse();
-                  args
-                },
+                args,
                 native_hash: hash
               }
             })
           } else {
-            stack.push_native_call(*arg_count as usize, *return_count as usize, hash)?
+            stack.push_native_call(args, *return_count as usize, hash)?
           }
         }
         Instruction::Enter { .. } => { /* SKIP */ }
         Instruction::Leave { return_count, .. } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Return {
-              values: stack.pop_n(*return_count as usize)?
+              // The first value pushed is the first field of the returned struct
+              values: {
+                let mut values = stack.pop_n(*return_count as usize)?;
+                values.reverse();
+                values
+              }
             }
           })
         }
         Instruction::Load => stack.push_deref()?,
         Instruction::Store => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: stack.pop()?,
               source:      stack.pop()?
             }
           })
         }
+        // Unlike `Store` the address stays on the stack, so it is only peeked at
         Instruction::StoreRev => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               source:      stack.pop()?,
               destination: {
@@ -830,7 +1206,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
           };
 
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               source:      value,
               destination: dest
@@ -847,7 +1223,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::ArrayU8Store { item_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_array_item(*item_size as usize)?;
@@ -867,7 +1243,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::LocalU8Store { offset } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_local(*offset as usize, self);
@@ -887,7 +1263,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::StaticU8Store { static_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_static(*static_index as usize, statics);
@@ -897,9 +1273,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
             }
           })
         }
-        Instruction::AddU8 { value } => {
-          stack.push_const_int_binary_operator(BinaryOperator::Add, *value as i64)?
-        }
+        Instruction::AddU8 { value } => stack.push_const_add(*value as i64)?,
         Instruction::MultiplyU8 { value } => {
           stack.push_const_int_binary_operator(BinaryOperator::Multiply, *value as i64)?
         }
@@ -917,7 +1291,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::OffsetU8Store { offset } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_const_offset(*offset as i64)?;
@@ -928,9 +1302,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
           })
         }
         Instruction::PushConstS16 { c1 } => stack.push_int(*c1 as i64),
-        Instruction::AddS16 { value } => {
-          stack.push_const_int_binary_operator(BinaryOperator::Add, *value as i64)?
-        }
+        Instruction::AddS16 { value } => stack.push_const_add(*value as i64)?,
         Instruction::MultiplyS16 { value } => {
           stack.push_const_int_binary_operator(BinaryOperator::Multiply, *value as i64)?
         }
@@ -944,7 +1316,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::OffsetS16Store { offset } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_const_offset(*offset as i64)?;
@@ -964,7 +1336,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::ArrayU16Store { item_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_array_item(*item_size as usize)?;
@@ -984,7 +1356,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::LocalU16Store { local_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_local(*local_index as usize, self);
@@ -1004,7 +1376,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::StaticU16Store { static_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_static(*static_index as usize, statics);
@@ -1024,7 +1396,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::GlobalU16Store { global_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_global(*global_index as usize, globals);
@@ -1044,33 +1416,9 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         | Instruction::IfLowerOrEqualJumpZero { .. }
         | Instruction::Switch { .. } => {
           match &info.instruction {
-            Instruction::IfEqualJumpZero { .. } => {
-              stack.push_binary_operator(
-                Primitives::Bool,
-                ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::Int),
-                  confidence: Confidence::Medium
-                },
-                ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::Int),
-                  confidence: Confidence::Medium
-                },
-                BinaryOperator::Equal
-              )?
-            }
+            Instruction::IfEqualJumpZero { .. } => stack.push_equality(BinaryOperator::Equal)?,
             Instruction::IfNotEqualJumpZero { .. } => {
-              stack.push_binary_operator(
-                Primitives::Bool,
-                ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::Int),
-                  confidence: Confidence::Medium
-                },
-                ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::Int),
-                  confidence: Confidence::Medium
-                },
-                BinaryOperator::NotEqual
-              )?
+              stack.push_equality(BinaryOperator::NotEqual)?
             }
             Instruction::IfGreaterThanJumpZero { .. } => {
               stack.push_binary_operator(
@@ -1143,16 +1491,32 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
               stack.try_make_bitwise_logical()?;
               return Ok(None);
             }
-            ControlFlow::Break { .. } => {
+            ControlFlow::Break { outer, .. } => {
               statements.push(StatementInfo {
-                instructions: &self.instructions[index..=index],
-                statement:    Statement::Break
+                instructions: &node.instructions[index..=index],
+                statement:    Statement::Break {
+                  outer: outer.and_then(|outer| {
+                    match outer {
+                      FlowType::Loop { node, .. } => {
+                        self.node_position(node).map(OuterTarget::Loop)
+                      }
+                      FlowType::Switch { node, .. } => {
+                        self.node_position(node).map(OuterTarget::Switch)
+                      }
+                      FlowType::NonBreakable { .. } => None
+                    }
+                  })
+                }
               })
             }
-            ControlFlow::Continue { .. } => {
+            ControlFlow::Continue {
+              continues, outer, ..
+            } => {
               statements.push(StatementInfo {
-                instructions: &self.instructions[index..=index],
-                statement:    Statement::Continue
+                instructions: &node.instructions[index..=index],
+                statement:    Statement::Continue {
+                  outer: outer.then(|| self.node_position(*continues)).flatten()
+                }
               })
             }
             ControlFlow::Leaf { .. } | ControlFlow::Flow { .. } => {}
@@ -1161,20 +1525,24 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         Instruction::FunctionCall { location } => {
           let location = *location as usize;
           let Some(target) = functions.get(&location) else {
-            // TODO: HANDLE THIS:
-            return Err(InvalidStackError { backtrace: Backtrace::capture() })?;
+            // Usually a call into another script, its parameters are unknown so it's passed
+            // the configured number of slots and nothing is assumed to be returned
+            statements.push(StatementInfo {
+              instructions: &node.instructions[index..=index],
+              statement:    Statement::FunctionCall {
+                args:             stack.pop_up_to(options.unknown_call_args),
+                function_address: location
+              }
+            });
+            continue;
           };
           if target.returns.is_some() {
             stack.push_function_call(target)?
           } else {
             statements.push(StatementInfo {
-              instructions: &self.instructions[index..=index],
+              instructions: &node.instructions[index..=index],
               statement:    Statement::FunctionCall {
-                args:             {
-                  let mut args = stack.pop_n(target.parameters.len())?;
-                  args.reverse();
-                  args
-                },
+                args:             stack.pop_function_args(target)?,
                 function_address: target.location
               }
             })
@@ -1190,7 +1558,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::StaticU24Store { static_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_static(*static_index as usize, statics);
@@ -1210,7 +1578,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::GlobalU24Store { global_index } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Assign {
               destination: {
                 stack.push_global(*global_index as usize, globals);
@@ -1225,7 +1593,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         Instruction::StringHash => stack.push_string_hash()?,
         Instruction::TextLabelAssignString { buffer_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::StringCopy {
               destination: stack.pop()?,
               string:      stack.pop()?,
@@ -1235,7 +1603,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::TextLabelAssignInt { buffer_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::IntToString {
               destination: stack.pop()?,
               int:         stack.pop()?,
@@ -1245,7 +1613,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::TextLabelAppendString { buffer_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::StringConcat {
               destination: stack.pop()?,
               string:      stack.pop()?,
@@ -1255,7 +1623,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         }
         Instruction::TextLabelAppendInt { buffer_size } => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::StringIntConcat {
               destination: stack.pop()?,
               int:         stack.pop()?,
@@ -1263,6 +1631,7 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
             }
           })
         }
+        // src1 ... srcN N buffer_size destination
         Instruction::TextLabelCopy => {
           let destination = stack.pop()?;
           let buffer_size = stack.pop()?;
@@ -1271,10 +1640,12 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
               backtrace: Backtrace::capture()
             })?
           };
-          let source = stack.pop_n(count as usize)?;
+          let mut source = stack.pop_n(count as usize)?;
+          // `pop_n` returns the values top first, the copy starts with the deepest one
+          source.reverse();
 
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::MemCopy {
               destination,
               source,
@@ -1286,9 +1657,10 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
         Instruction::Catch => stack.push_catch(),
         Instruction::Throw => {
           statements.push(StatementInfo {
-            instructions: &self.instructions[index..=index],
+            instructions: &node.instructions[index..=index],
             statement:    Statement::Throw {
-              value: stack.pop()?
+              value:    stack.pop()?,
+              uncaught: !self.script_catches
             }
           })
         }
@@ -1335,6 +1707,22 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
     Ok(None)
   }
 
+  fn node_position(&self, node: NodeIndex) -> Option<usize> {
+    self
+      .graph
+      .get_node(node)
+      .and_then(|node| node.instructions.first())
+      .map(|info| info.pos)
+  }
+
+  pub fn frame(&self) -> FunctionFrame {
+    FunctionFrame {
+      parameters: self.parameter_count as u32,
+      locals:     self.locals.len() as u32,
+      returns:    self.return_count as u32
+    }
+  }
+
   pub fn local_index_type(&self, index: usize) -> Option<&Rc<RefCell<LinkedValueType>>> {
     if index < self.parameters.len() {
       Some(&self.parameters[index])
@@ -1347,164 +1735,382 @@ impl<'input: 'bytes, 'bytes> Function<'input, 'bytes> {
     }
   }
 
-  fn add_statement_types(&self, statements: &[StatementInfo]) {
-    let mut stack = vec![statements];
-
-    while let Some(statements) = stack.pop() {
-      for info in statements {
-        match &info.statement {
-          Statement::Nop => {}
-          Statement::Assign {
-            destination,
-            source
-          } => {
-            LinkedValueType::link(&destination.ty, &source.ty);
-          }
-          Statement::Return { values } => {
-            match &values[..] {
-              [value] => {
-                LinkedValueType::link(self.returns.as_ref().unwrap(), &value.ty);
-              }
-              [] => {}
-              values => {
-                self
-                  .returns
-                  .as_ref()
-                  .unwrap()
-                  .borrow_mut()
-                  .hint(ValueTypeInfo {
-                    ty:         ValueType::Struct {
-                      fields: values.iter().map(|v| v.ty.clone()).collect()
-                    },
-                    confidence: Confidence::High
-                  })
-              }
+  pub fn add_statement_types(&self, statements: &[StatementInfo]) -> bool {
+    apply_statement_types(self.returns.as_ref(), statements)
+  }
+}
+
+/// Re-applies the statement types of all functions until they stop changing, so types
+/// propagate between callers and callees through their linked parameters.
+pub fn propagate_types(functions: &[&DecompiledFunction]) {
+  loop {
+    let mut changed = false;
+    for function in functions {
+      changed |= apply_statement_types(function.returns.as_ref(), &function.statements);
+    }
+
+    if !changed {
+      break;
+    }
+  }
+}
+
+/// Applies the type hints implied by the statements, returns whether any type changed.
+fn apply_statement_types(
+  returns: Option<&Rc<RefCell<LinkedValueType>>>,
+  statements: &[StatementInfo]
+) -> bool {
+  let mut changed = false;
+  let mut stack = vec![statements];
+
+  while let Some(statements) = stack.pop() {
+    for info in statements {
+      match &info.statement {
+        Statement::Nop => {}
+        Statement::Assign {
+          destination,
+          source
+        } => {
+          changed |= LinkedValueType::link(&destination.ty, &source.ty);
+        }
+        Statement::Return { values } => {
+          match &values[..] {
+            [value] => {
+              changed |= LinkedValueType::link(returns.unwrap(), &value.ty);
+            }
+            [] => {}
+            values => {
+              changed |= returns.unwrap().borrow_mut().hint(ValueTypeInfo {
+                ty:         ValueType::Struct {
+                  fields: values.iter().map(|v| v.ty.clone()).collect()
+                },
+                confidence: Confidence::High
+              })
             }
           }
-          Statement::Throw { .. } => {}
-          Statement::FunctionCall { .. } => {}
-          Statement::NativeCall { .. } => {}
-          Statement::If { condition, then } => {
-            condition.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Bool),
-              confidence: Confidence::Medium
-            });
-            stack.push(then);
-          }
-          Statement::IfElse {
-            condition,
-            then,
-            els
-          } => {
-            condition.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Bool),
-              confidence: Confidence::Medium
-            });
-            stack.push(then);
-            stack.push(els);
-          }
-          Statement::WhileLoop { condition, body } => {
-            condition.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Bool),
-              confidence: Confidence::Medium
-            });
+        }
+        Statement::Throw { .. } => {}
+        Statement::FunctionCall { .. } => {}
+        Statement::NativeCall { .. } => {}
+        Statement::If { condition, then } => {
+          changed |= condition.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Bool),
+            confidence: Confidence::Medium
+          });
+          stack.push(then);
+        }
+        Statement::IfElse {
+          condition,
+          then,
+          els
+        } => {
+          changed |= condition.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Bool),
+            confidence: Confidence::Medium
+          });
+          stack.push(then);
+          stack.push(els);
+        }
+        Statement::WhileLoop { condition, body } => {
+          changed |= condition.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Bool),
+            confidence: Confidence::Medium
+          });
+          stack.push(body);
+        }
+        Statement::Switch { condition, cases } => {
+          changed |= condition.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Int),
+            confidence: Confidence::Medium
+          });
+          for (body, _) in cases {
             stack.push(body);
           }
-          Statement::Switch { condition, cases } => {
-            condition.ty.borrow_mut().hint(ValueTypeInfo {
+        }
+        Statement::Break { .. } => {}
+        Statement::Continue { .. } => {}
+        Statement::TryCatch {
+          body,
+          handler,
+          error_var
+        } => {
+          if let Some(error_var) = error_var {
+            changed |= error_var.ty.borrow_mut().hint(ValueTypeInfo {
               ty:         ValueType::Primitive(Primitives::Int),
               confidence: Confidence::Medium
             });
-            for (body, _) in cases {
-              stack.push(body);
-            }
-          }
-          Statement::Break => {}
-          Statement::Continue => {}
-          Statement::StringCopy {
-            destination,
-            string,
-            ..
-          } => {
-            destination.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Ref(
-                LinkedValueType::Type(ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::String),
-                  confidence: Confidence::High
-                })
-                .make_shared()
-              ),
-              confidence: Confidence::High
-            });
-            string.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::String),
-              confidence: Confidence::High
-            });
-          }
-          Statement::IntToString {
-            destination, int, ..
-          } => {
-            destination.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Ref(
-                LinkedValueType::Type(ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::String),
-                  confidence: Confidence::High
-                })
-                .make_shared()
-              ),
-              confidence: Confidence::High
-            });
-            int.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::High
-            });
-          }
-          Statement::StringConcat {
-            destination,
-            string,
-            ..
-          } => {
-            destination.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Ref(
-                LinkedValueType::Type(ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::String),
-                  confidence: Confidence::High
-                })
-                .make_shared()
-              ),
-              confidence: Confidence::High
-            });
-            string.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::String),
-              confidence: Confidence::High
-            });
-          }
-          Statement::StringIntConcat {
-            destination, int, ..
-          } => {
-            destination.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Ref(
-                LinkedValueType::Type(ValueTypeInfo {
-                  ty:         ValueType::Primitive(Primitives::String),
-                  confidence: Confidence::High
-                })
-                .make_shared()
-              ),
-              confidence: Confidence::High
-            });
-            int.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::High
-            });
-          }
-          Statement::MemCopy { buffer_size, .. } => {
-            buffer_size.ty.borrow_mut().hint(ValueTypeInfo {
-              ty:         ValueType::Primitive(Primitives::Int),
-              confidence: Confidence::High
-            });
           }
+          stack.push(body);
+          stack.push(handler);
+        }
+        Statement::StringCopy {
+          destination,
+          string,
+          ..
+        } => {
+          changed |= destination.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Ref(
+              LinkedValueType::Type(ValueTypeInfo {
+                ty:         ValueType::Primitive(Primitives::String),
+                confidence: Confidence::High
+              })
+              .make_shared()
+            ),
+            confidence: Confidence::High
+          });
+          changed |= string.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::String),
+            confidence: Confidence::High
+          });
+        }
+        Statement::IntToString {
+          destination, int, ..
+        } => {
+          changed |= destination.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Ref(
+              LinkedValueType::Type(ValueTypeInfo {
+                ty:         ValueType::Primitive(Primitives::String),
+                confidence: Confidence::High
+              })
+              .make_shared()
+            ),
+            confidence: Confidence::High
+          });
+          changed |= int.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Int),
+            confidence: Confidence::High
+          });
+        }
+        Statement::StringConcat {
+          destination,
+          string,
+          ..
+        } => {
+          changed |= destination.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Ref(
+              LinkedValueType::Type(ValueTypeInfo {
+                ty:         ValueType::Primitive(Primitives::String),
+                confidence: Confidence::High
+              })
+              .make_shared()
+            ),
+            confidence: Confidence::High
+          });
+          changed |= string.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::String),
+            confidence: Confidence::High
+          });
+        }
+        Statement::StringIntConcat {
+          destination, int, ..
+        } => {
+          changed |= destination.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Ref(
+              LinkedValueType::Type(ValueTypeInfo {
+                ty:         ValueType::Primitive(Primitives::String),
+                confidence: Confidence::High
+              })
+              .make_shared()
+            ),
+            confidence: Confidence::High
+          });
+          changed |= int.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Int),
+            confidence: Confidence::High
+          });
+        }
+        Statement::MemCopy { buffer_size, .. } => {
+          changed |= buffer_size.ty.borrow_mut().hint(ValueTypeInfo {
+            ty:         ValueType::Primitive(Primitives::Int),
+            confidence: Confidence::High
+          });
         }
       }
     }
   }
+
+  changed
+}
+
+/// The value compared against -1 by `condition`, and whether `condition` holds if it is -1.
+fn catch_check<'a, 'i>(
+  condition: &'a StackEntryInfo<'i>
+) -> Option<(&'a StackEntryInfo<'i>, bool)> {
+  let StackEntry::BinaryOperator { lhs, rhs, op } = &condition.entry else {
+    return None;
+  };
+  let equal = match op {
+    BinaryOperator::Equal => true,
+    BinaryOperator::NotEqual => false,
+    _ => return None
+  };
+
+  let is_no_error =
+    |entry: &StackEntryInfo| matches!(entry.entry, StackEntry::Int(-1 | 0xFFFFFFFF));
+  if is_no_error(rhs) {
+    Some((lhs, equal))
+  } else if is_no_error(lhs) {
+    Some((rhs, equal))
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{get_functions, FunctionDecompileError},
+    disassembler::{disassemble, opcodes::Opcode},
+    test_utils::{decompile_cpp, decompile_report_with, Asm}
+  };
+
+  #[test]
+  fn local_count_leaves_room_for_parameters_and_frame() {
+    assert_eq!(compute_local_count(5, 1), Some(2));
+    assert_eq!(compute_local_count(3, 1), Some(0));
+    assert_eq!(compute_local_count(2, 0), Some(0));
+    assert_eq!(compute_local_count(u16::MAX, u8::MAX), Some(65278));
+    assert_eq!(compute_local_count(2, 1), None);
+    assert_eq!(compute_local_count(0, 0), None);
+  }
+
+  #[test]
+  fn frames_of_found_functions() {
+    let code = Asm::default()
+      .enter(2, 7)
+      .leave(2, 1)
+      // Too small for its parameter
+      .enter(1, 2)
+      .leave(1, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+
+    let frame = functions[0].frame();
+    assert_eq!(
+      frame,
+      FunctionFrame {
+        parameters: 2,
+        locals:     3,
+        returns:    1
+      }
+    );
+    assert_eq!(frame.size(), 7);
+    assert!(!functions[0].malformed);
+
+    assert_eq!(functions[1].frame().locals, 0);
+    assert!(functions[1].malformed);
+  }
+
+  #[test]
+  fn frames_smaller_than_their_parameters_decompile_without_panicking() {
+    let code = Asm::default()
+      .enter(5, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(4)
+      .leave(5, 1)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    assert!(functions[0].malformed);
+    assert_eq!(functions[0].frame().parameters, 5);
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("return parameter_4;"), "{cpp}");
+  }
+
+  #[test]
+  fn instructions_after_unconditional_jumps_are_dead() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .jump(Opcode::Jump, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::Drop)
+      .label("end")
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+
+    assert_eq!(functions[0].dead_instructions(), vec![8, 9]);
+  }
+
+  /// Pushes 1 and 2, calls 0x100, which isn't a function, and stores the top of the stack
+  /// after `cleanup` in local_0.
+  #[test]
+  fn nesting_past_the_max_depth_fails() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(0, 0)
+      .build();
+    let options = DecompileOptions {
+      max_depth: 2,
+      ..Default::default()
+    };
+
+    decompile_report_with(&code, &Default::default(), |_, report| {
+      assert!(report.functions[0].is_ok(), "{:?}", report.functions[0]);
+    });
+    decompile_report_with(&code, &options, |_, report| {
+      assert!(
+        matches!(
+          &report.functions[0],
+          Err(FunctionDecompileError {
+            source: DecompileError::TooDeep { max_depth: 2, .. },
+            ..
+          })
+        ),
+        "{:?}",
+        report.functions[0]
+      );
+    });
+  }
+
+  fn unknown_call(cleanup: &[Opcode]) -> Vec<u8> {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::PushConst1)
+      .op(Opcode::PushConst2)
+      .op(Opcode::FunctionCall)
+      .u24(0x100);
+    cleanup
+      .iter()
+      .fold(code, |code, opcode| code.op(*opcode))
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .build()
+  }
+
+  #[test]
+  fn calls_to_unknown_addresses_take_no_arguments_by_default() {
+    let cpp = decompile_cpp(&unknown_call(&[Opcode::Drop]), Default::default());
+    assert!(cpp.contains("unk_fn00000100();"), "{cpp}");
+    assert!(cpp.contains("local_0 = 1;"), "{cpp}");
+  }
+
+  #[test]
+  fn calls_to_unknown_addresses_take_the_configured_arguments() {
+    let options = DecompileOptions {
+      unknown_call_args: 1,
+      ..Default::default()
+    };
+
+    let cpp = decompile_report_with(&unknown_call(&[]), &options, |formatter, report| {
+      formatter.format_function(report.functions[0].as_ref().unwrap())
+    });
+    assert!(cpp.contains("unk_fn00000100(2);"), "{cpp}");
+    assert!(cpp.contains("local_0 = 1;"), "{cpp}");
+  }
 }
@@ -0,0 +1,202 @@
+use std::{
+  collections::{hash_map::DefaultHasher, hash_map::Entry, HashMap},
+  hash::{Hash, Hasher}
+};
+
+use crate::script::ScriptTables;
+
+use super::{decompiled::DecompiledFunction, DecompileError, DecompilerData, Function};
+
+/// Decompiled functions keyed by a hash of their bytecode, so only functions that changed are
+/// decompiled again. The output also depends on the script's tables and the
+/// [`DecompilerData`], which are part of the key as a [`CacheVersion`].
+#[derive(Default)]
+pub struct FunctionCache<'input, 'bytes> {
+  functions: HashMap<FunctionKey, DecompiledFunction<'input, 'bytes>>
+}
+
+/// Everything besides the bytecode a function is decompiled against. Computed once per
+/// script and passed to every lookup, callers bump `data_version` whenever the
+/// [`DecompilerData`] changes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CacheVersion {
+  tables:       u64,
+  data_version: u64
+}
+
+impl CacheVersion {
+  /// Hashes the string and native tables and the static values of a script.
+  pub fn new<'input>(
+    script: impl Into<ScriptTables<'input>>,
+    data: &DecompilerData,
+    data_version: u64
+  ) -> Self {
+    let script = script.into();
+    let mut hasher = DefaultHasher::new();
+    script.strings.hash(&mut hasher);
+    script.natives.hash(&mut hasher);
+    data.statics.initial_values().hash(&mut hasher);
+
+    Self {
+      tables: hasher.finish(),
+      data_version
+    }
+  }
+}
+
+/// The location and name are hashed with the bytes, the same bytes elsewhere decompile
+/// differently.
+#[derive(PartialEq, Eq, Hash)]
+struct FunctionKey {
+  hash:    u64,
+  version: CacheVersion
+}
+
+impl FunctionKey {
+  fn new(function: &Function, version: CacheVersion) -> Self {
+    let mut hasher = DefaultHasher::new();
+    function.location.hash(&mut hasher);
+    function.name.hash(&mut hasher);
+    for instruction in function.instructions {
+      hasher.write(instruction.bytes);
+    }
+
+    Self {
+      hash: hasher.finish(),
+      version
+    }
+  }
+}
+
+impl<'input: 'bytes, 'bytes> FunctionCache<'input, 'bytes> {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// Returns the cached result for `function` if neither its bytecode nor `version` changed,
+  /// otherwise decompiles it. Errors aren't cached.
+  pub fn decompile_function_cached(
+    &mut self,
+    function: &Function<'input, 'bytes>,
+    script: impl Into<ScriptTables<'input>>,
+    data: &DecompilerData,
+    version: CacheVersion
+  ) -> Result<&DecompiledFunction<'input, 'bytes>, DecompileError> {
+    match self.functions.entry(FunctionKey::new(function, version)) {
+      Entry::Occupied(entry) => Ok(entry.into_mut()),
+      Entry::Vacant(entry) => Ok(entry.insert(function.decompile(script, data)?))
+    }
+  }
+
+  /// Drops the results of versions other than `version`.
+  pub fn retain_version(&mut self, version: CacheVersion) {
+    self.functions.retain(|key, _| key.version == version);
+  }
+
+  pub fn len(&self) -> usize {
+    self.functions.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.functions.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.functions.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{get_functions, DecompilerDataBuilder, ScriptStatics},
+    disassembler::disassemble,
+    resources::{CrossMap, Natives},
+    test_utils::{Asm, NO_TABLES}
+  };
+
+  const TABLES: ScriptTables = ScriptTables {
+    strings: b"a\0",
+    ..NO_TABLES
+  };
+
+  fn function_code() -> Vec<u8> {
+    Asm::default().enter(0, 2).leave(0, 0).build()
+  }
+
+  #[test]
+  fn unchanged_functions_are_cache_hits() {
+    let code = function_code();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+    let data = builder.build();
+    let mut cache = FunctionCache::new();
+    let version = CacheVersion::new(TABLES, &data, 0);
+
+    let first = cache
+      .decompile_function_cached(&functions[0], TABLES, &data, version)
+      .unwrap() as *const _;
+    let second = cache
+      .decompile_function_cached(&functions[0], TABLES, &data, version)
+      .unwrap() as *const _;
+    assert_eq!(first, second);
+    assert_eq!(cache.len(), 1);
+
+    let bumped = CacheVersion::new(TABLES, &data, 1);
+    cache
+      .decompile_function_cached(&functions[0], TABLES, &data, bumped)
+      .unwrap();
+    assert_eq!(cache.len(), 2);
+    cache.retain_version(bumped);
+    assert_eq!(cache.len(), 1);
+  }
+
+  #[test]
+  fn changed_tables_and_bytes_are_cache_misses() {
+    let code = function_code();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+    let data = builder.build();
+    let mut cache = FunctionCache::new();
+
+    let version = CacheVersion::new(TABLES, &data, 0);
+    cache
+      .decompile_function_cached(&functions[0], TABLES, &data, version)
+      .unwrap();
+    let strings = ScriptTables {
+      strings: b"b\0",
+      ..NO_TABLES
+    };
+    let version = CacheVersion::new(strings, &data, 0);
+    cache
+      .decompile_function_cached(&functions[0], strings, &data, version)
+      .unwrap();
+    assert_eq!(cache.len(), 2, "the string table changed");
+
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .statics(ScriptStatics::with_values(&[1]))
+        .functions(&functions);
+    let statics = builder.build();
+    let version = CacheVersion::new(strings, &statics, 0);
+    cache
+      .decompile_function_cached(&functions[0], strings, &statics, version)
+      .unwrap();
+    assert_eq!(cache.len(), 3, "the static values changed");
+
+    let other_code = Asm::default().enter(0, 3).leave(0, 0).build();
+    let other_instructions = disassemble(&other_code).unwrap();
+    let other_functions = get_functions(&other_instructions);
+    cache
+      .decompile_function_cached(&other_functions[0], strings, &statics, version)
+      .unwrap();
+    assert_eq!(cache.len(), 4, "the bytecode changed");
+  }
+}
@@ -1,6 +1,7 @@
 use std::{
   collections::{HashMap, HashSet, LinkedList},
-  fmt::Debug
+  fmt::Debug,
+  ops::Range
 };
 
 use petgraph::{
@@ -10,9 +11,10 @@ use petgraph::{
   visit::{EdgeRef, IntoNodeIdentifiers, IntoNodeReferences},
   Direction
 };
+use serde::Serialize;
 
 use crate::{
-  disassembler::{Instruction, InstructionInfo, SwitchCase},
+  disassembler::{collect_jump_targets, Instruction, InstructionInfo, JumpKind},
   formatters::AssemblyFormatter
 };
 
@@ -22,7 +24,7 @@ use super::{
   ControlFlow
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub enum EdgeType {
   Jump,
   ConditionalJump,
@@ -36,6 +38,27 @@ pub struct FunctionGraphNode<'input, 'bytes> {
   pub instructions: &'input [InstructionInfo<'bytes>]
 }
 
+/// A basic block in [`FunctionGraph::to_json`], `start..end` are instruction positions.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNodeSummary {
+  pub index: usize,
+  pub start: usize,
+  pub end:   usize
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdgeSummary {
+  pub from: usize,
+  pub to:   usize,
+  pub ty:   EdgeType
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphSummary {
+  pub nodes: Vec<GraphNodeSummary>,
+  pub edges: Vec<GraphEdgeSummary>
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionGraph<'input, 'bytes> {
   graph:      DiGraph<FunctionGraphNode<'input, 'bytes>, EdgeType>,
@@ -46,7 +69,10 @@ pub struct FunctionGraph<'input, 'bytes> {
 impl<'input: 'bytes, 'bytes> FunctionGraph<'input, 'bytes> {
   pub fn generate(function: &FunctionInfo<'input, 'bytes>) -> Self {
     let mut graph: DiGraph<FunctionGraphNode<'input, 'bytes>, EdgeType> = Default::default();
-    let destinations = get_destinations(function.instructions);
+    let destinations = collect_jump_targets(function.instructions)
+      .filter(|(_, kind)| *kind != JumpKind::Call)
+      .map(|(location, _)| location)
+      .collect::<HashSet<_>>();
     let mut node_indices: HashMap<usize, NodeIndex> = Default::default();
 
     let mut current_index: Option<usize> = None;
@@ -152,13 +178,83 @@ impl<'input: 'bytes, 'bytes> FunctionGraph<'input, 'bytes> {
 
   pub fn to_dot_string(&self, formatter: &AssemblyFormatter) -> String {
     let mut first = true;
-    let mut diagram: LinkedList<String> = Default::default();
-    diagram.push_back(r#"digraph{graph[splines=ortho,rankdir=TB,concentrate=true]node[fontname="Consolas",fontcolor=black]"#.to_owned());
-    for (index, node) in self.graph.node_references() {
+    self.dot_string_colored(formatter, |index| {
       let has_edges = self
         .graph
         .edges_directed(index, Direction::Outgoing)
         .any(|_| true);
+      if first {
+        first = false;
+        "darkgreen"
+      } else if !has_edges {
+        "red4"
+      } else {
+        "black"
+      }
+    })
+  }
+
+  pub fn summary(&self) -> GraphSummary {
+    GraphSummary {
+      nodes: self
+        .node_ranges()
+        .into_iter()
+        .enumerate()
+        .map(|(index, range)| {
+          GraphNodeSummary {
+            index,
+            start: range.start,
+            end: range.end
+          }
+        })
+        .collect(),
+      edges: self
+        .graph
+        .edge_references()
+        .map(|edge| {
+          GraphEdgeSummary {
+            from: edge.source().index(),
+            to:   edge.target().index(),
+            ty:   *edge.weight()
+          }
+        })
+        .collect()
+    }
+  }
+
+  /// The nodes and edges of the graph as JSON, see [`Self::summary`].
+  pub fn to_json(&self) -> String {
+    serde_json::to_string(&self.summary()).expect("graph summaries always serialize")
+  }
+
+  /// Like [`Self::to_dot_string`], but colors each node by the kind of control flow
+  /// it was reduced to by [`Self::reduce_control_flow`].
+  pub fn to_structured_dot_string(
+    &self,
+    formatter: &AssemblyFormatter,
+    flow: &HashMap<NodeIndex, ControlFlow>
+  ) -> String {
+    self.dot_string_colored(formatter, |index| {
+      match flow.get(&index) {
+        Some(ControlFlow::WhileLoop { .. }) => "blue",
+        Some(ControlFlow::Switch { .. }) => "purple",
+        Some(ControlFlow::If { .. } | ControlFlow::IfElse { .. }) => "darkorange",
+        Some(ControlFlow::AndOr { .. }) => "goldenrod",
+        Some(ControlFlow::Break { .. } | ControlFlow::Continue { .. }) => "red4",
+        Some(ControlFlow::Leaf { .. } | ControlFlow::Flow { .. }) => "black",
+        None => "gray"
+      }
+    })
+  }
+
+  fn dot_string_colored(
+    &self,
+    formatter: &AssemblyFormatter,
+    mut color: impl FnMut(NodeIndex) -> &'static str
+  ) -> String {
+    let mut diagram: LinkedList<String> = Default::default();
+    diagram.push_back(r#"digraph{graph[splines=ortho,rankdir=TB,concentrate=true]node[fontname="Consolas",fontcolor=black]"#.to_owned());
+    for (index, node) in self.graph.node_references() {
       let assembly = formatter.format(node.instructions, false);
       diagram.push_back(format!(
         "node_{node}[margin=0.0,label=<<table border=\"0\"><tr><td bgcolor=\"#AAAAAA\">Node {node}</td></tr><tr><td align=\"text\">{assembly}<br align=\"left\" /></td></tr></table>>,shape=rectangle,color={color}]",
@@ -167,16 +263,7 @@ impl<'input: 'bytes, 'bytes> FunctionGraph<'input, 'bytes> {
           .trim_start_matches('\n')
           .replace('\n', "<br align=\"left\" />")
           .replace('\t', "    "),
-        color = {
-          if first {
-            first = false;
-            "darkgreen"
-          } else if !has_edges {
-            "red4"
-          } else {
-            "black"
-          }
-        }
+        color = color(index)
       ));
     }
 
@@ -206,7 +293,21 @@ impl<'input: 'bytes, 'bytes> FunctionGraph<'input, 'bytes> {
     diagram.into_iter().collect::<Vec<_>>().join("")
   }
 
-  pub fn get_node(&self, node: NodeIndex) -> Option<&FunctionGraphNode> {
+  /// The instruction positions covered by each node, indexed by the node index.
+  pub fn node_ranges(&self) -> Vec<Range<usize>> {
+    self
+      .graph
+      .node_weights()
+      .map(|node| {
+        match (node.instructions.first(), node.instructions.last()) {
+          (Some(first), Some(last)) => first.pos..last.pos + last.bytes.len(),
+          _ => 0..0
+        }
+      })
+      .collect()
+  }
+
+  pub fn get_node(&self, node: NodeIndex) -> Option<&FunctionGraphNode<'input, 'bytes>> {
     self.graph.node_weight(node)
   }
 
@@ -221,33 +322,6 @@ impl<'input: 'bytes, 'bytes> FunctionGraph<'input, 'bytes> {
   }
 }
 
-fn get_destinations(instructions: &[InstructionInfo]) -> HashSet<usize> {
-  let mut result = HashSet::new();
-
-  for instruction in instructions {
-    match &instruction.instruction {
-      Instruction::Jump { location }
-      | Instruction::JumpZero { location }
-      | Instruction::IfEqualJumpZero { location }
-      | Instruction::IfNotEqualJumpZero { location }
-      | Instruction::IfLowerThanJumpZero { location }
-      | Instruction::IfGreaterThanJumpZero { location }
-      | Instruction::IfLowerOrEqualJumpZero { location }
-      | Instruction::IfGreaterOrEqualJumpZero { location } => {
-        result.insert(*location as usize);
-      }
-      Instruction::Switch { cases } => {
-        for SwitchCase { location, .. } in cases {
-          result.insert(*location as usize);
-        }
-      }
-      _ => {}
-    }
-  }
-
-  result
-}
-
 // https://github.com/m4b/petgraph/blob/9a6af51bf9803414d68e27f5e8d08600ce2a6212/src/algo/dominators.rs#L90
 fn domination_frontiers<N: Debug, E>(
   graph: &DiGraph<N, E>,
@@ -306,3 +380,85 @@ fn remove_unreachable<'i: 'b, 'b>(
     |_, e| Some(*e)
   )
 }
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    decompiler::get_functions,
+    disassembler::{disassemble, opcodes::Opcode},
+    formatters::AssemblyFormatter,
+    test_utils::Asm
+  };
+
+  #[test]
+  fn structured_dot_colors_nodes_by_control_flow() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .label("loop")
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::PushConst2)
+      .jump(Opcode::IfNotEqualJumpZero, "loop")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .jump(Opcode::Jump, "loop")
+      .label("end")
+      .leave(1, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let graph = &functions[0].graph;
+    let formatter = AssemblyFormatter::new(&instructions, false, 0, &[]);
+
+    let flow = graph.reduce_control_flow().unwrap();
+    let dot = graph.to_structured_dot_string(&formatter, &flow);
+
+    assert!(dot.contains("shape=rectangle,color=blue]"), "{dot}");
+    assert!(dot.contains("shape=rectangle,color=darkorange]"), "{dot}");
+    assert!(!graph.to_dot_string(&formatter).contains("color=blue"));
+  }
+
+  #[test]
+  fn json_lists_the_nodes_and_edges_of_an_if_else() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "else")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .jump(Opcode::Jump, "end")
+      .label("else")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(1, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let graph = &functions[0].graph;
+
+    let summary = graph.summary();
+    assert_eq!(summary.nodes.len(), 4);
+    assert_eq!(summary.edges.len(), 4);
+    assert_eq!((summary.nodes[0].start, summary.nodes[0].end), (0, 10));
+
+    let json = graph.to_json();
+    assert_eq!(
+      json.matches(r#""ty":"ConditionalJump""#).count(),
+      1,
+      "{json}"
+    );
+    assert_eq!(
+      json.matches(r#""ty":"ConditionalFlow""#).count(),
+      1,
+      "{json}"
+    );
+  }
+}
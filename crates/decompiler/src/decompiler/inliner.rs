@@ -0,0 +1,372 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{
+  decompiled::{DecompiledFunction, Statement, StatementInfo, Visited},
+  DecompileReport, Function, LinkedValueType, StackEntry, StackEntryInfo
+};
+
+/// The default of [`DecompileOptions::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct DecompileOptions {
+  /// Calls to functions with at most this many statements are replaced with the body of
+  /// the called function, `0` disables inlining
+  pub inline_threshold:  usize,
+  /// Functions whose statements and expressions are nested deeper than this fail with
+  /// [`super::DecompileError::TooDeep`] instead of overflowing the stack
+  pub max_depth:         usize,
+  /// Stack slots passed to calls of functions missing from [`super::DecompilerData::functions`],
+  /// usually calls into other scripts whose parameters are unknown
+  pub unknown_call_args: usize
+}
+
+impl Default for DecompileOptions {
+  fn default() -> Self {
+    Self {
+      inline_threshold:  0,
+      max_depth:         DEFAULT_MAX_DEPTH,
+      unknown_call_args: 0
+    }
+  }
+}
+
+enum InlineBody<'input, 'bytes> {
+  /// The function only returns a value computed from its parameters
+  Expression(StackEntryInfo<'input>),
+  /// The function does not return anything
+  Statements(Vec<StatementInfo<'input, 'bytes>>)
+}
+
+struct Inlinable<'input, 'bytes> {
+  params: usize,
+  locals: Vec<Rc<RefCell<LinkedValueType>>>,
+  body:   InlineBody<'input, 'bytes>
+}
+
+impl<'input, 'bytes> Inlinable<'input, 'bytes> {
+  /// Only functions that do not call other functions are inlined, which rules out
+  /// recursion. Functions with more than one return are left alone.
+  fn new(function: &DecompiledFunction<'input, 'bytes>, threshold: usize) -> Option<Self> {
+    if !function.called_functions().is_empty() {
+      return None;
+    }
+
+    let params = function.params.len();
+    let statements = function
+      .statements
+      .iter()
+      .filter(|info| !matches!(info.statement, Statement::Nop))
+      .collect::<Vec<_>>();
+    if statements.is_empty() || statements.len() > threshold {
+      return None;
+    }
+
+    let mut returns = 0;
+    let mut uses_locals = false;
+    let mut writes_params = false;
+    function.visit(|visited| {
+      match visited {
+        Visited::Statement(Statement::Return { .. }) => returns += 1,
+        Visited::Statement(Statement::Assign { destination, .. }) => {
+          writes_params |= matches!(destination.entry, StackEntry::Local(local) if local < params);
+        }
+        Visited::Entry(StackEntry::Ref(source)) => {
+          writes_params |= matches!(source.entry, StackEntry::Local(local) if local < params);
+        }
+        Visited::Entry(StackEntry::Local(local)) => uses_locals |= *local >= params,
+        _ => {}
+      }
+    });
+    if writes_params {
+      return None;
+    }
+
+    let body = match statements[..] {
+      [StatementInfo {
+        statement: Statement::Return { values },
+        ..
+      }] if returns == 1 && !uses_locals && values.len() == 1 && values[0].entry.size() == 1 => {
+        InlineBody::Expression(values[0].clone())
+      }
+      _ if returns == 0 && function.returns.is_none() => {
+        InlineBody::Statements(statements.into_iter().cloned().collect())
+      }
+      _ => return None
+    };
+
+    Some(Self {
+      params,
+      locals: function.locals.clone(),
+      body
+    })
+  }
+
+  /// Whether the call arguments can be substituted for the parameters without changing
+  /// the number or order of their side effects. Substituted arguments are evaluated where
+  /// the parameters are used, which may be never or several times, so they must not have
+  /// side effects. They also must not read statics, globals or pointers, the body could
+  /// write them before the parameter is used.
+  fn accepts(&self, args: &[StackEntryInfo]) -> bool {
+    args.len() == self.params
+      && args
+        .iter()
+        .all(|arg| arg.entry.size() == 1 && !has_calls(arg) && !arg.entry.accesses_shared_memory())
+  }
+}
+
+fn has_calls(entry: &StackEntryInfo) -> bool {
+  matches!(
+    entry.entry,
+    StackEntry::FunctionCallResult { .. } | StackEntry::NativeCallResult { .. }
+  ) || entry.entry.children().into_iter().any(has_calls)
+}
+
+/// Replaces the parameters in `entry` with `args` and moves locals behind `local_base`.
+fn substitute<'input>(
+  entry: &mut StackEntryInfo<'input>,
+  args: &[StackEntryInfo<'input>],
+  local_base: usize
+) {
+  if let StackEntry::Local(local) = entry.entry {
+    if local < args.len() {
+      *entry = args[local].clone();
+    } else {
+      entry.entry =
+        StackEntry::Local(local - args.len() - 2 /* return address and stack frame */ + local_base);
+    }
+    return;
+  }
+
+  for child in entry.entry.children_mut() {
+    substitute(child, args, local_base);
+  }
+}
+
+fn substitute_statements<'input>(
+  statements: &mut [StatementInfo<'input, '_>],
+  args: &[StackEntryInfo<'input>],
+  local_base: usize
+) {
+  for info in statements {
+    for entry in info.statement.entries_mut() {
+      substitute(entry, args, local_base);
+    }
+    for body in info.statement.bodies_mut() {
+      substitute_statements(body, args, local_base);
+    }
+  }
+}
+
+struct Inliner<'a, 'input, 'bytes> {
+  inlinable: &'a HashMap<usize, Inlinable<'input, 'bytes>>,
+  params:    usize,
+  locals:    &'a mut Vec<Rc<RefCell<LinkedValueType>>>
+}
+
+impl<'a, 'input, 'bytes> Inliner<'a, 'input, 'bytes> {
+  fn inline_entry(&self, entry: &mut StackEntryInfo<'input>) {
+    for child in entry.entry.children_mut() {
+      self.inline_entry(child);
+    }
+
+    let StackEntry::FunctionCallResult {
+      args,
+      function_address,
+      return_values: 1
+    } = &entry.entry
+    else {
+      return;
+    };
+    let Some(callee) = self.inlinable.get(function_address) else {
+      return;
+    };
+    let InlineBody::Expression(expression) = &callee.body else {
+      return;
+    };
+    if !callee.accepts(args) {
+      return;
+    }
+
+    let mut expression = expression.clone();
+    substitute(&mut expression, args, 0);
+    *entry = expression;
+  }
+
+  fn inline_statements(&mut self, statements: &mut Vec<StatementInfo<'input, 'bytes>>) {
+    let mut result = Vec::with_capacity(statements.len());
+
+    for mut info in statements.drain(..) {
+      for entry in info.statement.entries_mut() {
+        self.inline_entry(entry);
+      }
+      for body in info.statement.bodies_mut() {
+        self.inline_statements(body);
+      }
+
+      if let Statement::FunctionCall {
+        args,
+        function_address
+      } = &info.statement
+      {
+        if let Some(callee) = self.inlinable.get(function_address) {
+          if let InlineBody::Statements(body) = &callee.body {
+            if callee.accepts(args) {
+              let local_base = self.params + 2 + self.locals.len();
+              self.locals.extend(callee.locals.iter().cloned());
+
+              let mut body = body.clone();
+              substitute_statements(&mut body, args, local_base);
+              result.extend(body);
+              continue;
+            }
+          }
+        }
+      }
+
+      result.push(info);
+    }
+
+    *statements = result;
+  }
+}
+
+/// Replaces calls to small functions with their bodies according to `options`. Locals of
+/// inlined functions are appended to the locals of the caller. `functions` are the
+/// functions `report` was created from.
+pub fn inline_functions<'input, 'bytes>(
+  report: &mut DecompileReport<'input, 'bytes>,
+  functions: &[Function<'input, 'bytes>],
+  options: &DecompileOptions
+) {
+  if options.inline_threshold == 0 {
+    return;
+  }
+
+  let inlinable = functions
+    .iter()
+    .zip(&report.functions)
+    .filter_map(|(function, result)| {
+      let decompiled = result.as_ref().ok()?;
+      Some((
+        function.location,
+        Inlinable::new(decompiled, options.inline_threshold)?
+      ))
+    })
+    .collect::<HashMap<_, _>>();
+
+  for function in report
+    .functions
+    .iter_mut()
+    .filter_map(|result| result.as_mut().ok())
+  {
+    let mut inliner = Inliner {
+      inlinable: &inlinable,
+      params:    function.params.len(),
+      locals:    &mut function.locals
+    };
+    inliner.inline_statements(&mut function.statements);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{decompile_all_with_options, get_functions, DecompilerDataBuilder},
+    disassembler::{disassemble, opcodes::Opcode},
+    formatters::CppFormatter,
+    resources::{CrossMap, Natives},
+    script::ScriptTables,
+    test_utils::Asm
+  };
+
+  /// The caller passing `arg`, which has to take 7 bytes, to a getter at 16 that returns
+  /// its parameter plus one.
+  fn call_getter(arg: Asm) -> Vec<u8> {
+    arg
+      .op(Opcode::FunctionCall)
+      .u24(16)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::AddU8)
+      .u8(1)
+      .leave(1, 1)
+      .build()
+  }
+
+  fn inlined_caller(code: &[u8]) -> String {
+    let instructions = disassemble(code).unwrap();
+    let functions = get_functions(&instructions);
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+    let data = builder.build();
+
+    let report = decompile_all_with_options(
+      &functions,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x1234]
+      },
+      &data,
+      &DecompileOptions {
+        inline_threshold: 1,
+        ..Default::default()
+      }
+    );
+    let caller = report.succeeded().next().unwrap();
+    CppFormatter::new(data).format_function(caller)
+  }
+
+  #[test]
+  fn getters_are_inlined() {
+    let code = call_getter(
+      Asm::default()
+        .enter(0, 3)
+        .op(Opcode::Nop)
+        .op(Opcode::PushConst2)
+    );
+
+    let cpp = inlined_caller(&code);
+    assert!(cpp.contains("local_0 = 2 + 1;"), "{cpp}");
+  }
+
+  #[test]
+  fn arguments_reading_shared_memory_are_not_substituted() {
+    let code = call_getter(Asm::default().enter(0, 3).op(Opcode::StaticU8Load).u8(0));
+
+    let cpp = inlined_caller(&code);
+    assert!(cpp.contains("local_0 = func_1(static_0);"), "{cpp}");
+  }
+
+  #[test]
+  fn arguments_with_side_effects_are_not_substituted() {
+    // NATIVE 0 1 0, the getter at 18 ignores its parameter
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::NativeCall)
+      .u8(1)
+      .u8(0)
+      .u8(0)
+      .op(Opcode::FunctionCall)
+      .u24(18)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .enter(1, 3)
+      .op(Opcode::PushConst1)
+      .leave(1, 1)
+      .build();
+
+    let cpp = inlined_caller(&code);
+    assert!(
+      cpp.contains("local_0 = func_1(unk_0x0000000000001234());"),
+      "{cpp}"
+    );
+  }
+}
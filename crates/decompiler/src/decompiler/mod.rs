@@ -2,21 +2,34 @@ use crate::disassembler::{Instruction, InstructionInfo};
 
 mod cfg_reducer;
 mod control_flow;
+mod decompile_report;
 pub mod decompiled;
 mod decompiler_data;
 mod function;
+mod function_cache;
 mod function_graph;
+mod inliner;
+mod multi_script;
+mod opcode_lowering;
 mod script_globals;
 mod script_statics;
+mod script_stats;
 mod stack;
 mod stack_entry;
 mod value_type;
 
 pub use control_flow::*;
+pub use decompile_report::*;
 pub use decompiler_data::*;
 pub use function::*;
+pub use function_cache::*;
+pub use inliner::*;
+pub use multi_script::*;
+pub use opcode_lowering::*;
 pub use script_globals::*;
 pub use script_statics::*;
+pub use script_stats::*;
+pub use stack::*;
 pub use stack_entry::*;
 pub use value_type::*;
 
@@ -25,12 +38,15 @@ fn find_functions<'bytes, 'input: 'bytes>(
 ) -> Vec<Function<'input, 'bytes>> {
   let mut result = vec![];
   let mut it = instructions.iter().enumerate().peekable();
+  let script_catches = instructions
+    .iter()
+    .any(|info| matches!(info.instruction, Instruction::Catch));
 
   while let Some((start, instr)) = it.next() {
     if let Instruction::Enter {
       arg_count,
       frame_size,
-      ..
+      ref name
     } = instr.instruction
     {
       let mut last_leave: Option<(usize, u8)> = None;
@@ -63,13 +79,20 @@ fn find_functions<'bytes, 'input: 'bytes>(
       }
 
       if let Some((end, return_count)) = last_leave {
+        let locals = compute_local_count(frame_size, arg_count);
+        // Without a name in the bytecode the disassembler made one up, which isn't a source
+        // name. The length of the name follows the frame size.
+        let named = matches!(instr.bytes.get(4), Some(length) if *length > 0);
         result.push(Function::new(FunctionInfo {
-          name:         format!("func_{}", result.len()),
-          location:     instructions[start].pos,
-          parameters:   arg_count as u32,
-          returns:      return_count as u32,
-          locals:       frame_size as u32 - arg_count as u32 - 2,
-          instructions: &instructions[start..=end]
+          name: format!("func_{}", result.len()),
+          source_name: named.then(|| name.clone()),
+          location: instructions[start].pos,
+          parameters: arg_count as u32,
+          returns: return_count as u32,
+          locals: locals.unwrap_or_default(),
+          instructions: &instructions[start..=end],
+          malformed: locals.is_none(),
+          script_catches
         }))
       }
     }
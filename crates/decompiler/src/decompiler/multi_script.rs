@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::{
+  disassembler::{disassemble, DisassembleError},
+  script::Script
+};
+
+use super::get_functions;
+
+/// Names of the functions of related scripts keyed by `(script index, location)`.
+pub type ScriptFunctionNames = HashMap<(usize, usize), String>;
+
+/// The functions of a [`MultiScript`] as seen from one of its scripts.
+#[derive(Clone, Copy)]
+pub struct RelatedScripts<'d> {
+  pub names:  &'d ScriptFunctionNames,
+  /// Index of the script being formatted, its own functions are never candidates
+  pub script: usize
+}
+
+impl<'d> RelatedScripts<'d> {
+  /// The function at `location` in one of the other scripts, if exactly one of them has a
+  /// function there.
+  pub fn get(&self, location: usize) -> Option<&'d str> {
+    let mut candidates = self
+      .names
+      .iter()
+      .filter(|((script, function), _)| *script != self.script && *function == location);
+
+    match (candidates.next(), candidates.next()) {
+      (Some((_, name)), None) => Some(name),
+      _ => None
+    }
+  }
+}
+
+/// A script split into a parent and its children, decompiled together so calls into
+/// another one of the scripts can be named.
+pub struct MultiScript {
+  pub scripts: Vec<Script>
+}
+
+impl MultiScript {
+  pub fn new(scripts: Vec<Script>) -> Self {
+    Self { scripts }
+  }
+
+  pub fn get(&self, name: &str) -> Option<(usize, &Script)> {
+    self
+      .scripts
+      .iter()
+      .enumerate()
+      .find(|(_, script)| script.header.name == name)
+  }
+
+  /// The names of all functions, qualified as `script::function`.
+  pub fn function_names(&self) -> Result<ScriptFunctionNames, DisassembleError> {
+    let mut names = ScriptFunctionNames::new();
+
+    for (index, script) in self.scripts.iter().enumerate() {
+      let instructions = disassemble(&script.code)?;
+      for function in get_functions(&instructions) {
+        names.insert(
+          (index, function.location),
+          format!("{}::{}", script.header.name, function.name)
+        );
+      }
+    }
+
+    Ok(names)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names() -> ScriptFunctionNames {
+    [
+      ((0, 0), "parent::func_0".to_owned()),
+      ((0, 8), "parent::func_1".to_owned()),
+      ((1, 0), "child::func_0".to_owned()),
+      ((1, 16), "child::func_1".to_owned()),
+      ((2, 16), "other::func_1".to_owned())
+    ]
+    .into_iter()
+    .collect()
+  }
+
+  #[test]
+  fn related_functions_come_from_the_other_scripts() {
+    let names = names();
+    let parent = RelatedScripts {
+      names:  &names,
+      script: 0
+    };
+
+    // Only the child has a function here, the parent's own doesn't count
+    assert_eq!(parent.get(0), Some("child::func_0"));
+    assert_eq!(parent.get(8), None);
+    // Ambiguous between the child and the other script
+    assert_eq!(parent.get(16), None);
+
+    let other = RelatedScripts {
+      names:  &names,
+      script: 2
+    };
+    assert_eq!(other.get(16), Some("child::func_1"));
+  }
+}
@@ -0,0 +1,121 @@
+use crate::{disassembler::InstructionInfo, script::ScriptTables};
+
+use super::{decompiled::StatementInfo, DecompilerData, InvalidStackError, Stack};
+
+/// Everything an [`OpcodeLowering`] can read or modify while lowering one instruction.
+pub struct LoweringContext<'a, 'input, 'bytes> {
+  pub stack:        &'a mut Stack<'input>,
+  pub statements:   &'a mut Vec<StatementInfo<'input, 'bytes>>,
+  /// The instruction being lowered, as a slice so it can be used for [`StatementInfo`]
+  pub instructions: &'input [InstructionInfo<'bytes>],
+  pub script:       ScriptTables<'input>,
+  pub data:         &'a DecompilerData<'a, 'a, 'a>
+}
+
+impl<'a, 'input, 'bytes> LoweringContext<'a, 'input, 'bytes> {
+  pub fn instruction(&self) -> &'input InstructionInfo<'bytes> {
+    &self.instructions[0]
+  }
+}
+
+/// Hook to override how instructions are turned into stack entries and statements.
+/// Jumps and switches are always handled by the decompiler, as they make up the control
+/// flow of the function.
+pub trait OpcodeLowering {
+  /// Lowers the instruction of `context`. Returns `false` to fall back to the default
+  /// lowering.
+  fn lower(&self, context: &mut LoweringContext) -> Result<bool, InvalidStackError> {
+    let _ = context;
+    Ok(false)
+  }
+}
+
+/// Lowers every instruction the default way.
+pub struct DefaultLowering;
+
+impl OpcodeLowering for DefaultLowering {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{decompiled::Statement, get_functions, DecompilerDataBuilder},
+    disassembler::{disassemble, opcodes::Opcode, Instruction},
+    formatters::CppFormatter,
+    resources::{CrossMap, Natives},
+    test_utils::Asm
+  };
+
+  const WAIT: u64 = 0x4EDE34FBADD967A6;
+  const MARKER: u64 = 0x1234;
+
+  /// Replaces calls to `WAIT` with calls to `MARKER`, dropping the argument.
+  struct MarkWaits;
+
+  impl OpcodeLowering for MarkWaits {
+    fn lower(&self, context: &mut LoweringContext) -> Result<bool, InvalidStackError> {
+      let Instruction::NativeCall { native_index, .. } = context.instruction().instruction else {
+        return Ok(false);
+      };
+      if context.script.get_native(native_index as usize) != Some(WAIT) {
+        return Ok(false);
+      }
+
+      context.stack.pop()?;
+      context.statements.push(StatementInfo {
+        instructions: context.instructions,
+        statement:    Statement::NativeCall {
+          args:        vec![],
+          native_hash: MARKER
+        }
+      });
+      Ok(true)
+    }
+  }
+
+  #[test]
+  fn custom_lowering_rewrites_natives() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConst1)
+      .op(Opcode::NativeCall)
+      .u8(1 << 2)
+      .u8(0)
+      .u8(0)
+      .op(Opcode::PushConst2)
+      .op(Opcode::NativeCall)
+      .u8(1 << 2)
+      .u8(0)
+      .u8(1)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let natives = Natives::from_json(
+      r#"{ "natives": {
+        "0x4EDE34FBADD967A6": { "name": "WAIT" },
+        "0x1234": { "name": "MARKER" },
+        "0x5678": { "name": "OTHER" }
+      } }"#
+    )
+    .unwrap();
+    let builder = DecompilerDataBuilder::new(natives, CrossMap::identity()).functions(&functions);
+    let data = builder.build();
+    let script = ScriptTables {
+      strings: &[],
+      natives: &[WAIT, 0x5678]
+    };
+
+    let custom = functions[0]
+      .decompile_with_lowering(script, &data, &MarkWaits)
+      .unwrap();
+    let default = functions[0].decompile(script, &data).unwrap();
+    let formatter = CppFormatter::new(data);
+
+    let cpp = formatter.format_function(&custom);
+    assert!(cpp.contains("MARKER();"), "{cpp}");
+    assert!(cpp.contains("OTHER(2);"), "{cpp}");
+    let cpp = formatter.format_function(&default);
+    assert!(cpp.contains("WAIT(1);"), "{cpp}");
+  }
+}
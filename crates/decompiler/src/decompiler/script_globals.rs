@@ -2,6 +2,14 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::LinkedValueType;
 
+/// Number of globals in a global block.
+pub const GLOBAL_BLOCK_SIZE: usize = 0x40000;
+
+/// Splits a global index into its block and the index inside that block.
+pub fn split_global_index(global: usize) -> (usize, usize) {
+  (global / GLOBAL_BLOCK_SIZE, global % GLOBAL_BLOCK_SIZE)
+}
+
 #[derive(Default)]
 pub struct ScriptGlobals {
   globals: RefCell<HashMap<usize, Rc<RefCell<LinkedValueType>>>>
@@ -16,4 +24,30 @@ impl ScriptGlobals {
       .or_insert_with(|| LinkedValueType::new_primitive(super::Primitives::Unknown).make_shared())
       .clone()
   }
+
+  /// All globals accessed so far by index, with the types of all their accesses merged.
+  pub fn globals(&self) -> Vec<(usize, Rc<RefCell<LinkedValueType>>)> {
+    let mut globals = self
+      .globals
+      .borrow()
+      .iter()
+      .map(|(global, ty)| (*global, ty.clone()))
+      .collect::<Vec<_>>();
+    globals.sort_by_key(|(global, _)| *global);
+    globals
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn global_indices_split_into_blocks() {
+    assert_eq!(split_global_index(0), (0, 0));
+    assert_eq!(split_global_index(GLOBAL_BLOCK_SIZE - 1), (0, 0x3FFFF));
+    assert_eq!(split_global_index(GLOBAL_BLOCK_SIZE), (1, 0));
+    assert_eq!(split_global_index(0x40005), (1, 5));
+    assert_eq!(split_global_index(0x3F_FFFF), (15, 0x3FFFF));
+  }
 }
@@ -3,19 +3,43 @@ use std::{cell::RefCell, rc::Rc};
 use super::LinkedValueType;
 
 pub struct ScriptStatics {
-  statics: Vec<Rc<RefCell<LinkedValueType>>>
+  statics:        Vec<Rc<RefCell<LinkedValueType>>>,
+  initial_values: Vec<u64>
 }
 
 impl ScriptStatics {
   pub fn new(static_count: usize) -> Self {
+    Self::with_values(&vec![0; static_count])
+  }
+
+  /// Statics initialized with the values from the script's statics table.
+  pub fn with_values(initial_values: &[u64]) -> Self {
     Self {
-      statics: (0..static_count)
+      statics:        initial_values
+        .iter()
         .map(|_| LinkedValueType::new_primitive(super::Primitives::Unknown).make_shared())
-        .collect()
+        .collect(),
+      initial_values: initial_values.to_vec()
     }
   }
 
   pub fn get_static(&self, static_index: usize) -> Option<&Rc<RefCell<LinkedValueType>>> {
     self.statics.get(static_index)
   }
+
+  pub fn get_initial_value(&self, static_index: usize) -> Option<u64> {
+    self.initial_values.get(static_index).copied()
+  }
+
+  pub fn initial_values(&self) -> &[u64] {
+    &self.initial_values
+  }
+
+  pub fn len(&self) -> usize {
+    self.statics.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.statics.is_empty()
+  }
 }
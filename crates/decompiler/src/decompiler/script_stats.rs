@@ -0,0 +1,135 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::disassembler::{opcodes::Opcode, Instruction, InstructionInfo};
+
+use super::Function;
+
+/// Opcode and function size statistics of a disassembled script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptStats {
+  /// How often each opcode occurs, by mnemonic
+  pub opcodes:        BTreeMap<&'static str, usize>,
+  /// The size of every function in bytes
+  pub function_sizes: Vec<usize>,
+  pub native_calls:   usize
+}
+
+impl ScriptStats {
+  pub fn collect(instructions: &[InstructionInfo], functions: &[Function]) -> Self {
+    let mut stats = Self::default();
+
+    for info in instructions {
+      if let Some(opcode) = info
+        .bytes
+        .first()
+        .and_then(|byte| Opcode::try_from(*byte).ok())
+      {
+        *stats.opcodes.entry(opcode.mnemonic()).or_default() += 1;
+      }
+      if matches!(info.instruction, Instruction::NativeCall { .. }) {
+        stats.native_calls += 1;
+      }
+    }
+
+    stats.function_sizes = functions
+      .iter()
+      .map(|function| {
+        match function.instructions.last() {
+          Some(last) => last.pos + last.bytes.len() - function.location,
+          None => 0
+        }
+      })
+      .collect();
+
+    stats
+  }
+
+  /// Adds the statistics of `other` to these, for totals over multiple scripts.
+  pub fn merge(&mut self, other: &Self) {
+    for (mnemonic, count) in &other.opcodes {
+      *self.opcodes.entry(mnemonic).or_default() += count;
+    }
+    self.function_sizes.extend(&other.function_sizes);
+    self.native_calls += other.native_calls;
+  }
+
+  pub fn function_count(&self) -> usize {
+    self.function_sizes.len()
+  }
+
+  pub fn average_function_size(&self) -> f64 {
+    if self.function_sizes.is_empty() {
+      0.0
+    } else {
+      self.function_sizes.iter().sum::<usize>() as f64 / self.function_sizes.len() as f64
+    }
+  }
+
+  pub fn max_function_size(&self) -> usize {
+    self
+      .function_sizes
+      .iter()
+      .copied()
+      .max()
+      .unwrap_or_default()
+  }
+}
+
+impl fmt::Display for ScriptStats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "functions:     {}", self.function_count())?;
+    writeln!(
+      f,
+      "average size:  {:.1} bytes",
+      self.average_function_size()
+    )?;
+    writeln!(f, "max size:      {} bytes", self.max_function_size())?;
+    writeln!(f, "native calls:  {}", self.native_calls)?;
+    writeln!(f, "opcodes:")?;
+
+    let mut opcodes = self.opcodes.iter().collect::<Vec<_>>();
+    opcodes.sort_by(|a, b| b.1.cmp(a.1));
+    for (mnemonic, count) in opcodes {
+      writeln!(f, "  {mnemonic:<24} {count}")?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{decompiler::get_functions, disassembler::disassemble, test_utils::Asm};
+
+  #[test]
+  fn stats_count_opcodes_functions_and_natives() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConst1)
+      .op(Opcode::PushConst1)
+      .op(Opcode::Drop)
+      .op(Opcode::NativeCall)
+      .u8(1 << 2)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+
+    let instructions = disassemble(&code).unwrap();
+    let stats = ScriptStats::collect(&instructions, &get_functions(&instructions));
+
+    assert_eq!(stats.opcodes.get(Opcode::Enter.mnemonic()), Some(&2));
+    assert_eq!(stats.opcodes.get(Opcode::Leave.mnemonic()), Some(&2));
+    assert_eq!(stats.opcodes.get(Opcode::PushConst1.mnemonic()), Some(&2));
+    assert_eq!(stats.opcodes.get(Opcode::Drop.mnemonic()), Some(&1));
+    assert_eq!(stats.opcodes.get(Opcode::NativeCall.mnemonic()), Some(&1));
+    assert_eq!(stats.opcodes.values().sum::<usize>(), 8);
+    assert_eq!(stats.native_calls, 1);
+    assert_eq!(stats.function_sizes, vec![15, 8]);
+    assert_eq!(stats.max_function_size(), 15);
+    assert_eq!(stats.average_function_size(), 11.5);
+  }
+}
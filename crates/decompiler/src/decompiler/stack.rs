@@ -2,7 +2,7 @@ use std::{backtrace::Backtrace, collections::VecDeque};
 
 use thiserror::Error;
 
-use crate::script::Script;
+use crate::{resources::NativeParam, script::ScriptTables};
 
 use super::{
   stack_entry::{BinaryOperator, StackEntry, UnaryOperator},
@@ -10,6 +10,9 @@ use super::{
   ValueType, ValueTypeInfo
 };
 
+/// Size of a stack slot in bytes
+const SLOT_SIZE: i64 = 8;
+
 #[derive(Default, Debug, Clone)]
 pub struct Stack<'i> {
   stack: VecDeque<StackEntryInfo<'i>>
@@ -31,14 +34,14 @@ impl<'i> Stack<'i> {
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::Float(val),
       ty:    {
-        let mut ty = LinkedValueType::new_primitive(Primitives::Int);
+        let mut ty = LinkedValueType::new_primitive(Primitives::Float);
         ty.confidence(Confidence::High);
         ty.make_shared()
       }
     })
   }
 
-  pub fn push_string(&mut self, script: &'i Script) -> Result<(), InvalidStackError> {
+  pub fn push_string(&mut self, script: ScriptTables<'i>) -> Result<(), InvalidStackError> {
     static UNKNOWN_STRING: &str = "<UNKNOWN>";
 
     let index = self.pop()?;
@@ -50,7 +53,12 @@ impl<'i> Stack<'i> {
     };
 
     self.stack.push_back(StackEntryInfo {
-      entry: StackEntry::String(script.get_string(n as usize).unwrap_or(UNKNOWN_STRING)),
+      entry: StackEntry::String(
+        usize::try_from(n)
+          .ok()
+          .and_then(|index| script.get_string(index))
+          .unwrap_or(UNKNOWN_STRING)
+      ),
       ty:    {
         let mut ty = LinkedValueType::new_primitive(Primitives::String);
         ty.confidence(Confidence::High);
@@ -79,7 +87,7 @@ impl<'i> Stack<'i> {
 
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::Offset { source, offset },
-      // TODO: CAN OFFSETS BE DYNAMIC?
+      // The offset is only known at runtime, so there is no field to take the type of
       ty:    LinkedValueType::new_primitive(Primitives::Unknown).make_shared()
     });
 
@@ -89,8 +97,13 @@ impl<'i> Stack<'i> {
   pub fn push_const_offset(&mut self, offset: i64) -> Result<(), InvalidStackError> {
     let source = Box::new(self.pop()?);
 
-    let source_type = source.ty.borrow_mut().ref_type();
-    let field = LinkedValueType::struct_field(&source_type, offset as usize);
+    // Negative offsets point in front of the source, they aren't one of its fields
+    let field = if offset < 0 {
+      LinkedValueType::new_primitive(Primitives::Unknown).make_shared()
+    } else {
+      let source_type = source.ty.borrow_mut().ref_type();
+      LinkedValueType::struct_field(&source_type, offset as usize)
+    };
 
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::Offset {
@@ -115,6 +128,10 @@ impl<'i> Stack<'i> {
     let index = Box::new(self.pop()?);
 
     let array_item_type = source.ty.borrow_mut().array_item_type();
+    // items spanning multiple slots are structs of at least that size
+    if array_item_type.borrow().size() < item_size {
+      array_item_type.borrow_mut().struct_size(item_size);
+    }
 
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::ArrayItem {
@@ -219,13 +236,54 @@ impl<'i> Stack<'i> {
     Ok(())
   }
 
+  /// `==` and `!=`, which also compare addresses, e.g. against `NULL`. Operands are only
+  /// hinted as ints when neither of them is a reference.
+  pub fn push_equality(&mut self, op: BinaryOperator) -> Result<(), InvalidStackError> {
+    let compares_refs = self.stack.iter().rev().take(2).any(StackEntryInfo::is_ref);
+
+    let operand_ty = if compares_refs {
+      ValueTypeInfo {
+        ty:         ValueType::Primitive(Primitives::Unknown),
+        confidence: Confidence::None
+      }
+    } else {
+      ValueTypeInfo {
+        ty:         ValueType::Primitive(Primitives::Int),
+        confidence: Confidence::Medium
+      }
+    };
+
+    self.push_binary_operator(Primitives::Bool, operand_ty.clone(), operand_ty, op)
+  }
+
   pub fn push_unary_operator(
     &mut self,
     ty: Primitives,
     operand_type: ValueTypeInfo,
     op: UnaryOperator
   ) -> Result<(), InvalidStackError> {
-    let lhs = Box::new(self.pop()?);
+    let lhs = self.pop()?;
+
+    // `!(a < b)` is pushed as `a >= b`
+    let negated = match (op, &lhs.entry) {
+      (UnaryOperator::Not, StackEntry::BinaryOperator { op, lhs, .. }) => op.negated_for(lhs),
+      _ => None
+    };
+    if let Some(negated) = negated {
+      if let StackEntry::BinaryOperator { lhs: a, rhs: b, .. } = lhs.entry {
+        self.stack.push_back(StackEntryInfo {
+          entry: StackEntry::BinaryOperator {
+            lhs: a,
+            rhs: b,
+            op:  negated
+          },
+          ty:    lhs.ty
+        });
+        return Ok(());
+      }
+    }
+
+    let lhs = Box::new(lhs);
 
     lhs.ty.borrow_mut().hint(operand_type);
 
@@ -320,6 +378,45 @@ impl<'i> Stack<'i> {
 
     source.ty.borrow_mut().hint(from_type);
 
+    // Casts of literals are folded into the converted literal
+    let folded = match (&source.entry, ty) {
+      (StackEntry::Int(val), Primitives::Float) => Some(StackEntry::Float(*val as f32)),
+      (StackEntry::Float(val), Primitives::Int) => Some(StackEntry::Int(*val as i32 as i64)),
+      _ => None
+    };
+    if let Some(entry) = folded {
+      self.stack.push_back(StackEntryInfo {
+        entry,
+        ty: {
+          let mut ty = LinkedValueType::new_primitive(ty);
+          ty.confidence(Confidence::High);
+          ty.make_shared()
+        }
+      });
+      return Ok(());
+    }
+
+    // `(int)(float)x` is only `x` while floats hold every value of `x` exactly, which is
+    // known for booleans. Literals were folded above, other integers above 2^24 lose bits
+    // and the opposite direction truncates, so both are kept.
+    let round_trip = matches!(ty, Primitives::Int)
+      && matches!(
+        source.ty.borrow().get_concrete().ty,
+        ValueType::Primitive(Primitives::Float)
+      );
+    if round_trip {
+      if let StackEntry::Cast { source: int } = &source.entry {
+        let exact = matches!(
+          int.ty.borrow().get_concrete().ty,
+          ValueType::Primitive(Primitives::Bool)
+        );
+        if exact {
+          self.stack.push_back(int.as_ref().clone());
+          return Ok(());
+        }
+      }
+    }
+
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::Cast { source },
       ty:    {
@@ -398,6 +495,23 @@ impl<'i> Stack<'i> {
     Ok(())
   }
 
+  /// `AddU8`/`AddS16`, which compilers also emit to move addresses by whole slots. Those
+  /// additions are pushed as a reference to the slot at the offset, so the address doesn't
+  /// get hinted as an int.
+  pub fn push_const_add(&mut self, value: i64) -> Result<(), InvalidStackError> {
+    let is_address = match self.stack.back() {
+      Some(top) => top.is_ref(),
+      None => false
+    };
+
+    if is_address && value % SLOT_SIZE == 0 {
+      self.push_const_offset(value / SLOT_SIZE)?;
+      self.push_reference()
+    } else {
+      self.push_const_int_binary_operator(BinaryOperator::Add, value)
+    }
+  }
+
   pub fn push_const_int_binary_operator(
     &mut self,
     op: BinaryOperator,
@@ -430,7 +544,11 @@ impl<'i> Stack<'i> {
     Ok(())
   }
 
-  pub fn push_function_call(&mut self, function: &Function) -> Result<(), InvalidStackError> {
+  /// Pops the arguments of a call to `function`, linking their types to its parameters.
+  pub fn pop_function_args(
+    &mut self,
+    function: &Function
+  ) -> Result<Vec<StackEntryInfo<'i>>, InvalidStackError> {
     let mut args: Vec<StackEntryInfo> = self.pop_n(function.parameter_count)?;
     args.reverse();
 
@@ -439,9 +557,15 @@ impl<'i> Stack<'i> {
       if let Some(param) = param_iter.next() {
         LinkedValueType::link(&arg.ty, param);
       }
-      let _ = param_iter.advance_by(arg.entry.size());
+      let _ = param_iter.advance_by(arg.entry.size().saturating_sub(1));
     }
 
+    Ok(args)
+  }
+
+  pub fn push_function_call(&mut self, function: &Function) -> Result<(), InvalidStackError> {
+    let args = self.pop_function_args(function)?;
+
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::FunctionCallResult {
         args,
@@ -457,14 +581,32 @@ impl<'i> Stack<'i> {
     Ok(())
   }
 
-  pub fn push_native_call(
+  /// Pops the arguments of a native call. When `params` declares `Vector3` parameters the
+  /// three floats passed for each of them are grouped into a single vector argument.
+  pub fn pop_native_args(
     &mut self,
     arg_count: usize,
+    params: &[NativeParam]
+  ) -> Result<Vec<StackEntryInfo<'i>>, InvalidStackError> {
+    let mut args = self.pop_n(arg_count)?;
+    args.reverse();
+
+    if !params.iter().any(is_vector_param) {
+      return Ok(args);
+    }
+
+    Ok(match vector_arg_groups(&args, params) {
+      Some(groups) => group_vector_args(args, &groups),
+      None => args
+    })
+  }
+
+  pub fn push_native_call(
+    &mut self,
+    args: Vec<StackEntryInfo<'i>>,
     return_count: usize,
     native_hash: u64
   ) -> Result<(), InvalidStackError> {
-    let mut args = self.pop_n(arg_count)?;
-    args.reverse();
     self.stack.push_back(StackEntryInfo {
       entry: StackEntry::NativeCallResult {
         return_values: return_count,
@@ -522,7 +664,22 @@ impl<'i> Stack<'i> {
     Ok(result)
   }
 
-  pub fn nth_back(&mut self, n: usize) -> Result<StackEntryInfo<'i>, InvalidStackError> {
+  /// Pops `n` slots, or fewer if the stack runs out first, in the order they were pushed.
+  pub fn pop_up_to(&mut self, n: usize) -> Vec<StackEntryInfo<'i>> {
+    let available = self
+      .stack
+      .iter()
+      .map(|entry| entry.entry.size())
+      .sum::<usize>();
+    let mut popped = self
+      .pop_n(n.min(available))
+      .expect("the stack holds the slots that are popped");
+    popped.reverse();
+    popped
+  }
+
+  /// A copy of the top slot of the `n`th entry from the back, the stack is left untouched.
+  pub fn nth_back(&self, n: usize) -> Result<StackEntryInfo<'i>, InvalidStackError> {
     let back = self
       .stack
       .iter()
@@ -533,17 +690,7 @@ impl<'i> Stack<'i> {
       })?
       .clone();
 
-    if back.entry.size() > 1 {
-      let (last, rest) = back.split_off();
-
-      if let Some(rest) = rest {
-        self.stack.push_back(rest);
-      }
-
-      Ok(last)
-    } else {
-      Ok(back)
-    }
+    Ok(back.split_off().0)
   }
 
   pub fn try_make_bitwise_logical(&mut self) -> Result<(), InvalidStackError> {
@@ -593,6 +740,72 @@ impl<'i> Stack<'i> {
   }
 }
 
+fn is_vector_param(param: &NativeParam) -> bool {
+  param.ty == "Vector3"
+}
+
+/// How many arguments are passed for each parameter, `Some` for `Vector3` parameters whose
+/// arguments have to be grouped. `None` if the arguments don't line up with the parameters.
+fn vector_arg_groups(
+  args: &[StackEntryInfo],
+  params: &[NativeParam]
+) -> Option<Vec<Option<usize>>> {
+  let mut sizes = args.iter().map(|arg| arg.entry.size());
+  let mut result = Vec::with_capacity(params.len());
+
+  for param in params {
+    let first = sizes.next()?;
+    if !is_vector_param(param) || first == 3 {
+      result.push(None);
+      continue;
+    }
+
+    let mut count = 1;
+    let mut size = first;
+    while size < 3 {
+      size += sizes.next()?;
+      count += 1;
+    }
+    if size != 3 {
+      return None;
+    }
+
+    result.push(Some(count));
+  }
+
+  sizes.next().is_none().then_some(result)
+}
+
+/// Groups the arguments passed for `Vector3` parameters as laid out by [`vector_arg_groups`].
+fn group_vector_args<'i>(
+  args: Vec<StackEntryInfo<'i>>,
+  groups: &[Option<usize>]
+) -> Vec<StackEntryInfo<'i>> {
+  let mut args = args.into_iter();
+
+  groups
+    .iter()
+    .map(|group| {
+      let Some(count) = group else {
+        return args.next().expect("arguments were counted");
+      };
+
+      let values = args.by_ref().take(*count).collect::<Vec<_>>();
+      for value in &values {
+        value.ty.borrow_mut().hint(ValueTypeInfo {
+          ty:         ValueType::Primitive(Primitives::Float),
+          confidence: Confidence::Medium
+        });
+      }
+
+      StackEntryInfo {
+        entry: StackEntry::ResultStruct { values },
+        ty:    LinkedValueType::new_vector3().make_shared()
+      }
+    })
+    .collect()
+}
+
 #[derive(Debug, Error)]
 #[error("Stack is in an invalid state:\n${backtrace:#?}")]
 pub struct InvalidStackError {
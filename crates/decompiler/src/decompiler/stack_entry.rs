@@ -2,7 +2,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use thiserror::Error;
 
-use super::LinkedValueType;
+use super::{LinkedValueType, Primitives, ValueType};
 
 #[derive(Clone, Debug)]
 pub enum StackEntry<'i> {
@@ -68,6 +68,144 @@ pub struct StackEntryInfo<'i> {
 }
 
 impl<'i> StackEntryInfo<'i> {
+  /// Whether the value is an address, either taken here or of a reference type.
+  pub fn is_ref(&self) -> bool {
+    matches!(self.entry, StackEntry::Ref(_))
+      || matches!(self.ty.borrow().get_concrete().ty, ValueType::Ref(_))
+  }
+
+  /// The logical negation of this condition, comparisons are flipped instead of wrapped
+  /// where that is equivalent, see [`BinaryOperator::negated_for`].
+  pub fn negated(self) -> Self {
+    match self.entry {
+      StackEntry::BinaryOperator { lhs, rhs, op } => {
+        match op.negated_for(&lhs) {
+          Some(op) => {
+            Self {
+              entry: StackEntry::BinaryOperator { lhs, rhs, op },
+              ty:    self.ty
+            }
+          }
+          None => {
+            Self {
+              entry: StackEntry::UnaryOperator {
+                lhs: Box::new(Self {
+                  entry: StackEntry::BinaryOperator { lhs, rhs, op },
+                  ty:    self.ty
+                }),
+                op:  UnaryOperator::Not
+              },
+              ty:    LinkedValueType::new_primitive(Primitives::Bool).make_shared()
+            }
+          }
+        }
+      }
+      StackEntry::UnaryOperator {
+        lhs,
+        op: UnaryOperator::Not
+      } => *lhs,
+      entry => {
+        Self {
+          entry: StackEntry::UnaryOperator {
+            lhs: Box::new(Self { entry, ty: self.ty }),
+            op:  UnaryOperator::Not
+          },
+          ty:    LinkedValueType::new_primitive(Primitives::Bool).make_shared()
+        }
+      }
+    }
+  }
+
+  /// Evaluates operators whose operands are all literals. Integers wrap around like the
+  /// 32 bit values the VM works with, divisions by zero are left as they are.
+  pub fn fold_constants(&mut self) {
+    for child in self.entry.children_mut() {
+      child.fold_constants();
+    }
+
+    let float = matches!(
+      self.ty.borrow().get_concrete().ty,
+      ValueType::Primitive(Primitives::Float)
+    );
+
+    let folded = match &self.entry {
+      StackEntry::BinaryOperator { lhs, rhs, op } if float => {
+        match (lhs.entry.as_float(), rhs.entry.as_float()) {
+          (Some(a), Some(b)) => op.apply_float(a, b).map(StackEntry::Float),
+          _ => None
+        }
+      }
+      StackEntry::BinaryOperator { lhs, rhs, op } => {
+        match (&lhs.entry, &rhs.entry) {
+          (StackEntry::Int(a), StackEntry::Int(b)) => {
+            op.apply_int(*a as i32, *b as i32)
+              .map(|value| StackEntry::Int(value as i64))
+          }
+          _ => None
+        }
+      }
+      StackEntry::UnaryOperator {
+        lhs,
+        op: UnaryOperator::Negate
+      } if float => lhs.entry.as_float().map(|value| StackEntry::Float(-value)),
+      StackEntry::UnaryOperator { lhs, op } => {
+        match (&lhs.entry, op) {
+          (StackEntry::Int(value), UnaryOperator::Negate) => {
+            Some(StackEntry::Int((*value as i32).wrapping_neg() as i64))
+          }
+          (StackEntry::Int(value), UnaryOperator::Not) => {
+            Some(StackEntry::Int((*value == 0) as i64))
+          }
+          _ => None
+        }
+      }
+      _ => None
+    };
+
+    if let Some(folded) = folded {
+      self.entry = folded;
+    }
+  }
+
+  /// Cancels out all `*&x` and `&*x` pairs in this entry and its children. A field of
+  /// `*&x` is kept as it is, it is shown as `x->f`.
+  pub fn simplify(&mut self) {
+    let field_of_deref_ref = matches!(
+      &self.entry,
+      StackEntry::StructField { source, .. } if source.entry.is_deref_ref()
+    );
+
+    for child in self.entry.children_mut() {
+      if field_of_deref_ref {
+        for grandchild in child.entry.children_mut() {
+          grandchild.simplify();
+        }
+      } else {
+        child.simplify();
+      }
+    }
+
+    let cancelled = match &self.entry {
+      StackEntry::Deref(inner) => {
+        match &inner.entry {
+          StackEntry::Ref(value) => Some(value.as_ref().clone()),
+          _ => None
+        }
+      }
+      StackEntry::Ref(inner) => {
+        match &inner.entry {
+          StackEntry::Deref(value) => Some(value.as_ref().clone()),
+          _ => None
+        }
+      }
+      _ => None
+    };
+
+    if let Some(value) = cancelled {
+      *self = value;
+    }
+  }
+
   pub fn split_off(mut self) -> (Self, Option<Self>) {
     // Avoid unnecessary clone
     if self.entry.size() == 1 {
@@ -119,6 +257,149 @@ impl<'i> StackEntryInfo<'i> {
 }
 
 impl<'i> StackEntry<'i> {
+  /// The entries this entry is directly made up of.
+  pub fn children(&self) -> Vec<&StackEntryInfo<'i>> {
+    match self {
+      Self::Int(_)
+      | Self::Float(_)
+      | Self::String(_)
+      | Self::Local(_)
+      | Self::Static(_)
+      | Self::Global(_)
+      | Self::CatchValue => vec![],
+      Self::Struct { origin, .. } => vec![origin],
+      Self::StructField { source, .. }
+      | Self::Deref(source)
+      | Self::Ref(source)
+      | Self::FloatToVector(source)
+      | Self::Cast { source }
+      | Self::StringHash(source)
+      | Self::UnaryOperator { lhs: source, .. } => vec![source],
+      Self::Offset { source, offset } => vec![source, offset],
+      Self::ArrayItem { source, index, .. } => vec![source, index],
+      Self::BinaryOperator { lhs, rhs, .. } => vec![lhs, rhs],
+      Self::ResultStruct { values: entries }
+      | Self::FunctionCallResult { args: entries, .. }
+      | Self::NativeCallResult { args: entries, .. } => entries.iter().collect()
+    }
+  }
+
+  /// Mutable version of [`Self::children`].
+  pub fn children_mut(&mut self) -> Vec<&mut StackEntryInfo<'i>> {
+    match self {
+      Self::Int(_)
+      | Self::Float(_)
+      | Self::String(_)
+      | Self::Local(_)
+      | Self::Static(_)
+      | Self::Global(_)
+      | Self::CatchValue => vec![],
+      Self::Struct { origin, .. } => vec![origin],
+      Self::StructField { source, .. }
+      | Self::Deref(source)
+      | Self::Ref(source)
+      | Self::FloatToVector(source)
+      | Self::Cast { source }
+      | Self::StringHash(source)
+      | Self::UnaryOperator { lhs: source, .. } => vec![source],
+      Self::Offset { source, offset } => vec![source, offset],
+      Self::ArrayItem { source, index, .. } => vec![source, index],
+      Self::BinaryOperator { lhs, rhs, .. } => vec![lhs, rhs],
+      Self::ResultStruct { values: entries }
+      | Self::FunctionCallResult { args: entries, .. }
+      | Self::NativeCallResult { args: entries, .. } => entries.iter_mut().collect()
+    }
+  }
+
+  /// Whether both entries refer to the same value, `*&x` and `x` are considered equal.
+  /// Only entries without side effects are compared, calls are never the same.
+  pub fn is_same(&self, other: &StackEntry<'i>) -> bool {
+    match (self.strip_deref_ref(), other.strip_deref_ref()) {
+      (Self::Int(a), Self::Int(b)) => a == b,
+      (Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+      (Self::String(a), Self::String(b)) => a == b,
+      (Self::Local(a), Self::Local(b))
+      | (Self::Static(a), Self::Static(b))
+      | (Self::Global(a), Self::Global(b)) => a == b,
+      (
+        Self::StructField {
+          source: a,
+          field: a_field
+        },
+        Self::StructField {
+          source: b,
+          field: b_field
+        }
+      ) => a_field == b_field && a.entry.is_same(&b.entry),
+      (
+        Self::Offset {
+          source: a,
+          offset: a_offset
+        },
+        Self::Offset {
+          source: b,
+          offset: b_offset
+        }
+      ) => a.entry.is_same(&b.entry) && a_offset.entry.is_same(&b_offset.entry),
+      (
+        Self::ArrayItem {
+          source: a,
+          index: a_index,
+          item_size: a_size
+        },
+        Self::ArrayItem {
+          source: b,
+          index: b_index,
+          item_size: b_size
+        }
+      ) => a_size == b_size && a.entry.is_same(&b.entry) && a_index.entry.is_same(&b_index.entry),
+      (Self::Deref(a), Self::Deref(b)) | (Self::Ref(a), Self::Ref(b)) => a.entry.is_same(&b.entry),
+      // Loads address the first field of a value, stores the value itself
+      (Self::StructField { source, field: 0 }, other)
+      | (other, Self::StructField { source, field: 0 }) => source.entry.is_same(other),
+      _ => false
+    }
+  }
+
+  /// Whether the entry reads globals, statics or memory behind a pointer. Other script
+  /// threads and natives can change those between two accesses.
+  pub fn accesses_shared_memory(&self) -> bool {
+    match self.strip_deref_ref() {
+      Self::Static(_) | Self::Global(_) | Self::Deref(_) => true,
+      entry => {
+        entry
+          .children()
+          .into_iter()
+          .any(|child| child.entry.accesses_shared_memory())
+      }
+    }
+  }
+
+  fn is_deref_ref(&self) -> bool {
+    matches!(self, Self::Deref(deref) if matches!(deref.entry, Self::Ref(_)))
+  }
+
+  fn strip_deref_ref(&self) -> &Self {
+    match self {
+      Self::Deref(deref) => {
+        match &deref.entry {
+          Self::Ref(rf) => rf.entry.strip_deref_ref(),
+          _ => self
+        }
+      }
+      _ => self
+    }
+  }
+
+  /// The value of a numeric literal as a float, integers typed as floats hold its bits.
+  fn as_float(&self) -> Option<f32> {
+    match self {
+      Self::Float(value) => Some(*value),
+      Self::Int(value) => Some(f32::from_bits(*value as u32)),
+      _ => None
+    }
+  }
+
   pub fn size(&self) -> usize {
     match self {
       Self::Struct { size, .. } => *size,
@@ -134,6 +415,8 @@ impl<'i> StackEntry<'i> {
 #[error("Value cannot be split off")]
 pub struct SplitOffError;
 
+/// Integer operators work on signed 32 bit values. The VM has no shift instructions,
+/// scripts use the `SHIFT_LEFT`/`SHIFT_RIGHT` natives instead.
 #[derive(Copy, Clone, Debug)]
 pub enum BinaryOperator {
   Add,
@@ -150,13 +433,267 @@ pub enum BinaryOperator {
   GreaterOrEqual,
   LowerThan,
   LowerOrEqual,
+  /// Whether bit `rhs` of `lhs` is set, `lhs` is pushed first
   BitTest,
   LogicalAnd,
   LogicalOr
 }
 
+impl BinaryOperator {
+  /// Evaluates an arithmetic or bitwise operator on 32 bit integers.
+  pub fn apply_int(self, a: i32, b: i32) -> Option<i32> {
+    match self {
+      Self::Add => Some(a.wrapping_add(b)),
+      Self::Subtract => Some(a.wrapping_sub(b)),
+      Self::Multiply => Some(a.wrapping_mul(b)),
+      Self::Divide if b != 0 => Some(a.wrapping_div(b)),
+      Self::Modulo if b != 0 => Some(a.wrapping_rem(b)),
+      Self::BitwiseAnd => Some(a & b),
+      Self::BitwiseOr => Some(a | b),
+      Self::BitwiseXor => Some(a ^ b),
+      Self::BitTest => Some(a.wrapping_shr(b as u32) & 1),
+      _ => None
+    }
+  }
+
+  /// Evaluates an arithmetic operator on floats.
+  pub fn apply_float(self, a: f32, b: f32) -> Option<f32> {
+    match self {
+      Self::Add => Some(a + b),
+      Self::Subtract => Some(a - b),
+      Self::Multiply => Some(a * b),
+      Self::Divide if b != 0.0 => Some(a / b),
+      Self::Modulo if b != 0.0 => Some(a % b),
+      _ => None
+    }
+  }
+
+  /// The comparison that yields the logical negation of this one, `None` for
+  /// operators that aren't comparisons.
+  pub fn negated(self) -> Option<Self> {
+    match self {
+      Self::Equal => Some(Self::NotEqual),
+      Self::NotEqual => Some(Self::Equal),
+      Self::GreaterThan => Some(Self::LowerOrEqual),
+      Self::GreaterOrEqual => Some(Self::LowerThan),
+      Self::LowerThan => Some(Self::GreaterOrEqual),
+      Self::LowerOrEqual => Some(Self::GreaterThan),
+      _ => None
+    }
+  }
+
+  /// Like [`Self::negated`] for a comparison of `lhs`. Ordering comparisons of floats are
+  /// kept, `!(a < b)` isn't `a >= b` when either is NaN.
+  pub fn negated_for(self, lhs: &StackEntryInfo) -> Option<Self> {
+    let float = matches!(
+      lhs.ty.borrow().get_concrete().ty,
+      ValueType::Primitive(Primitives::Float)
+    );
+
+    match self {
+      Self::Equal | Self::NotEqual => self.negated(),
+      _ if float => None,
+      _ => self.negated()
+    }
+  }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum UnaryOperator {
   Not,
   Negate
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn info(entry: StackEntry) -> StackEntryInfo {
+    StackEntryInfo {
+      entry,
+      ty: LinkedValueType::new_primitive(Primitives::Int).make_shared()
+    }
+  }
+
+  fn deref(entry: StackEntry) -> StackEntry {
+    StackEntry::Deref(Box::new(info(entry)))
+  }
+
+  fn reference(entry: StackEntry) -> StackEntry {
+    StackEntry::Ref(Box::new(info(entry)))
+  }
+
+  fn field(source: StackEntry, field: usize) -> StackEntry {
+    StackEntry::StructField {
+      source: Box::new(info(source)),
+      field
+    }
+  }
+
+  fn simplified(entry: StackEntry) -> StackEntry {
+    let mut info = info(entry);
+    info.simplify();
+    info.entry
+  }
+
+  #[test]
+  fn simplify_cancels_deref_of_ref_and_ref_of_deref() {
+    assert!(matches!(
+      simplified(deref(reference(StackEntry::Local(2)))),
+      StackEntry::Local(2)
+    ));
+    assert!(matches!(
+      simplified(reference(deref(StackEntry::Local(2)))),
+      StackEntry::Local(2)
+    ));
+    assert!(matches!(
+      simplified(deref(reference(deref(reference(StackEntry::Static(1)))))),
+      StackEntry::Static(1)
+    ));
+  }
+
+  #[test]
+  fn simplify_cancels_nested_pairs() {
+    let StackEntry::StructField { source, field: 1 } =
+      simplified(deref(reference(field(StackEntry::Local(2), 1))))
+    else {
+      panic!("the field was not kept");
+    };
+    assert!(matches!(source.entry, StackEntry::Local(2)));
+
+    let StackEntry::BinaryOperator { lhs, .. } = simplified(StackEntry::BinaryOperator {
+      lhs: Box::new(info(deref(reference(StackEntry::Local(2))))),
+      rhs: Box::new(info(StackEntry::Int(1))),
+      op:  BinaryOperator::Add
+    }) else {
+      panic!("the operator was not kept");
+    };
+    assert!(matches!(lhs.entry, StackEntry::Local(2)));
+  }
+
+  #[test]
+  fn simplify_keeps_fields_of_deref_ref() {
+    let StackEntry::StructField { source, field: 1 } = simplified(field(
+      deref(reference(deref(reference(StackEntry::Local(2))))),
+      1
+    )) else {
+      panic!("the field was not kept");
+    };
+    let StackEntry::Deref(deref) = source.entry else {
+      panic!("the dereference was cancelled");
+    };
+    let StackEntry::Ref(rf) = deref.entry else {
+      panic!("the reference was cancelled");
+    };
+    assert!(matches!(rf.entry, StackEntry::Local(2)));
+  }
+
+  fn float(entry: StackEntry) -> StackEntryInfo {
+    StackEntryInfo {
+      entry,
+      ty: LinkedValueType::new_primitive(Primitives::Float).make_shared()
+    }
+  }
+
+  fn binary(
+    lhs: StackEntryInfo<'static>,
+    op: BinaryOperator,
+    rhs: StackEntryInfo<'static>
+  ) -> StackEntry<'static> {
+    StackEntry::BinaryOperator {
+      lhs: Box::new(lhs),
+      rhs: Box::new(rhs),
+      op
+    }
+  }
+
+  fn folded(mut value: StackEntryInfo) -> StackEntry {
+    value.fold_constants();
+    value.entry
+  }
+
+  #[test]
+  fn fold_constants_evaluates_integer_arithmetic() {
+    // 2 + 3 * 4
+    let product = info(binary(
+      info(StackEntry::Int(3)),
+      BinaryOperator::Multiply,
+      info(StackEntry::Int(4))
+    ));
+    let sum = info(binary(
+      info(StackEntry::Int(2)),
+      BinaryOperator::Add,
+      product
+    ));
+    assert!(matches!(folded(sum), StackEntry::Int(14)));
+
+    let overflow = info(binary(
+      info(StackEntry::Int(i32::MAX as i64)),
+      BinaryOperator::Add,
+      info(StackEntry::Int(1))
+    ));
+    assert!(matches!(folded(overflow), StackEntry::Int(value) if value == i32::MIN as i64));
+
+    let negated = info(StackEntry::UnaryOperator {
+      lhs: Box::new(info(StackEntry::Int(5))),
+      op:  UnaryOperator::Negate
+    });
+    assert!(matches!(folded(negated), StackEntry::Int(-5)));
+  }
+
+  #[test]
+  fn fold_constants_evaluates_float_arithmetic() {
+    let sum = float(binary(
+      float(StackEntry::Float(1.5)),
+      BinaryOperator::Add,
+      float(StackEntry::Float(2.0))
+    ));
+    assert!(matches!(folded(sum), StackEntry::Float(value) if value == 3.5));
+
+    // Integer literals typed as floats hold the bits of the float
+    let bits = float(binary(
+      float(StackEntry::Int(2f32.to_bits() as i64)),
+      BinaryOperator::Multiply,
+      float(StackEntry::Float(4.0))
+    ));
+    assert!(matches!(folded(bits), StackEntry::Float(value) if value == 8.0));
+  }
+
+  #[test]
+  fn fold_constants_evaluates_bit_tests() {
+    // The value is the left operand, the bit the right one
+    let set = info(binary(
+      info(StackEntry::Int(0b1010)),
+      BinaryOperator::BitTest,
+      info(StackEntry::Int(3))
+    ));
+    assert!(matches!(folded(set), StackEntry::Int(1)));
+
+    let unset = info(binary(
+      info(StackEntry::Int(0b1010)),
+      BinaryOperator::BitTest,
+      info(StackEntry::Int(2))
+    ));
+    assert!(matches!(folded(unset), StackEntry::Int(0)));
+  }
+
+  #[test]
+  fn fold_constants_keeps_non_literal_operands() {
+    let mixed = info(binary(
+      info(StackEntry::Local(2)),
+      BinaryOperator::Add,
+      info(StackEntry::Int(1))
+    ));
+    assert!(matches!(folded(mixed), StackEntry::BinaryOperator { .. }));
+
+    let division_by_zero = info(binary(
+      info(StackEntry::Int(1)),
+      BinaryOperator::Divide,
+      info(StackEntry::Int(0))
+    ));
+    assert!(matches!(
+      folded(division_by_zero),
+      StackEntry::BinaryOperator { .. }
+    ));
+  }
+}
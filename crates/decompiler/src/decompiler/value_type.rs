@@ -1,5 +1,7 @@
 use std::{cell::RefCell, rc::Rc};
 
+use serde::Serialize;
+
 #[derive(Debug, Clone, Copy)]
 pub enum Primitives {
   Float,
@@ -9,7 +11,7 @@ pub enum Primitives {
   Unknown
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub enum Confidence {
   None,
   Low,
@@ -46,21 +48,25 @@ pub enum LinkedValueType {
 }
 
 impl LinkedValueType {
-  pub fn link(_a: &Rc<RefCell<LinkedValueType>>, _b: &Rc<RefCell<LinkedValueType>>) {
-    // let a_concrete_ptr = Self::get_concrete_ptr(a);
-    // let b_concrete_ptr = Self::get_concrete_ptr(b);
-    // if Rc::ptr_eq(&a_concrete_ptr, &b_concrete_ptr) {
-    //   return;
-    // }
+  /// Makes `a` and `b` share the same type, keeping the one with the highest confidence.
+  /// Returns `false` if they already shared a type.
+  pub fn link(a: &Rc<RefCell<LinkedValueType>>, b: &Rc<RefCell<LinkedValueType>>) -> bool {
+    let a_concrete_ptr = Self::get_concrete_ptr(a);
+    let b_concrete_ptr = Self::get_concrete_ptr(b);
+    if Rc::ptr_eq(&a_concrete_ptr, &b_concrete_ptr) {
+      return false;
+    }
+
+    let a_concrete = a_concrete_ptr.borrow().get_concrete();
+    let b_concrete = b_concrete_ptr.borrow().get_concrete();
 
-    // let a_concrete = a.borrow().get_concrete();
-    // let b_concrete = b.borrow().get_concrete();
+    if a_concrete.confidence > b_concrete.confidence {
+      *b_concrete_ptr.borrow_mut() = LinkedValueType::Redirect(a_concrete_ptr.clone())
+    } else {
+      *a_concrete_ptr.borrow_mut() = LinkedValueType::Redirect(b_concrete_ptr.clone())
+    }
 
-    // if a_concrete.confidence > b_concrete.confidence {
-    //   *b_concrete_ptr.borrow_mut() = LinkedValueType::Redirect(a.clone())
-    // } else {
-    //   *a_concrete_ptr.borrow_mut() = LinkedValueType::Redirect(b.clone())
-    // }
+    true
   }
 
   pub fn new_primitive(primitive: Primitives) -> Self {
@@ -210,7 +216,8 @@ impl LinkedValueType {
     }
   }
 
-  pub fn hint(&mut self, ty: ValueTypeInfo) {
+  /// Returns whether the hint changed the type.
+  pub fn hint(&mut self, ty: ValueTypeInfo) -> bool {
     match self {
       LinkedValueType::Type(t) => {
         match (&ty.ty, &t.ty) {
@@ -222,6 +229,9 @@ impl LinkedValueType {
             if ty.confidence > t.confidence {
               t.ty = ty.ty;
               t.confidence = ty.confidence;
+              true
+            } else {
+              false
             }
           }
         }
@@ -252,11 +262,11 @@ impl LinkedValueType {
     }
   }
 
-  // fn get_concrete_ptr(ty: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
-  //   let rf: &Self = &ty.borrow();
-  //   match rf {
-  //     LinkedValueType::Type(_) => ty.clone(),
-  //     LinkedValueType::Redirect(r) => Self::get_concrete_ptr(r)
-  //   }
-  // }
+  fn get_concrete_ptr(ty: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
+    let rf: &Self = &ty.borrow();
+    match rf {
+      LinkedValueType::Type(_) => ty.clone(),
+      LinkedValueType::Redirect(r) => Self::get_concrete_ptr(r)
+    }
+  }
 }
@@ -0,0 +1,51 @@
+use super::DisassembleError;
+
+/// Little endian reads from script code, in place of `binary_reader` which needs `std`.
+pub(super) struct CodeReader<'a> {
+  code:    &'a [u8],
+  pub pos: usize
+}
+
+impl<'a> CodeReader<'a> {
+  pub fn new(code: &'a [u8], pos: usize) -> Self {
+    Self { code, pos }
+  }
+
+  pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], DisassembleError> {
+    let bytes = self
+      .code
+      .get(self.pos..self.pos + length)
+      .ok_or(DisassembleError::UnexpectedEnd { pos: self.pos })?;
+    self.pos += length;
+    Ok(bytes)
+  }
+
+  fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DisassembleError> {
+    Ok(self.read_bytes(N)?.try_into().unwrap())
+  }
+
+  pub fn read_u8(&mut self) -> Result<u8, DisassembleError> {
+    Ok(self.read_array::<1>()?[0])
+  }
+
+  pub fn read_u16(&mut self) -> Result<u16, DisassembleError> {
+    Ok(u16::from_le_bytes(self.read_array()?))
+  }
+
+  pub fn read_i16(&mut self) -> Result<i16, DisassembleError> {
+    Ok(i16::from_le_bytes(self.read_array()?))
+  }
+
+  pub fn read_u24(&mut self) -> Result<u32, DisassembleError> {
+    let [a, b, c] = self.read_array()?;
+    Ok(u32::from_le_bytes([a, b, c, 0]))
+  }
+
+  pub fn read_u32(&mut self) -> Result<u32, DisassembleError> {
+    Ok(u32::from_le_bytes(self.read_array()?))
+  }
+
+  pub fn read_f32(&mut self) -> Result<f32, DisassembleError> {
+    Ok(f32::from_le_bytes(self.read_array()?))
+  }
+}
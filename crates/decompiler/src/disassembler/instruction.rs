@@ -1,3 +1,11 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+  hash::{Hash, Hasher},
+  mem
+};
+
+use super::opcodes::Opcode;
+
 /// Represents a ysc instruction.
 ///
 /// Details based on <https://github.com/alexguirre/gtav-sc-tools/blob/master/docs/InstructionSet.md>.
@@ -1381,8 +1389,552 @@ pub enum Instruction {
   BitTest
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Instruction {
+  /// The opcode this instruction is encoded with.
+  pub fn opcode(&self) -> Opcode {
+    match self {
+      Self::Nop => Opcode::Nop,
+      Self::IntegerAdd => Opcode::IntegerAdd,
+      Self::IntegerSubtract => Opcode::IntegerSubtract,
+      Self::IntegerMultiply => Opcode::IntegerMultiply,
+      Self::IntegerDivide => Opcode::IntegerDivide,
+      Self::IntegerModulo => Opcode::IntegerModulo,
+      Self::IntegerNot => Opcode::IntegerNot,
+      Self::IntegerNegate => Opcode::IntegerNegate,
+      Self::IntegerEquals => Opcode::IntegerEquals,
+      Self::IntegerNotEquals => Opcode::IntegerNotEquals,
+      Self::IntegerGreaterThan => Opcode::IntegerGreaterThan,
+      Self::IntegerGreaterOrEqual => Opcode::IntegerGreaterOrEqual,
+      Self::IntegerLowerThan => Opcode::IntegerLowerThan,
+      Self::IntegerLowerOrEqual => Opcode::IntegerLowerOrEqual,
+      Self::FloatAdd => Opcode::FloatAdd,
+      Self::FloatSubtract => Opcode::FloatSubtract,
+      Self::FloatMultiply => Opcode::FloatMultiply,
+      Self::FloatDivide => Opcode::FloatDivide,
+      Self::FloatModule => Opcode::FloatModule,
+      Self::FloatNegate => Opcode::FloatNegate,
+      Self::FloatEquals => Opcode::FloatEquals,
+      Self::FloatNotEquals => Opcode::FloatNotEquals,
+      Self::FloatGreaterThan => Opcode::FloatGreaterThan,
+      Self::FloatGreaterOrEqual => Opcode::FloatGreaterOrEqual,
+      Self::FloatLowerThan => Opcode::FloatLowerThan,
+      Self::FloatLowerOrEqual => Opcode::FloatLowerOrEqual,
+      Self::VectorAdd => Opcode::VectorAdd,
+      Self::VectorSubtract => Opcode::VectorSubtract,
+      Self::VectorMultiply => Opcode::VectorMultiply,
+      Self::VectorDivide => Opcode::VectorDivide,
+      Self::VectorNegate => Opcode::VectorNegate,
+      Self::BitwiseAnd => Opcode::BitwiseAnd,
+      Self::BitwiseOr => Opcode::BitwiseOr,
+      Self::BitwiseXor => Opcode::BitwiseXor,
+      Self::IntegerToFloat => Opcode::IntegerToFloat,
+      Self::FloatToInteger => Opcode::FloatToInteger,
+      Self::FloatToVector => Opcode::FloatToVector,
+      Self::PushConstU8 { .. } => Opcode::PushConstU8,
+      Self::PushConstU8U8 { .. } => Opcode::PushConstU8U8,
+      Self::PushConstU8U8U8 { .. } => Opcode::PushConstU8U8U8,
+      Self::PushConstU32 { .. } => Opcode::PushConstU32,
+      Self::PushConstFloat { .. } => Opcode::PushConstFloat,
+      Self::Dup => Opcode::Dup,
+      Self::Drop => Opcode::Drop,
+      Self::NativeCall { .. } => Opcode::NativeCall,
+      Self::Enter { .. } => Opcode::Enter,
+      Self::Leave { .. } => Opcode::Leave,
+      Self::Load => Opcode::Load,
+      Self::Store => Opcode::Store,
+      Self::StoreRev => Opcode::StoreRev,
+      Self::LoadN => Opcode::LoadN,
+      Self::StoreN => Opcode::StoreN,
+      Self::ArrayU8 { .. } => Opcode::ArrayU8,
+      Self::ArrayU8Load { .. } => Opcode::ArrayU8Load,
+      Self::ArrayU8Store { .. } => Opcode::ArrayU8Store,
+      Self::LocalU8 { .. } => Opcode::LocalU8,
+      Self::LocalU8Load { .. } => Opcode::LocalU8Load,
+      Self::LocalU8Store { .. } => Opcode::LocalU8Store,
+      Self::StaticU8 { .. } => Opcode::StaticU8,
+      Self::StaticU8Load { .. } => Opcode::StaticU8Load,
+      Self::StaticU8Store { .. } => Opcode::StaticU8Store,
+      Self::AddU8 { .. } => Opcode::AddU8,
+      Self::MultiplyU8 { .. } => Opcode::MultiplyU8,
+      Self::Offset => Opcode::Offset,
+      Self::OffsetU8 { .. } => Opcode::OffsetU8,
+      Self::OffsetU8Load { .. } => Opcode::OffsetU8Load,
+      Self::OffsetU8Store { .. } => Opcode::OffsetU8Store,
+      Self::PushConstS16 { .. } => Opcode::PushConstS16,
+      Self::AddS16 { .. } => Opcode::AddS16,
+      Self::MultiplyS16 { .. } => Opcode::MultiplyS16,
+      Self::OffsetS16 { .. } => Opcode::OffsetS16,
+      Self::OffsetS16Load { .. } => Opcode::OffsetS16Load,
+      Self::OffsetS16Store { .. } => Opcode::OffsetS16Store,
+      Self::ArrayU16 { .. } => Opcode::ArrayU16,
+      Self::ArrayU16Load { .. } => Opcode::ArrayU16Load,
+      Self::ArrayU16Store { .. } => Opcode::ArrayU16Store,
+      Self::LocalU16 { .. } => Opcode::LocalU16,
+      Self::LocalU16Load { .. } => Opcode::LocalU16Load,
+      Self::LocalU16Store { .. } => Opcode::LocalU16Store,
+      Self::StaticU16 { .. } => Opcode::StaticU16,
+      Self::StaticU16Load { .. } => Opcode::StaticU16Load,
+      Self::StaticU16Store { .. } => Opcode::StaticU16Store,
+      Self::GlobalU16 { .. } => Opcode::GlobalU16,
+      Self::GlobalU16Load { .. } => Opcode::GlobalU16Load,
+      Self::GlobalU16Store { .. } => Opcode::GlobalU16Store,
+      Self::Jump { .. } => Opcode::Jump,
+      Self::JumpZero { .. } => Opcode::JumpZero,
+      Self::IfEqualJumpZero { .. } => Opcode::IfEqualJumpZero,
+      Self::IfNotEqualJumpZero { .. } => Opcode::IfNotEqualJumpZero,
+      Self::IfGreaterThanJumpZero { .. } => Opcode::IfGreaterThanJumpZero,
+      Self::IfGreaterOrEqualJumpZero { .. } => Opcode::IfGreaterOrEqualJumpZero,
+      Self::IfLowerThanJumpZero { .. } => Opcode::IfLowerThanJumpZero,
+      Self::IfLowerOrEqualJumpZero { .. } => Opcode::IfLowerOrEqualJumpZero,
+      Self::FunctionCall { .. } => Opcode::FunctionCall,
+      Self::StaticU24 { .. } => Opcode::StaticU24,
+      Self::StaticU24Load { .. } => Opcode::StaticU24Load,
+      Self::StaticU24Store { .. } => Opcode::StaticU24Store,
+      Self::GlobalU24 { .. } => Opcode::GlobalU24,
+      Self::GlobalU24Load { .. } => Opcode::GlobalU24Load,
+      Self::GlobalU24Store { .. } => Opcode::GlobalU24Store,
+      Self::PushConstU24 { .. } => Opcode::PushConstU24,
+      Self::Switch { .. } => Opcode::Switch,
+      Self::String => Opcode::String,
+      Self::StringHash => Opcode::StringHash,
+      Self::TextLabelAssignString { .. } => Opcode::TextLabelAssignString,
+      Self::TextLabelAssignInt { .. } => Opcode::TextLabelAssignInt,
+      Self::TextLabelAppendString { .. } => Opcode::TextLabelAppendString,
+      Self::TextLabelAppendInt { .. } => Opcode::TextLabelAppendInt,
+      Self::TextLabelCopy => Opcode::TextLabelCopy,
+      Self::Catch => Opcode::Catch,
+      Self::Throw => Opcode::Throw,
+      Self::CallIndirect => Opcode::CallIndirect,
+      Self::PushConstM1 => Opcode::PushConstM1,
+      Self::PushConst0 => Opcode::PushConst0,
+      Self::PushConst1 => Opcode::PushConst1,
+      Self::PushConst2 => Opcode::PushConst2,
+      Self::PushConst3 => Opcode::PushConst3,
+      Self::PushConst4 => Opcode::PushConst4,
+      Self::PushConst5 => Opcode::PushConst5,
+      Self::PushConst6 => Opcode::PushConst6,
+      Self::PushConst7 => Opcode::PushConst7,
+      Self::PushConstFm1 => Opcode::PushConstFm1,
+      Self::PushConstF0 => Opcode::PushConstF0,
+      Self::PushConstF1 => Opcode::PushConstF1,
+      Self::PushConstF2 => Opcode::PushConstF2,
+      Self::PushConstF3 => Opcode::PushConstF3,
+      Self::PushConstF4 => Opcode::PushConstF4,
+      Self::PushConstF5 => Opcode::PushConstF5,
+      Self::PushConstF6 => Opcode::PushConstF6,
+      Self::PushConstF7 => Opcode::PushConstF7,
+      Self::BitTest => Opcode::BitTest
+    }
+  }
+}
+
+/// Instructions are compared by their operands, float constants by their bit pattern.
+impl PartialEq for Instruction {
+  fn eq(&self, other: &Self) -> bool {
+    match self {
+      Self::Nop => matches!(other, Self::Nop),
+      Self::IntegerAdd => matches!(other, Self::IntegerAdd),
+      Self::IntegerSubtract => matches!(other, Self::IntegerSubtract),
+      Self::IntegerMultiply => matches!(other, Self::IntegerMultiply),
+      Self::IntegerDivide => matches!(other, Self::IntegerDivide),
+      Self::IntegerModulo => matches!(other, Self::IntegerModulo),
+      Self::IntegerNot => matches!(other, Self::IntegerNot),
+      Self::IntegerNegate => matches!(other, Self::IntegerNegate),
+      Self::IntegerEquals => matches!(other, Self::IntegerEquals),
+      Self::IntegerNotEquals => matches!(other, Self::IntegerNotEquals),
+      Self::IntegerGreaterThan => matches!(other, Self::IntegerGreaterThan),
+      Self::IntegerGreaterOrEqual => matches!(other, Self::IntegerGreaterOrEqual),
+      Self::IntegerLowerThan => matches!(other, Self::IntegerLowerThan),
+      Self::IntegerLowerOrEqual => matches!(other, Self::IntegerLowerOrEqual),
+      Self::FloatAdd => matches!(other, Self::FloatAdd),
+      Self::FloatSubtract => matches!(other, Self::FloatSubtract),
+      Self::FloatMultiply => matches!(other, Self::FloatMultiply),
+      Self::FloatDivide => matches!(other, Self::FloatDivide),
+      Self::FloatModule => matches!(other, Self::FloatModule),
+      Self::FloatNegate => matches!(other, Self::FloatNegate),
+      Self::FloatEquals => matches!(other, Self::FloatEquals),
+      Self::FloatNotEquals => matches!(other, Self::FloatNotEquals),
+      Self::FloatGreaterThan => matches!(other, Self::FloatGreaterThan),
+      Self::FloatGreaterOrEqual => matches!(other, Self::FloatGreaterOrEqual),
+      Self::FloatLowerThan => matches!(other, Self::FloatLowerThan),
+      Self::FloatLowerOrEqual => matches!(other, Self::FloatLowerOrEqual),
+      Self::VectorAdd => matches!(other, Self::VectorAdd),
+      Self::VectorSubtract => matches!(other, Self::VectorSubtract),
+      Self::VectorMultiply => matches!(other, Self::VectorMultiply),
+      Self::VectorDivide => matches!(other, Self::VectorDivide),
+      Self::VectorNegate => matches!(other, Self::VectorNegate),
+      Self::BitwiseAnd => matches!(other, Self::BitwiseAnd),
+      Self::BitwiseOr => matches!(other, Self::BitwiseOr),
+      Self::BitwiseXor => matches!(other, Self::BitwiseXor),
+      Self::IntegerToFloat => matches!(other, Self::IntegerToFloat),
+      Self::FloatToInteger => matches!(other, Self::FloatToInteger),
+      Self::FloatToVector => matches!(other, Self::FloatToVector),
+      Self::PushConstU8 { c1 } => {
+        matches!(other, Self::PushConstU8 { c1: other_c1 } if c1 == other_c1)
+      }
+      Self::PushConstU8U8 { c1, c2 } => {
+        matches!(other, Self::PushConstU8U8 { c1: other_c1, c2: other_c2 } if c1 == other_c1 && c2 == other_c2)
+      }
+      Self::PushConstU8U8U8 { c1, c2, c3 } => {
+        matches!(other, Self::PushConstU8U8U8 { c1: other_c1, c2: other_c2, c3: other_c3 } if c1 == other_c1 && c2 == other_c2 && c3 == other_c3)
+      }
+      Self::PushConstU32 { c1 } => {
+        matches!(other, Self::PushConstU32 { c1: other_c1 } if c1 == other_c1)
+      }
+      Self::PushConstFloat { c1 } => {
+        matches!(other, Self::PushConstFloat { c1: other_c1 } if c1.to_bits() == other_c1.to_bits())
+      }
+      Self::Dup => matches!(other, Self::Dup),
+      Self::Drop => matches!(other, Self::Drop),
+      Self::NativeCall {
+        arg_count,
+        return_count,
+        native_index
+      } => {
+        matches!(other, Self::NativeCall { arg_count: other_arg_count, return_count: other_return_count, native_index: other_native_index } if arg_count == other_arg_count && return_count == other_return_count && native_index == other_native_index)
+      }
+      Self::Enter {
+        arg_count,
+        frame_size,
+        name
+      } => {
+        matches!(other, Self::Enter { arg_count: other_arg_count, frame_size: other_frame_size, name: other_name } if arg_count == other_arg_count && frame_size == other_frame_size && name == other_name)
+      }
+      Self::Leave {
+        parameter_count,
+        return_count
+      } => {
+        matches!(other, Self::Leave { parameter_count: other_parameter_count, return_count: other_return_count } if parameter_count == other_parameter_count && return_count == other_return_count)
+      }
+      Self::Load => matches!(other, Self::Load),
+      Self::Store => matches!(other, Self::Store),
+      Self::StoreRev => matches!(other, Self::StoreRev),
+      Self::LoadN => matches!(other, Self::LoadN),
+      Self::StoreN => matches!(other, Self::StoreN),
+      Self::ArrayU8 { item_size } => {
+        matches!(other, Self::ArrayU8 { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::ArrayU8Load { item_size } => {
+        matches!(other, Self::ArrayU8Load { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::ArrayU8Store { item_size } => {
+        matches!(other, Self::ArrayU8Store { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::LocalU8 { offset } => {
+        matches!(other, Self::LocalU8 { offset: other_offset } if offset == other_offset)
+      }
+      Self::LocalU8Load { offset } => {
+        matches!(other, Self::LocalU8Load { offset: other_offset } if offset == other_offset)
+      }
+      Self::LocalU8Store { offset } => {
+        matches!(other, Self::LocalU8Store { offset: other_offset } if offset == other_offset)
+      }
+      Self::StaticU8 { static_index } => {
+        matches!(other, Self::StaticU8 { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU8Load { static_index } => {
+        matches!(other, Self::StaticU8Load { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU8Store { static_index } => {
+        matches!(other, Self::StaticU8Store { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::AddU8 { value } => {
+        matches!(other, Self::AddU8 { value: other_value } if value == other_value)
+      }
+      Self::MultiplyU8 { value } => {
+        matches!(other, Self::MultiplyU8 { value: other_value } if value == other_value)
+      }
+      Self::Offset => matches!(other, Self::Offset),
+      Self::OffsetU8 { offset } => {
+        matches!(other, Self::OffsetU8 { offset: other_offset } if offset == other_offset)
+      }
+      Self::OffsetU8Load { offset } => {
+        matches!(other, Self::OffsetU8Load { offset: other_offset } if offset == other_offset)
+      }
+      Self::OffsetU8Store { offset } => {
+        matches!(other, Self::OffsetU8Store { offset: other_offset } if offset == other_offset)
+      }
+      Self::PushConstS16 { c1 } => {
+        matches!(other, Self::PushConstS16 { c1: other_c1 } if c1 == other_c1)
+      }
+      Self::AddS16 { value } => {
+        matches!(other, Self::AddS16 { value: other_value } if value == other_value)
+      }
+      Self::MultiplyS16 { value } => {
+        matches!(other, Self::MultiplyS16 { value: other_value } if value == other_value)
+      }
+      Self::OffsetS16 { offset } => {
+        matches!(other, Self::OffsetS16 { offset: other_offset } if offset == other_offset)
+      }
+      Self::OffsetS16Load { offset } => {
+        matches!(other, Self::OffsetS16Load { offset: other_offset } if offset == other_offset)
+      }
+      Self::OffsetS16Store { offset } => {
+        matches!(other, Self::OffsetS16Store { offset: other_offset } if offset == other_offset)
+      }
+      Self::ArrayU16 { item_size } => {
+        matches!(other, Self::ArrayU16 { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::ArrayU16Load { item_size } => {
+        matches!(other, Self::ArrayU16Load { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::ArrayU16Store { item_size } => {
+        matches!(other, Self::ArrayU16Store { item_size: other_item_size } if item_size == other_item_size)
+      }
+      Self::LocalU16 { local_index } => {
+        matches!(other, Self::LocalU16 { local_index: other_local_index } if local_index == other_local_index)
+      }
+      Self::LocalU16Load { local_index } => {
+        matches!(other, Self::LocalU16Load { local_index: other_local_index } if local_index == other_local_index)
+      }
+      Self::LocalU16Store { local_index } => {
+        matches!(other, Self::LocalU16Store { local_index: other_local_index } if local_index == other_local_index)
+      }
+      Self::StaticU16 { static_index } => {
+        matches!(other, Self::StaticU16 { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU16Load { static_index } => {
+        matches!(other, Self::StaticU16Load { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU16Store { static_index } => {
+        matches!(other, Self::StaticU16Store { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::GlobalU16 { global_index } => {
+        matches!(other, Self::GlobalU16 { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::GlobalU16Load { global_index } => {
+        matches!(other, Self::GlobalU16Load { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::GlobalU16Store { global_index } => {
+        matches!(other, Self::GlobalU16Store { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::Jump { location } => {
+        matches!(other, Self::Jump { location: other_location } if location == other_location)
+      }
+      Self::JumpZero { location } => {
+        matches!(other, Self::JumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfEqualJumpZero { location } => {
+        matches!(other, Self::IfEqualJumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfNotEqualJumpZero { location } => {
+        matches!(other, Self::IfNotEqualJumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfGreaterThanJumpZero { location } => {
+        matches!(other, Self::IfGreaterThanJumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfGreaterOrEqualJumpZero { location } => {
+        matches!(other, Self::IfGreaterOrEqualJumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfLowerThanJumpZero { location } => {
+        matches!(other, Self::IfLowerThanJumpZero { location: other_location } if location == other_location)
+      }
+      Self::IfLowerOrEqualJumpZero { location } => {
+        matches!(other, Self::IfLowerOrEqualJumpZero { location: other_location } if location == other_location)
+      }
+      Self::FunctionCall { location } => {
+        matches!(other, Self::FunctionCall { location: other_location } if location == other_location)
+      }
+      Self::StaticU24 { static_index } => {
+        matches!(other, Self::StaticU24 { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU24Load { static_index } => {
+        matches!(other, Self::StaticU24Load { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::StaticU24Store { static_index } => {
+        matches!(other, Self::StaticU24Store { static_index: other_static_index } if static_index == other_static_index)
+      }
+      Self::GlobalU24 { global_index } => {
+        matches!(other, Self::GlobalU24 { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::GlobalU24Load { global_index } => {
+        matches!(other, Self::GlobalU24Load { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::GlobalU24Store { global_index } => {
+        matches!(other, Self::GlobalU24Store { global_index: other_global_index } if global_index == other_global_index)
+      }
+      Self::PushConstU24 { c1 } => {
+        matches!(other, Self::PushConstU24 { c1: other_c1 } if c1 == other_c1)
+      }
+      Self::Switch { cases } => {
+        matches!(other, Self::Switch { cases: other_cases } if cases == other_cases)
+      }
+      Self::String => matches!(other, Self::String),
+      Self::StringHash => matches!(other, Self::StringHash),
+      Self::TextLabelAssignString { buffer_size } => {
+        matches!(other, Self::TextLabelAssignString { buffer_size: other_buffer_size } if buffer_size == other_buffer_size)
+      }
+      Self::TextLabelAssignInt { buffer_size } => {
+        matches!(other, Self::TextLabelAssignInt { buffer_size: other_buffer_size } if buffer_size == other_buffer_size)
+      }
+      Self::TextLabelAppendString { buffer_size } => {
+        matches!(other, Self::TextLabelAppendString { buffer_size: other_buffer_size } if buffer_size == other_buffer_size)
+      }
+      Self::TextLabelAppendInt { buffer_size } => {
+        matches!(other, Self::TextLabelAppendInt { buffer_size: other_buffer_size } if buffer_size == other_buffer_size)
+      }
+      Self::TextLabelCopy => matches!(other, Self::TextLabelCopy),
+      Self::Catch => matches!(other, Self::Catch),
+      Self::Throw => matches!(other, Self::Throw),
+      Self::CallIndirect => matches!(other, Self::CallIndirect),
+      Self::PushConstM1 => matches!(other, Self::PushConstM1),
+      Self::PushConst0 => matches!(other, Self::PushConst0),
+      Self::PushConst1 => matches!(other, Self::PushConst1),
+      Self::PushConst2 => matches!(other, Self::PushConst2),
+      Self::PushConst3 => matches!(other, Self::PushConst3),
+      Self::PushConst4 => matches!(other, Self::PushConst4),
+      Self::PushConst5 => matches!(other, Self::PushConst5),
+      Self::PushConst6 => matches!(other, Self::PushConst6),
+      Self::PushConst7 => matches!(other, Self::PushConst7),
+      Self::PushConstFm1 => matches!(other, Self::PushConstFm1),
+      Self::PushConstF0 => matches!(other, Self::PushConstF0),
+      Self::PushConstF1 => matches!(other, Self::PushConstF1),
+      Self::PushConstF2 => matches!(other, Self::PushConstF2),
+      Self::PushConstF3 => matches!(other, Self::PushConstF3),
+      Self::PushConstF4 => matches!(other, Self::PushConstF4),
+      Self::PushConstF5 => matches!(other, Self::PushConstF5),
+      Self::PushConstF6 => matches!(other, Self::PushConstF6),
+      Self::PushConstF7 => matches!(other, Self::PushConstF7),
+      Self::BitTest => matches!(other, Self::BitTest)
+    }
+  }
+}
+
+impl Eq for Instruction {}
+
+impl Hash for Instruction {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    mem::discriminant(self).hash(state);
+    match self {
+      Self::PushConstU8 { c1 } => c1.hash(state),
+      Self::PushConstU8U8 { c1, c2 } => {
+        c1.hash(state);
+        c2.hash(state);
+      }
+      Self::PushConstU8U8U8 { c1, c2, c3 } => {
+        c1.hash(state);
+        c2.hash(state);
+        c3.hash(state);
+      }
+      Self::PushConstU32 { c1 } => c1.hash(state),
+      Self::PushConstFloat { c1 } => c1.to_bits().hash(state),
+      Self::NativeCall {
+        arg_count,
+        return_count,
+        native_index
+      } => {
+        arg_count.hash(state);
+        return_count.hash(state);
+        native_index.hash(state);
+      }
+      Self::Enter {
+        arg_count,
+        frame_size,
+        name
+      } => {
+        arg_count.hash(state);
+        frame_size.hash(state);
+        name.hash(state);
+      }
+      Self::Leave {
+        parameter_count,
+        return_count
+      } => {
+        parameter_count.hash(state);
+        return_count.hash(state);
+      }
+      Self::ArrayU8 { item_size } => item_size.hash(state),
+      Self::ArrayU8Load { item_size } => item_size.hash(state),
+      Self::ArrayU8Store { item_size } => item_size.hash(state),
+      Self::LocalU8 { offset } => offset.hash(state),
+      Self::LocalU8Load { offset } => offset.hash(state),
+      Self::LocalU8Store { offset } => offset.hash(state),
+      Self::StaticU8 { static_index } => static_index.hash(state),
+      Self::StaticU8Load { static_index } => static_index.hash(state),
+      Self::StaticU8Store { static_index } => static_index.hash(state),
+      Self::AddU8 { value } => value.hash(state),
+      Self::MultiplyU8 { value } => value.hash(state),
+      Self::OffsetU8 { offset } => offset.hash(state),
+      Self::OffsetU8Load { offset } => offset.hash(state),
+      Self::OffsetU8Store { offset } => offset.hash(state),
+      Self::PushConstS16 { c1 } => c1.hash(state),
+      Self::AddS16 { value } => value.hash(state),
+      Self::MultiplyS16 { value } => value.hash(state),
+      Self::OffsetS16 { offset } => offset.hash(state),
+      Self::OffsetS16Load { offset } => offset.hash(state),
+      Self::OffsetS16Store { offset } => offset.hash(state),
+      Self::ArrayU16 { item_size } => item_size.hash(state),
+      Self::ArrayU16Load { item_size } => item_size.hash(state),
+      Self::ArrayU16Store { item_size } => item_size.hash(state),
+      Self::LocalU16 { local_index } => local_index.hash(state),
+      Self::LocalU16Load { local_index } => local_index.hash(state),
+      Self::LocalU16Store { local_index } => local_index.hash(state),
+      Self::StaticU16 { static_index } => static_index.hash(state),
+      Self::StaticU16Load { static_index } => static_index.hash(state),
+      Self::StaticU16Store { static_index } => static_index.hash(state),
+      Self::GlobalU16 { global_index } => global_index.hash(state),
+      Self::GlobalU16Load { global_index } => global_index.hash(state),
+      Self::GlobalU16Store { global_index } => global_index.hash(state),
+      Self::Jump { location } => location.hash(state),
+      Self::JumpZero { location } => location.hash(state),
+      Self::IfEqualJumpZero { location } => location.hash(state),
+      Self::IfNotEqualJumpZero { location } => location.hash(state),
+      Self::IfGreaterThanJumpZero { location } => location.hash(state),
+      Self::IfGreaterOrEqualJumpZero { location } => location.hash(state),
+      Self::IfLowerThanJumpZero { location } => location.hash(state),
+      Self::IfLowerOrEqualJumpZero { location } => location.hash(state),
+      Self::FunctionCall { location } => location.hash(state),
+      Self::StaticU24 { static_index } => static_index.hash(state),
+      Self::StaticU24Load { static_index } => static_index.hash(state),
+      Self::StaticU24Store { static_index } => static_index.hash(state),
+      Self::GlobalU24 { global_index } => global_index.hash(state),
+      Self::GlobalU24Load { global_index } => global_index.hash(state),
+      Self::GlobalU24Store { global_index } => global_index.hash(state),
+      Self::PushConstU24 { c1 } => c1.hash(state),
+      Self::Switch { cases } => cases.hash(state),
+      Self::TextLabelAssignString { buffer_size } => buffer_size.hash(state),
+      Self::TextLabelAssignInt { buffer_size } => buffer_size.hash(state),
+      Self::TextLabelAppendString { buffer_size } => buffer_size.hash(state),
+      Self::TextLabelAppendInt { buffer_size } => buffer_size.hash(state),
+      _ => {}
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SwitchCase {
   pub value:    u32,
   pub location: u32
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+  use crate::disassembler::{disassemble, opcodes::Opcode};
+
+  #[test]
+  fn instructions_compare_by_operands() {
+    let push = |value: f32| {
+      let mut code = vec![Opcode::PushConstFloat.into()];
+      code.extend(value.to_bits().to_le_bytes());
+      disassemble(&code).unwrap().remove(0).instruction
+    };
+
+    assert_eq!(push(1.5), push(1.5));
+    assert_ne!(push(1.5), push(2.5));
+    // Compared by their bits, so NaN equals itself and 0 differs from -0
+    assert_eq!(push(f32::NAN), push(f32::NAN));
+    assert_ne!(push(0.0), push(-0.0));
+    assert_ne!(
+      Instruction::PushConstU8 { c1: 1 },
+      Instruction::PushConstS16 { c1: 1 }
+    );
+
+    let unique = [push(1.5), push(1.5), push(2.5)]
+      .into_iter()
+      .collect::<HashSet<_>>();
+    assert_eq!(unique.len(), 2);
+  }
+}
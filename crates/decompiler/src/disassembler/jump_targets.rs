@@ -0,0 +1,42 @@
+use super::{Instruction, InstructionInfo, SwitchCase};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JumpKind {
+  Jump,
+  ConditionalJump,
+  SwitchCase,
+  Call
+}
+
+/// The location every jump, switch case and call in `instructions` transfers control to,
+/// in instruction order. Locations can repeat.
+pub fn collect_jump_targets<'a>(
+  instructions: &'a [InstructionInfo]
+) -> impl Iterator<Item = (usize, JumpKind)> + 'a {
+  instructions.iter().flat_map(|info| {
+    let target = match &info.instruction {
+      Instruction::Jump { location } => Some((*location as usize, JumpKind::Jump)),
+      Instruction::JumpZero { location }
+      | Instruction::IfEqualJumpZero { location }
+      | Instruction::IfNotEqualJumpZero { location }
+      | Instruction::IfLowerThanJumpZero { location }
+      | Instruction::IfGreaterThanJumpZero { location }
+      | Instruction::IfLowerOrEqualJumpZero { location }
+      | Instruction::IfGreaterOrEqualJumpZero { location } => {
+        Some((*location as usize, JumpKind::ConditionalJump))
+      }
+      Instruction::FunctionCall { location } => Some((*location as usize, JumpKind::Call)),
+      _ => None
+    };
+    let cases: &[SwitchCase] = match &info.instruction {
+      Instruction::Switch { cases } => cases,
+      _ => &[]
+    };
+
+    target.into_iter().chain(
+      cases
+        .iter()
+        .map(|case| (case.location as usize, JumpKind::SwitchCase))
+    )
+  })
+}
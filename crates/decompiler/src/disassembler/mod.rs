@@ -1,34 +1,78 @@
-use std::{io, string::FromUtf8Error};
+use alloc::{
+  format,
+  string::{FromUtf8Error, String},
+  vec::Vec
+};
+use core::fmt;
 
-use binary_reader::{BinaryReader, Endian};
-use thiserror::Error;
+#[cfg(feature = "std")]
+use num_enum::TryFromPrimitiveError;
 
-use self::opcodes::Opcode;
+#[cfg(feature = "std")]
+use crate::script::OpcodeVersion;
 
+use self::{code_reader::CodeReader, opcodes::Opcode};
+
+mod code_reader;
 mod instruction;
 mod instruction_info;
+mod jump_targets;
 pub mod opcodes;
 
 pub use instruction::*;
 pub use instruction_info::*;
+pub use jump_targets::*;
 
 pub fn disassemble(code: &[u8]) -> Result<Vec<InstructionInfo>, DisassembleError> {
-  let mut result: Vec<InstructionInfo> = Default::default();
+  disassemble_range(code, 0, code.len())
+}
 
-  let mut reader = BinaryReader::from_u8(code);
-  reader.set_endian(Endian::Little);
+/// Disassembles the instructions starting in `[start, end)` of `code`. Positions stay
+/// relative to the start of `code`, `start` has to be the start of an instruction.
+pub fn disassemble_range(
+  code: &[u8],
+  start: usize,
+  end: usize
+) -> Result<Vec<InstructionInfo>, DisassembleError> {
+  if start > end || end > code.len() {
+    return Err(DisassembleError::InvalidRange {
+      start,
+      end,
+      length: code.len()
+    });
+  }
 
+  // Unnamed functions are numbered, so the ones before `start` have to be counted
   let mut n_func = 0;
-  while reader.pos != reader.length {
+  let mut pos = 0;
+  while pos < start {
+    let opcode = read_opcode(code, pos)?;
+    if matches!(opcode, Opcode::Enter) {
+      n_func += 1;
+    }
+    pos += check_operands(opcode, code, pos)?;
+  }
+  if pos != start {
+    return Err(DisassembleError::MisalignedStart { start });
+  }
+
+  let mut result: Vec<InstructionInfo> = Default::default();
+
+  let mut reader = CodeReader::new(code, start);
+
+  while reader.pos < end {
     let start_pos = reader.pos;
     let raw_opcode = reader.read_u8()?;
-    let instruction = match Opcode::try_from(raw_opcode).map_err(|e| {
+    let opcode = Opcode::try_from(raw_opcode).map_err(|e| {
       DisassembleError::ReadInstructionError {
         input:  raw_opcode,
         offset: start_pos,
         source: e
       }
-    })? {
+    })?;
+    check_operands(opcode, code, start_pos)?;
+
+    let instruction = match opcode {
       Opcode::Nop => Instruction::Nop,
       Opcode::IntegerAdd => Instruction::IntegerAdd,
       Opcode::IntegerSubtract => Instruction::IntegerSubtract,
@@ -100,10 +144,12 @@ pub fn disassemble(code: &[u8]) -> Result<Vec<InstructionInfo>, DisassembleError
         let val = reader.read_u8()?;
         let return_count = val & 0b00000011;
         let arg_count = (val & 0b11111100) >> 2;
+        // Unlike every other operand the native index is stored big endian
+        let native_index = u16::from_be_bytes([reader.read_u8()?, reader.read_u8()?]);
         Instruction::NativeCall {
           arg_count,
           return_count,
-          native_index: ((reader.read_u8()? as u16) << 8) + reader.read_u8()? as u16
+          native_index
         }
       }
       Opcode::Enter => {
@@ -384,17 +430,14 @@ pub fn disassemble(code: &[u8]) -> Result<Vec<InstructionInfo>, DisassembleError
             let count = reader.read_u8()?;
             (0..count)
               .map(|_| {
-                reader
-                  .read_u32()
-                  .map_err(DisassembleError::from)
-                  .and_then(|v| {
-                    get_jump_address(&mut reader).map(|v2| {
-                      SwitchCase {
-                        value:    v,
-                        location: v2
-                      }
-                    })
+                reader.read_u32().and_then(|v| {
+                  get_jump_address(&mut reader).map(|v2| {
+                    SwitchCase {
+                      value:    v,
+                      location: v2
+                    }
                   })
+                })
               })
               .collect::<Result<_, _>>()?
           }
@@ -456,7 +499,103 @@ pub fn disassemble(code: &[u8]) -> Result<Vec<InstructionInfo>, DisassembleError
   Ok(result)
 }
 
-fn get_jump_address(reader: &mut BinaryReader) -> Result<u32, DisassembleError> {
+#[cfg(feature = "std")]
+pub fn disassemble_with_version(
+  code: &mut [u8],
+  version: OpcodeVersion
+) -> Result<Vec<InstructionInfo>, DisassembleError> {
+  remap_opcodes(version, code)?;
+  disassemble(code)
+}
+
+/// Rewrites the opcodes in `code` in place so they match the current [`Opcode`] numbering.
+/// Builds before b2802 don't have `STATIC_U24`, `STATIC_U24_LOAD` and `STATIC_U24_STORE`, so
+/// every later opcode is 3 lower there. Operands are left untouched. The whole code is
+/// validated first, `code` is left unchanged on errors.
+#[cfg(feature = "std")]
+pub fn remap_opcodes(version: OpcodeVersion, code: &mut [u8]) -> Result<(), DisassembleError> {
+  let mut i = 0;
+  while i < code.len() {
+    let opcode = remapped_opcode(version, code, i)?;
+    i += check_operands(opcode, code, i)?;
+  }
+
+  let mut i = 0;
+  while i < code.len() {
+    let opcode = remapped_opcode(version, code, i)?;
+    code[i] = opcode.into();
+    i += check_operands(opcode, code, i)?;
+  }
+
+  Ok(())
+}
+
+/// The opcode at `pos` in the numbering of `version`, errors report the byte as it is in `code`.
+#[cfg(feature = "std")]
+fn remapped_opcode(
+  version: OpcodeVersion,
+  code: &[u8],
+  pos: usize
+) -> Result<Opcode, DisassembleError> {
+  let byte = code[pos];
+  let remapped = if version < OpcodeVersion::B2802 && byte >= Opcode::StaticU24.into() {
+    byte.checked_add(3)
+  } else {
+    Some(byte)
+  };
+
+  remapped
+    .and_then(|remapped| Opcode::try_from(remapped).ok())
+    .ok_or(DisassembleError::ReadInstructionError {
+      input:  byte,
+      offset: pos,
+      source: TryFromPrimitiveError { number: byte }
+    })
+}
+
+/// Inverse of [`remap_opcodes`], rewrites opcodes in the current numbering to the one of
+/// `version`. Fails on opcodes `version` doesn't have.
+#[cfg(feature = "std")]
+pub fn unmap_opcodes(version: OpcodeVersion, code: &mut [u8]) -> Result<(), DisassembleError> {
+  let mut i = 0;
+  while i < code.len() {
+    let opcode = read_opcode(code, i)?;
+    let size = check_operands(opcode, code, i)?;
+
+    if version < OpcodeVersion::B2802 && code[i] >= Opcode::StaticU24.into() {
+      if matches!(
+        opcode,
+        Opcode::StaticU24 | Opcode::StaticU24Load | Opcode::StaticU24Store
+      ) {
+        return Err(DisassembleError::UnsupportedOpcode { opcode, pos: i });
+      }
+      code[i] -= 3;
+    }
+
+    i += size;
+  }
+
+  Ok(())
+}
+
+fn read_opcode(code: &[u8], pos: usize) -> Result<Opcode, DisassembleError> {
+  Opcode::try_from(code[pos]).map_err(|e| {
+    DisassembleError::ReadInstructionError {
+      input:  code[pos],
+      offset: pos,
+      source: e
+    }
+  })
+}
+
+fn check_operands(opcode: Opcode, code: &[u8], pos: usize) -> Result<usize, DisassembleError> {
+  opcode.checked_size(&code[pos..]).ok_or(match opcode {
+    Opcode::Switch => DisassembleError::TruncatedSwitch { pos },
+    _ => DisassembleError::TruncatedOperand { opcode, pos }
+  })
+}
+
+fn get_jump_address(reader: &mut CodeReader) -> Result<u32, DisassembleError> {
   let offset = reader.read_i16()?;
   Ok(
     add_i16_to_usize(reader.pos, offset).ok_or(DisassembleError::InvalidJump {
@@ -474,30 +613,267 @@ fn add_i16_to_usize(usize: usize, i16: i16) -> Option<usize> {
   }
 }
 
-#[derive(Debug, Error)]
+/// Implemented by hand instead of with `thiserror`, which needs `std`.
+///
+/// Code is read from a slice instead of an `io::Read`, the `ReadError` variant wrapping an
+/// `io::Error` is gone. Operands running past the end of the code are reported as
+/// [`Self::TruncatedOperand`] instead.
+#[derive(Debug)]
 pub enum DisassembleError {
-  #[error("{} at {} is not a recognized instruction", input, offset)]
   ReadInstructionError {
     input:  u8,
     offset: usize,
-    #[source]
     source: <Opcode as TryFrom<u8>>::Error
   },
-
-  #[error("Read error: {}", source)]
-  ReadError {
-    #[source]
-    #[from]
-    source: io::Error
+  UnexpectedEnd {
+    pos: usize
+  },
+  InvalidJump {
+    pos:    usize,
+    offset: i16
+  },
+  TruncatedSwitch {
+    pos: usize
+  },
+  TruncatedOperand {
+    opcode: Opcode,
+    pos:    usize
+  },
+  InvalidRange {
+    start:  usize,
+    end:    usize,
+    length: usize
+  },
+  MisalignedStart {
+    start: usize
+  },
+  UnsupportedOpcode {
+    opcode: Opcode,
+    pos:    usize
   },
-
-  #[error("Invalid jump offset at: {}, with offset: {}", pos, offset)]
-  InvalidJump { pos: usize, offset: i16 },
-
-  #[error("Failed to parse function name at: {}", pos)]
   InvalidFunctionNameError {
     pos:    usize,
-    #[source]
     source: FromUtf8Error
   }
 }
+
+impl fmt::Display for DisassembleError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ReadInstructionError { input, offset, .. } => {
+        write!(f, "{input} at {offset} is not a recognized instruction")
+      }
+      Self::UnexpectedEnd { pos } => write!(f, "Unexpected end of the code at: {pos}"),
+      Self::InvalidJump { pos, offset } => {
+        write!(f, "Invalid jump offset at: {pos}, with offset: {offset}")
+      }
+      Self::TruncatedSwitch { pos } => {
+        write!(f, "Switch table at: {pos} runs past the end of the code")
+      }
+      Self::TruncatedOperand { opcode, pos } => {
+        write!(
+          f,
+          "Operands of {opcode:?} at: {pos} run past the end of the code"
+        )
+      }
+      Self::InvalidRange { start, end, length } => {
+        write!(
+          f,
+          "Range {start}..{end} is outside of the code of length {length}"
+        )
+      }
+      Self::MisalignedStart { start } => write!(f, "{start} is not the start of an instruction"),
+      Self::UnsupportedOpcode { opcode, pos } => {
+        write!(
+          f,
+          "{opcode:?} at: {pos} does not exist in the target version"
+        )
+      }
+      Self::InvalidFunctionNameError { pos, .. } => {
+        write!(f, "Failed to parse function name at: {pos}")
+      }
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DisassembleError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::ReadInstructionError { source, .. } => Some(source),
+      Self::InvalidFunctionNameError { source, .. } => Some(source),
+      _ => None
+    }
+  }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+  use super::*;
+
+  /// The byte an opcode had before b2802 added the `STATIC_U24` opcodes.
+  fn old(opcode: Opcode) -> u8 {
+    let byte: u8 = opcode.into();
+    if byte >= Opcode::StaticU24.into() {
+      byte - 3
+    } else {
+      byte
+    }
+  }
+
+  fn old_code() -> Vec<u8> {
+    let push_const_1 = old(Opcode::PushConst1);
+    vec![
+      old(Opcode::Enter),
+      0,
+      2,
+      0,
+      0,
+      // The operand bytes look like opcodes that need remapping
+      old(Opcode::GlobalU24Load),
+      push_const_1,
+      push_const_1,
+      push_const_1,
+      old(Opcode::PushConst2),
+      old(Opcode::BitTest),
+      old(Opcode::Drop),
+      old(Opcode::Leave),
+      0,
+      0,
+    ]
+  }
+
+  #[test]
+  fn disassemble_with_version_remaps_opcodes_before_b2802() {
+    let mut code = old_code();
+    let instructions = disassemble_with_version(&mut code, OpcodeVersion::B2699).unwrap();
+
+    let push_const_1: u32 = old(Opcode::PushConst1).into();
+    assert_eq!(
+      instructions
+        .iter()
+        .map(|info| info.instruction.clone())
+        .collect::<Vec<_>>(),
+      vec![
+        Instruction::Enter {
+          arg_count:  0,
+          frame_size: 2,
+          name:       "func_0".into()
+        },
+        Instruction::GlobalU24Load {
+          global_index: push_const_1 | push_const_1 << 8 | push_const_1 << 16
+        },
+        Instruction::PushConst2,
+        Instruction::BitTest,
+        Instruction::Drop,
+        Instruction::Leave {
+          parameter_count: 0,
+          return_count:    0
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn disassemble_with_version_keeps_b2802_opcodes() {
+    let mut code = vec![
+      Opcode::StaticU24Load.into(),
+      1,
+      0,
+      0,
+      Opcode::BitTest.into(),
+    ];
+    let instructions = disassemble_with_version(&mut code, OpcodeVersion::B2802).unwrap();
+
+    assert!(matches!(
+      instructions[0].instruction,
+      Instruction::StaticU24Load { static_index: 1 }
+    ));
+    assert!(matches!(instructions[1].instruction, Instruction::BitTest));
+  }
+
+  #[test]
+  fn truncated_switch_table_is_reported_as_such() {
+    // Claims two cases but only holds one
+    let code = [Opcode::Switch.into(), 2, 1, 0, 0, 0, 0, 0];
+
+    assert!(matches!(
+      disassemble(&code),
+      Err(DisassembleError::TruncatedSwitch { pos: 0 })
+    ));
+  }
+
+  #[test]
+  fn truncated_operand_names_the_opcode() {
+    let code = [Opcode::Nop.into(), Opcode::PushConstU32.into(), 1, 0];
+
+    assert!(matches!(
+      disassemble(&code),
+      Err(DisassembleError::TruncatedOperand {
+        opcode: Opcode::PushConstU32,
+        pos:    1
+      })
+    ));
+  }
+
+  #[test]
+  fn disassemble_range_keeps_positions_and_names() {
+    let code = crate::test_utils::Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .enter(0, 2)
+      .op(Opcode::Nop)
+      .leave(0, 0)
+      .build();
+
+    let instructions = disassemble_range(&code, 8, 14).unwrap();
+    assert_eq!(
+      instructions.iter().map(|info| info.pos).collect::<Vec<_>>(),
+      vec![8, 13]
+    );
+    assert!(matches!(
+      &instructions[0].instruction,
+      Instruction::Enter { name, .. } if name == "func_1"
+    ));
+    assert!(matches!(instructions[1].instruction, Instruction::Nop));
+  }
+
+  #[test]
+  fn disassemble_range_rejects_misaligned_starts() {
+    let code = crate::test_utils::Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+
+    assert!(matches!(
+      disassemble_range(&code, 1, code.len()),
+      Err(DisassembleError::MisalignedStart { start: 1 })
+    ));
+    assert!(matches!(
+      disassemble_range(&code, 0, code.len() + 1),
+      Err(DisassembleError::InvalidRange { .. })
+    ));
+  }
+
+  #[test]
+  fn remap_opcodes_reports_the_original_byte_and_leaves_the_code_unchanged() {
+    let mut code = old_code();
+    code.extend([old(Opcode::PushConst2), 0xFE]);
+    let original = code.clone();
+
+    let error = remap_opcodes(OpcodeVersion::B2699, &mut code).unwrap_err();
+
+    assert!(
+      matches!(
+        error,
+        DisassembleError::ReadInstructionError {
+          input: 0xFE,
+          offset: 16,
+          ..
+        }
+      ),
+      "{error:?}"
+    );
+    assert_eq!(code, original);
+  }
+}
@@ -1,143 +1,190 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-#[repr(u8)]
-#[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy)]
-pub enum Opcode {
-  Nop,
-  IntegerAdd,
-  IntegerSubtract,
-  IntegerMultiply,
-  IntegerDivide,
-  IntegerModulo,
-  IntegerNot,
-  IntegerNegate,
-  IntegerEquals,
-  IntegerNotEquals,
-  IntegerGreaterThan,
-  IntegerGreaterOrEqual,
-  IntegerLowerThan,
-  IntegerLowerOrEqual,
-  FloatAdd,
-  FloatSubtract,
-  FloatMultiply,
-  FloatDivide,
-  FloatModule,
-  FloatNegate,
-  FloatEquals,
-  FloatNotEquals,
-  FloatGreaterThan,
-  FloatGreaterOrEqual,
-  FloatLowerThan,
-  FloatLowerOrEqual,
-  VectorAdd,
-  VectorSubtract,
-  VectorMultiply,
-  VectorDivide,
-  VectorNegate,
-  BitwiseAnd,
-  BitwiseOr,
-  BitwiseXor,
-  IntegerToFloat,
-  FloatToInteger,
-  FloatToVector,
-  PushConstU8,
-  PushConstU8U8,
-  PushConstU8U8U8,
-  PushConstU32,
-  PushConstFloat,
-  Dup,
-  Drop,
-  NativeCall,
-  Enter,
-  Leave,
-  Load,
-  Store,
-  StoreRev,
-  LoadN,
-  StoreN,
-  ArrayU8,
-  ArrayU8Load,
-  ArrayU8Store,
-  LocalU8,
-  LocalU8Load,
-  LocalU8Store,
-  StaticU8,
-  StaticU8Load,
-  StaticU8Store,
-  AddU8,
-  MultiplyU8,
-  Offset,
-  OffsetU8,
-  OffsetU8Load,
-  OffsetU8Store,
-  PushConstS16,
-  AddS16,
-  MultiplyS16,
-  OffsetS16,
-  OffsetS16Load,
-  OffsetS16Store,
-  ArrayU16,
-  ArrayU16Load,
-  ArrayU16Store,
-  LocalU16,
-  LocalU16Load,
-  LocalU16Store,
-  StaticU16,
-  StaticU16Load,
-  StaticU16Store,
-  GlobalU16,
-  GlobalU16Load,
-  GlobalU16Store,
-  Jump,
-  JumpZero,
-  IfEqualJumpZero,
-  IfNotEqualJumpZero,
-  IfGreaterThanJumpZero,
-  IfGreaterOrEqualJumpZero,
-  IfLowerThanJumpZero,
-  IfLowerOrEqualJumpZero,
-  FunctionCall,
-  StaticU24,
-  StaticU24Load,
-  StaticU24Store,
-  GlobalU24,
-  GlobalU24Load,
-  GlobalU24Store,
-  PushConstU24,
-  Switch,
-  String,
-  StringHash,
-  TextLabelAssignString,
-  TextLabelAssignInt,
-  TextLabelAppendString,
-  TextLabelAppendInt,
-  TextLabelCopy,
-  Catch,
-  Throw,
-  CallIndirect,
-  PushConstM1,
-  PushConst0,
-  PushConst1,
-  PushConst2,
-  PushConst3,
-  PushConst4,
-  PushConst5,
-  PushConst6,
-  PushConst7,
-  PushConstFm1,
-  PushConstF0,
-  PushConstF1,
-  PushConstF2,
-  PushConstF3,
-  PushConstF4,
-  PushConstF5,
-  PushConstF6,
-  PushConstF7,
-  BitTest
+/// Defines [`Opcode`] together with its mnemonics, variants are listed in opcode order.
+macro_rules! opcodes {
+  ($($opcode:ident => $mnemonic:literal),* $(,)?) => {
+    #[repr(u8)]
+    #[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum Opcode {
+      $($opcode),*
+    }
+
+    impl Opcode {
+      /// Every opcode, in opcode order.
+      pub const ALL: &'static [Opcode] = &[$(Opcode::$opcode),*];
+
+      /// The mnemonic used for this opcode in disassembly output.
+      pub fn mnemonic(self) -> &'static str {
+        match self {
+          $(Opcode::$opcode => $mnemonic),*
+        }
+      }
+
+      pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        match mnemonic {
+          $($mnemonic => Some(Opcode::$opcode),)*
+          _ => None
+        }
+      }
+    }
+  };
+}
+
+opcodes! {
+  Nop                      => "NOP",
+  IntegerAdd               => "IADD",
+  IntegerSubtract          => "ISUB",
+  IntegerMultiply          => "IMUL",
+  IntegerDivide            => "IDIV",
+  IntegerModulo            => "IMOD",
+  IntegerNot               => "INOT",
+  IntegerNegate            => "INEG",
+  IntegerEquals            => "IEQ",
+  IntegerNotEquals         => "INE",
+  IntegerGreaterThan       => "IGT",
+  IntegerGreaterOrEqual    => "IGE",
+  IntegerLowerThan         => "ILT",
+  IntegerLowerOrEqual      => "ILE",
+  FloatAdd                 => "FADD",
+  FloatSubtract            => "FSUB",
+  FloatMultiply            => "FMUL",
+  FloatDivide              => "FDIV",
+  FloatModule              => "FMOD",
+  FloatNegate              => "FNEG",
+  FloatEquals              => "FEQ",
+  FloatNotEquals           => "FNE",
+  FloatGreaterThan         => "FGT",
+  FloatGreaterOrEqual      => "FGE",
+  FloatLowerThan           => "FLT",
+  FloatLowerOrEqual        => "FLE",
+  VectorAdd                => "VADD",
+  VectorSubtract           => "VSUB",
+  VectorMultiply           => "VMUL",
+  VectorDivide             => "VDIV",
+  VectorNegate             => "VNEG",
+  BitwiseAnd               => "IAND",
+  BitwiseOr                => "IOR",
+  BitwiseXor               => "IXOR",
+  IntegerToFloat           => "I2F",
+  FloatToInteger           => "F2I",
+  FloatToVector            => "F2V",
+  PushConstU8              => "PUSH_CONST_U8",
+  PushConstU8U8            => "PUSH_CONST_U8_U8",
+  PushConstU8U8U8          => "PUSH_CONST_U8_U8_U8",
+  PushConstU32             => "PUSH_CONST_U32",
+  PushConstFloat           => "PUSH_CONST_F",
+  Dup                      => "DUP",
+  Drop                     => "DROP",
+  NativeCall               => "NATIVE",
+  Enter                    => "ENTER",
+  Leave                    => "LEAVE",
+  Load                     => "LOAD",
+  Store                    => "STORE",
+  StoreRev                 => "STORE_REV",
+  LoadN                    => "LOAD_N",
+  StoreN                   => "STORE_N",
+  ArrayU8                  => "ARRAY_U8",
+  ArrayU8Load              => "ARRAY_U8_LOAD",
+  ArrayU8Store             => "ARRAY_U8_STORE",
+  LocalU8                  => "LOCAL_U8",
+  LocalU8Load              => "LOCAL_U8_LOAD",
+  LocalU8Store             => "LOCAL_U8_STORE",
+  StaticU8                 => "STATIC_U8",
+  StaticU8Load             => "STATIC_U8_LOAD",
+  StaticU8Store            => "STATIC_U8_STORE",
+  AddU8                    => "IADD_U8",
+  MultiplyU8               => "IMUL_U8",
+  Offset                   => "IOFFSET",
+  OffsetU8                 => "IOFFSET_U8",
+  OffsetU8Load             => "IOFFSET_U8_LOAD",
+  OffsetU8Store            => "IOFFSET_U8_STORE",
+  PushConstS16             => "PUSH_CONST_S16",
+  AddS16                   => "IADD_S16",
+  MultiplyS16              => "IMUL_S16",
+  OffsetS16                => "IOFFSET_S16",
+  OffsetS16Load            => "IOFFSET_S16_LOAD",
+  OffsetS16Store           => "IOFFSET_S16_STORE",
+  ArrayU16                 => "ARRAY_U16",
+  ArrayU16Load             => "ARRAY_U16_LOAD",
+  ArrayU16Store            => "ARRAY_U16_STORE",
+  LocalU16                 => "LOCAL_U16",
+  LocalU16Load             => "LOCAL_U16_LOAD",
+  LocalU16Store            => "LOCAL_U16_STORE",
+  StaticU16                => "STATIC_U16",
+  StaticU16Load            => "STATIC_U16_LOAD",
+  StaticU16Store           => "STATIC_U16_STORE",
+  GlobalU16                => "GLOBAL_U16",
+  GlobalU16Load            => "GLOBAL_U16_LOAD",
+  GlobalU16Store           => "GLOBAL_U16_STORE",
+  Jump                     => "J",
+  JumpZero                 => "JZ",
+  IfEqualJumpZero          => "IEQ_JZ",
+  IfNotEqualJumpZero       => "INE_JZ",
+  IfGreaterThanJumpZero    => "IGT_JZ",
+  IfGreaterOrEqualJumpZero => "IGE_JZ",
+  IfLowerThanJumpZero      => "ILT_JZ",
+  IfLowerOrEqualJumpZero   => "ILE_JZ",
+  FunctionCall             => "CALL",
+  StaticU24                => "STATIC_U24",
+  StaticU24Load            => "STATIC_U24_LOAD",
+  StaticU24Store           => "STATIC_U24_STORE",
+  GlobalU24                => "GLOBAL_U24",
+  GlobalU24Load            => "GLOBAL_U24_LOAD",
+  GlobalU24Store           => "GLOBAL_U24_STORE",
+  PushConstU24             => "PUSH_CONST_U24",
+  Switch                   => "SWITCH",
+  String                   => "STRING",
+  StringHash               => "STRING_HASH",
+  TextLabelAssignString    => "TEXT_LABEL_ASSIGN_STRING",
+  TextLabelAssignInt       => "TEXT_LABEL_ASSIGN_INT",
+  TextLabelAppendString    => "TEXT_LABEL_APPEND_STRING",
+  TextLabelAppendInt       => "TEXT_LABEL_APPEND_INT",
+  TextLabelCopy            => "TEXT_LABEL_COPY",
+  Catch                    => "CATCH",
+  Throw                    => "THROW",
+  CallIndirect             => "CALLINDIRECT",
+  PushConstM1              => "PUSH_CONST_M1",
+  PushConst0               => "PUSH_CONST_0",
+  PushConst1               => "PUSH_CONST_1",
+  PushConst2               => "PUSH_CONST_2",
+  PushConst3               => "PUSH_CONST_3",
+  PushConst4               => "PUSH_CONST_4",
+  PushConst5               => "PUSH_CONST_5",
+  PushConst6               => "PUSH_CONST_6",
+  PushConst7               => "PUSH_CONST_7",
+  PushConstFm1             => "PUSH_CONST_FM1",
+  PushConstF0              => "PUSH_CONST_F0",
+  PushConstF1              => "PUSH_CONST_F1",
+  PushConstF2              => "PUSH_CONST_F2",
+  PushConstF3              => "PUSH_CONST_F3",
+  PushConstF4              => "PUSH_CONST_F4",
+  PushConstF5              => "PUSH_CONST_F5",
+  PushConstF6              => "PUSH_CONST_F6",
+  PushConstF7              => "PUSH_CONST_F7",
+  BitTest                  => "BITTEST"
 }
 
 impl Opcode {
-  pub fn size(self, bytes: &[u8]) -> u16 {
+  /// Like [`Opcode::size`], but returns `None` if `bytes` doesn't contain the whole instruction.
+  pub fn checked_size(self, bytes: &[u8]) -> Option<usize> {
+    let header_size = match self {
+      Opcode::Enter => 5,
+      Opcode::Switch => 2,
+      _ => 1
+    };
+
+    if bytes.len() < header_size {
+      return None;
+    }
+
+    let size = self.size(bytes);
+    (bytes.len() >= size).then_some(size)
+  }
+
+  /// The size in bytes of the instruction at the start of `bytes`, including the opcode.
+  /// `bytes` must contain the operands that determine the size (`Enter`'s name length and
+  /// `Switch`'s case count), use [`Opcode::checked_size`] for untrusted input.
+  pub fn size(self, bytes: &[u8]) -> usize {
     match self {
       Opcode::Nop => 1,
       Opcode::IntegerAdd => 1,
@@ -184,7 +231,7 @@ impl Opcode {
       Opcode::Dup => 1,
       Opcode::Drop => 1,
       Opcode::NativeCall => 4,
-      Opcode::Enter => 5 + bytes[4] as u16,
+      Opcode::Enter => 5 + bytes[4] as usize,
       Opcode::Leave => 3,
       Opcode::Load => 1,
       Opcode::Store => 1,
@@ -240,7 +287,7 @@ impl Opcode {
       Opcode::GlobalU24Load => 4,
       Opcode::GlobalU24Store => 4,
       Opcode::PushConstU24 => 4,
-      Opcode::Switch => 2 + bytes[1] as u16 * 6,
+      Opcode::Switch => 2 + bytes[1] as usize * 6,
       Opcode::String => 1,
       Opcode::StringHash => 1,
       Opcode::TextLabelAssignString => 2,
@@ -273,3 +320,75 @@ impl Opcode {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn every_opcode_round_trips_through_its_byte_and_mnemonic() {
+    for (byte, opcode) in Opcode::ALL.iter().enumerate() {
+      assert_eq!(Opcode::try_from(byte as u8), Ok(*opcode));
+      assert_eq!(Opcode::from_mnemonic(opcode.mnemonic()), Some(*opcode));
+    }
+    assert!(Opcode::try_from(Opcode::ALL.len() as u8).is_err());
+    assert_eq!(Opcode::from_mnemonic("iadd"), None);
+  }
+
+  #[test]
+  fn size_of_each_size_class() {
+    let cases: &[(Opcode, &[u8], usize)] = &[
+      (Opcode::Nop, &[], 1),
+      (Opcode::PushConstU8, &[1], 2),
+      (Opcode::PushConstU8U8, &[1, 2], 3),
+      (Opcode::PushConstU8U8U8, &[1, 2, 3], 4),
+      (Opcode::PushConstS16, &[1, 0], 3),
+      (Opcode::PushConstU24, &[1, 0, 0], 4),
+      (Opcode::PushConstU32, &[1, 0, 0, 0], 5),
+      (Opcode::PushConstFloat, &[0, 0, 0x80, 0x3F], 5),
+      (Opcode::NativeCall, &[0b101, 0, 1], 4),
+      (Opcode::Leave, &[1, 1], 3),
+      (Opcode::Jump, &[0, 0], 3),
+      (Opcode::FunctionCall, &[5, 0, 0], 4),
+      (Opcode::Enter, &[1, 3, 0, 0], 5),
+      (Opcode::Enter, &[1, 3, 0, 5, b'm', b'a', b'i', b'n', 0], 10),
+      (Opcode::Switch, &[0], 2),
+      (Opcode::Switch, &[1, 7, 0, 0, 0, 4, 0], 8),
+      (
+        Opcode::Switch,
+        &[3, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0],
+        20
+      )
+    ];
+
+    for (opcode, operands, size) in cases {
+      let mut bytes = vec![(*opcode).into()];
+      bytes.extend_from_slice(operands);
+
+      assert_eq!(opcode.size(&bytes), *size, "{opcode:?} {operands:?}");
+      assert_eq!(
+        opcode.checked_size(&bytes),
+        Some(*size),
+        "{opcode:?} {operands:?}"
+      );
+      assert_eq!(
+        opcode.checked_size(&bytes[..size - 1]),
+        None,
+        "{opcode:?} {operands:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn size_matches_the_bytes_the_disassembler_reads() {
+    for opcode in Opcode::ALL {
+      let mut bytes = vec![0; 5];
+      bytes[0] = (*opcode).into();
+      bytes.truncate(opcode.size(&bytes));
+
+      let instructions = crate::disassembler::disassemble(&bytes).unwrap();
+      assert_eq!(instructions.len(), 1, "{opcode:?}");
+      assert_eq!(instructions[0].bytes.len(), bytes.len(), "{opcode:?}");
+    }
+  }
+}
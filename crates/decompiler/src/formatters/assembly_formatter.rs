@@ -1,10 +1,14 @@
 use std::{collections::HashMap, ffi::CStr};
 
-use crate::disassembler::{Instruction, InstructionInfo, SwitchCase};
+use crate::{
+  disassembler::{collect_jump_targets, Instruction, InstructionInfo, SwitchCase},
+  script::ScriptInfo
+};
 
 pub struct AssemblyFormatter<'strings> {
   include_offset:    bool,
   max_bytes_to_show: usize,
+  align_operands:    bool,
   labels:            HashMap<usize, String>,
   string_table:      &'strings [u8]
 }
@@ -19,11 +23,34 @@ impl<'strings> AssemblyFormatter<'strings> {
     Self {
       include_offset,
       max_bytes_to_show,
+      align_operands: false,
       labels: create_labels(instructions),
       string_table
     }
   }
 
+  /// Pads mnemonics so the operands of all instructions start in the same column.
+  pub fn align_operands(mut self, align_operands: bool) -> Self {
+    self.align_operands = align_operands;
+    self
+  }
+
+  pub fn format_header(&self, info: &ScriptInfo) -> String {
+    [
+      format!("; Script:     {}", info.name),
+      format!("; Hash:       0x{:08X}", info.name_hash),
+      format!("; Parameters: {}", info.parameter_count),
+      format!("; Statics:    {}", info.static_count),
+      format!(
+        "; Globals:    {} (version {})",
+        info.global_count, info.globals_version
+      ),
+      String::new(),
+      String::new()
+    ]
+    .join("\n")
+  }
+
   pub fn format(&self, instructions: &[InstructionInfo], show_function_separators: bool) -> String {
     let mut lines: Vec<String> = Vec::with_capacity(instructions.len());
     let mut last_constant: u32 = 0;
@@ -52,70 +79,32 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
       }
 
+      let mnemonic = info.instruction.opcode().mnemonic();
       match &info.instruction {
-        Instruction::Nop => lines.push(format!("{prefix}\tNOP")),
-        Instruction::IntegerAdd => lines.push(format!("{prefix}\tIADD")),
-        Instruction::IntegerSubtract => lines.push(format!("{prefix}\tISUB")),
-        Instruction::IntegerMultiply => lines.push(format!("{prefix}\tIMUL")),
-        Instruction::IntegerDivide => lines.push(format!("{prefix}\tIDIV")),
-        Instruction::IntegerModulo => lines.push(format!("{prefix}\tIMOD")),
-        Instruction::IntegerNot => lines.push(format!("{prefix}\tINOT")),
-        Instruction::IntegerNegate => lines.push(format!("{prefix}\tINEG")),
-        Instruction::IntegerEquals => lines.push(format!("{prefix}\tIEQ")),
-        Instruction::IntegerNotEquals => lines.push(format!("{prefix}\tINE")),
-        Instruction::IntegerGreaterThan => lines.push(format!("{prefix}\tIGT")),
-        Instruction::IntegerGreaterOrEqual => lines.push(format!("{prefix}\tIGE")),
-        Instruction::IntegerLowerThan => lines.push(format!("{prefix}\tILT")),
-        Instruction::IntegerLowerOrEqual => lines.push(format!("{prefix}\tILE")),
-        Instruction::FloatAdd => lines.push(format!("{prefix}\tFADD")),
-        Instruction::FloatSubtract => lines.push(format!("{prefix}\tFSUB")),
-        Instruction::FloatMultiply => lines.push(format!("{prefix}\tFMUL")),
-        Instruction::FloatDivide => lines.push(format!("{prefix}\tFDIV")),
-        Instruction::FloatModule => lines.push(format!("{prefix}\tFMOD")),
-        Instruction::FloatNegate => lines.push(format!("{prefix}\tFNEG")),
-        Instruction::FloatEquals => lines.push(format!("{prefix}\tFEQ")),
-        Instruction::FloatNotEquals => lines.push(format!("{prefix}\tFNE")),
-        Instruction::FloatGreaterThan => lines.push(format!("{prefix}\tFGT")),
-        Instruction::FloatGreaterOrEqual => lines.push(format!("{prefix}\tFGE")),
-        Instruction::FloatLowerThan => lines.push(format!("{prefix}\tFLT")),
-        Instruction::FloatLowerOrEqual => lines.push(format!("{prefix}\tFLE")),
-        Instruction::VectorAdd => lines.push(format!("{prefix}\tVADD")),
-        Instruction::VectorSubtract => lines.push(format!("{prefix}\tVSUB")),
-        Instruction::VectorMultiply => lines.push(format!("{prefix}\tVMUL")),
-        Instruction::VectorDivide => lines.push(format!("{prefix}\tVDIV")),
-        Instruction::VectorNegate => lines.push(format!("{prefix}\tVNEG")),
-        Instruction::BitwiseAnd => lines.push(format!("{prefix}\tIAND")),
-        Instruction::BitwiseOr => lines.push(format!("{prefix}\tIOR")),
-        Instruction::BitwiseXor => lines.push(format!("{prefix}\tIXOR")),
-        Instruction::IntegerToFloat => lines.push(format!("{prefix}\tI2F")),
-        Instruction::FloatToInteger => lines.push(format!("{prefix}\tF2I")),
-        Instruction::FloatToVector => lines.push(format!("{prefix}\tF2V")),
         Instruction::PushConstU8 { c1 } => {
           last_constant = *c1 as u32;
-          lines.push(format!("{prefix}\tPUSH_CONST_U8 {c1}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1}"))
         }
         Instruction::PushConstU8U8 { c1, c2 } => {
           last_constant = *c2 as u32;
-          lines.push(format!("{prefix}\tPUSH_CONST_U8_U8 {c1} {c2}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1} {c2}"))
         }
         Instruction::PushConstU8U8U8 { c1, c2, c3 } => {
           last_constant = *c3 as u32;
-          lines.push(format!("{prefix}\tPUSH_CONST_U8_U8_U8 {c1} {c2} {c3}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1} {c2} {c3}"))
         }
         Instruction::PushConstU32 { c1 } => {
           last_constant = *c1;
-          lines.push(format!("{prefix}\tPUSH_CONST_U32 {c1}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1}"))
         }
-        Instruction::PushConstFloat { c1 } => lines.push(format!("{prefix}\tPUSH_CONST_F {c1}")),
-        Instruction::Dup => lines.push(format!("{prefix}\tDUP")),
-        Instruction::Drop => lines.push(format!("{prefix}\tDROP")),
+        Instruction::PushConstFloat { c1 } => lines.push(format!("{prefix}\t{mnemonic} {c1}")),
         Instruction::NativeCall {
           arg_count,
           return_count,
           native_index
         } => {
           lines.push(format!(
-            "{prefix}\tNATIVE {arg_count} {return_count} {native_index}"
+            "{prefix}\t{mnemonic} {arg_count} {return_count} {native_index}"
           ))
         }
         Instruction::Enter {
@@ -134,108 +123,107 @@ impl<'strings> AssemblyFormatter<'strings> {
           }
           lines.push(format!("{prefix_without_bytes}.{display_name}:"));
           lines.push(if !name.starts_with("func_") {
-            format!("{prefix}\tENTER {parameter_count} {var_count} \"{name}\"")
+            format!("{prefix}\t{mnemonic} {parameter_count} {var_count} \"{name}\"")
           } else {
-            format!("{prefix}\tENTER {parameter_count} {var_count}")
+            format!("{prefix}\t{mnemonic} {parameter_count} {var_count}")
           });
         }
         Instruction::Leave {
           parameter_count,
           return_count
-        } => lines.push(format!("{prefix}\tLEAVE {parameter_count} {return_count}")),
-
-        Instruction::Load => lines.push(format!("{prefix}\tLOAD")),
-        Instruction::Store => lines.push(format!("{prefix}\tSTORE")),
-        Instruction::StoreRev => lines.push(format!("{prefix}\tSTORE_REV")),
-        Instruction::LoadN => lines.push(format!("{prefix}\tLOAD_N")),
-        Instruction::StoreN => lines.push(format!("{prefix}\tSTORE_N")),
-        Instruction::ArrayU8 { item_size } => lines.push(format!("{prefix}\tARRAY_U8 {item_size}")),
+        } => {
+          lines.push(format!(
+            "{prefix}\t{mnemonic} {parameter_count} {return_count}"
+          ))
+        }
+        Instruction::ArrayU8 { item_size } => {
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
+        }
         Instruction::ArrayU8Load { item_size } => {
-          lines.push(format!("{prefix}\tARRAY_U8_LOAD {item_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
         }
         Instruction::ArrayU8Store { item_size } => {
-          lines.push(format!("{prefix}\tARRAY_U8_STORE {item_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
         }
         Instruction::LocalU8 {
           offset: local_index
-        } => lines.push(format!("{prefix}\tLOCAL_U8 {local_index}")),
+        } => lines.push(format!("{prefix}\t{mnemonic} {local_index}")),
         Instruction::LocalU8Load {
           offset: local_index
-        } => lines.push(format!("{prefix}\tLOCAL_U8_LOAD {local_index}")),
+        } => lines.push(format!("{prefix}\t{mnemonic} {local_index}")),
         Instruction::LocalU8Store {
           offset: local_index
-        } => lines.push(format!("{prefix}\tLOCAL_U8_STORE {local_index}")),
+        } => lines.push(format!("{prefix}\t{mnemonic} {local_index}")),
         Instruction::StaticU8 { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U8 {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU8Load { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U8_LOAD {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU8Store { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U8_STORE {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
-        Instruction::AddU8 { value } => lines.push(format!("{prefix}\tIADD_U8 {value}")),
-        Instruction::MultiplyU8 { value } => lines.push(format!("{prefix}\tIMUL_U8 {value}")),
-        Instruction::Offset => lines.push(format!("{prefix}\tIOFFSET")),
-        Instruction::OffsetU8 { offset } => lines.push(format!("{prefix}\tIOFFSET_U8 {offset}")),
+        Instruction::AddU8 { value } => lines.push(format!("{prefix}\t{mnemonic} {value}")),
+        Instruction::MultiplyU8 { value } => lines.push(format!("{prefix}\t{mnemonic} {value}")),
+        Instruction::OffsetU8 { offset } => lines.push(format!("{prefix}\t{mnemonic} {offset}")),
         Instruction::OffsetU8Load { offset } => {
-          lines.push(format!("{prefix}\tIOFFSET_U8_LOAD {offset}"))
+          lines.push(format!("{prefix}\t{mnemonic} {offset}"))
         }
         Instruction::OffsetU8Store { offset } => {
-          lines.push(format!("{prefix}\tIOFFSET_U8_STORE {offset}"))
+          lines.push(format!("{prefix}\t{mnemonic} {offset}"))
         }
         Instruction::PushConstS16 { c1 } => {
           last_constant = *c1 as u32;
-          lines.push(format!("{prefix}\tPUSH_CONST_S16 {c1}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1}"))
         }
-        Instruction::AddS16 { value } => lines.push(format!("{prefix}\tIADD_S16 {value}")),
-        Instruction::MultiplyS16 { value } => lines.push(format!("{prefix}\tIMUL_S16 {value}")),
-        Instruction::OffsetS16 { offset } => lines.push(format!("{prefix}\tIOFFSET_S16 {offset}")),
+        Instruction::AddS16 { value } => lines.push(format!("{prefix}\t{mnemonic} {value}")),
+        Instruction::MultiplyS16 { value } => lines.push(format!("{prefix}\t{mnemonic} {value}")),
+        Instruction::OffsetS16 { offset } => lines.push(format!("{prefix}\t{mnemonic} {offset}")),
         Instruction::OffsetS16Load { offset } => {
-          lines.push(format!("{prefix}\tIOFFSET_S16_LOAD {offset}"))
+          lines.push(format!("{prefix}\t{mnemonic} {offset}"))
         }
         Instruction::OffsetS16Store { offset } => {
-          lines.push(format!("{prefix}\tIOFFSET_S16_STORE {offset}"))
+          lines.push(format!("{prefix}\t{mnemonic} {offset}"))
         }
         Instruction::ArrayU16 { item_size } => {
-          lines.push(format!("{prefix}\tARRAY_U16 {item_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
         }
         Instruction::ArrayU16Load { item_size } => {
-          lines.push(format!("{prefix}\tARRAY_U16_LOAD {item_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
         }
         Instruction::ArrayU16Store { item_size } => {
-          lines.push(format!("{prefix}\tARRAY_U16_STORE {item_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {item_size}"))
         }
         Instruction::LocalU16 { local_index } => {
-          lines.push(format!("{prefix}\tLOCAL_U16 {local_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {local_index}"))
         }
         Instruction::LocalU16Load { local_index } => {
-          lines.push(format!("{prefix}\tLOCAL_U16_LOAD {local_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {local_index}"))
         }
         Instruction::LocalU16Store { local_index } => {
-          lines.push(format!("{prefix}\tLOCAL_U16_STORE {local_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {local_index}"))
         }
         Instruction::StaticU16 { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U16 {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU16Load { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U16_LOAD {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU16Store { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U16_STORE {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::GlobalU16 { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U16 {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::GlobalU16Load { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U16_LOAD {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::GlobalU16Store { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U16_STORE {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::Jump { location } => {
           lines.push(format!(
-            "{prefix}\tJ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -244,7 +232,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::JumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tJZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -253,7 +241,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfEqualJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tIEQ_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -262,7 +250,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfNotEqualJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tINE_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -271,7 +259,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfGreaterThanJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tIGT_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -280,7 +268,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfGreaterOrEqualJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tIGE_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -289,7 +277,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfLowerThanJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tILT_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -298,7 +286,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::IfLowerOrEqualJumpZero { location } => {
           lines.push(format!(
-            "{prefix}\tILE_JZ {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -307,7 +295,7 @@ impl<'strings> AssemblyFormatter<'strings> {
         }
         Instruction::FunctionCall { location } => {
           lines.push(format!(
-            "{prefix}\tCALL {}",
+            "{prefix}\t{mnemonic} {}",
             self
               .labels
               .get(&(*location as usize))
@@ -315,29 +303,29 @@ impl<'strings> AssemblyFormatter<'strings> {
           ))
         }
         Instruction::StaticU24 { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U24 {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU24Load { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U24_LOAD {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::StaticU24Store { static_index } => {
-          lines.push(format!("{prefix}\tSTATIC_U24_STORE {static_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {static_index}"))
         }
         Instruction::GlobalU24 { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U24 {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::GlobalU24Load { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U24_LOAD {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::GlobalU24Store { global_index } => {
-          lines.push(format!("{prefix}\tGLOBAL_U24_STORE {global_index}"))
+          lines.push(format!("{prefix}\t{mnemonic} {global_index}"))
         }
         Instruction::PushConstU24 { c1 } => {
           last_constant = *c1;
-          lines.push(format!("{prefix}\tPUSH_CONST_U24 {c1}"))
+          lines.push(format!("{prefix}\t{mnemonic} {c1}"))
         }
         Instruction::Switch { cases } => {
-          lines.push(format!("{prefix}\tSWITCH"));
+          lines.push(format!("{prefix}\t{mnemonic}"));
           lines.extend(cases.iter().map(|SwitchCase { value, location }| {
             format!(
               "{prefix_without_bytes}\t\tCASE 0x{value:08X} {} ; {value}",
@@ -349,62 +337,69 @@ impl<'strings> AssemblyFormatter<'strings> {
           }))
         }
         Instruction::String => {
-          if last_constant as usize > self.string_table.len() {
-            last_constant = 0
-          }
-          let cstr = CStr::from_bytes_until_nul(&self.string_table[last_constant as usize..]);
-          let str = if let Ok(cstr) = cstr {
-            if let Ok(str) = cstr.to_str() {
-              str
-            } else {
-              "<<INVALID STRING>>"
-            }
-          } else {
-            "<<INVALID STRING>>"
-          };
+          // U24 and U32 constants can index past the end of the table
+          let str = self
+            .string_table
+            .get(last_constant as usize..)
+            .and_then(|bytes| CStr::from_bytes_until_nul(bytes).ok())
+            .and_then(|cstr| cstr.to_str().ok())
+            .unwrap_or("<<INVALID STRING>>");
 
           last_constant = 0;
-          lines.push(format!("{prefix}\tSTRING ; \"{str}\""));
+          lines.push(format!("{prefix}\t{mnemonic} ; \"{str}\""));
         }
-        Instruction::StringHash => lines.push(format!("{prefix}\tSTRING_HASH")),
         Instruction::TextLabelAssignString { buffer_size } => {
-          lines.push(format!("{prefix}\tTEXT_LABEL_ASSIGN_STRING {buffer_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {buffer_size}"))
         }
         Instruction::TextLabelAssignInt { buffer_size } => {
-          lines.push(format!("{prefix}\tTEXT_LABEL_ASSIGN_INT {buffer_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {buffer_size}"))
         }
         Instruction::TextLabelAppendString { buffer_size } => {
-          lines.push(format!("{prefix}\tTEXT_LABEL_APPEND_STRING {buffer_size}"))
+          lines.push(format!("{prefix}\t{mnemonic} {buffer_size}"))
         }
         Instruction::TextLabelAppendInt { buffer_size } => {
-          lines.push(format!("{prefix}\tTEXT_LABEL_APPEND_INT {buffer_size}"))
-        }
-        Instruction::TextLabelCopy => lines.push(format!("{prefix}\tTEXT_LABEL_COPY")),
-        Instruction::Catch => lines.push(format!("{prefix}\tCATCH")),
-        Instruction::Throw => lines.push(format!("{prefix}\tTHROW")),
-        Instruction::CallIndirect => lines.push(format!("{prefix}\tCALLINDIRECT")),
-        Instruction::PushConstM1 => lines.push(format!("{prefix}\tPUSH_CONST_M1")),
-        Instruction::PushConst0 => lines.push(format!("{prefix}\tPUSH_CONST_0")),
-        Instruction::PushConst1 => lines.push(format!("{prefix}\tPUSH_CONST_1")),
-        Instruction::PushConst2 => lines.push(format!("{prefix}\tPUSH_CONST_2")),
-        Instruction::PushConst3 => lines.push(format!("{prefix}\tPUSH_CONST_3")),
-        Instruction::PushConst4 => lines.push(format!("{prefix}\tPUSH_CONST_4")),
-        Instruction::PushConst5 => lines.push(format!("{prefix}\tPUSH_CONST_5")),
-        Instruction::PushConst6 => lines.push(format!("{prefix}\tPUSH_CONST_6")),
-        Instruction::PushConst7 => lines.push(format!("{prefix}\tPUSH_CONST_7")),
-        Instruction::PushConstFm1 => lines.push(format!("{prefix}\tPUSH_CONST_FM1")),
-        Instruction::PushConstF0 => lines.push(format!("{prefix}\tPUSH_CONST_F0")),
-        Instruction::PushConstF1 => lines.push(format!("{prefix}\tPUSH_CONST_F1")),
-        Instruction::PushConstF2 => lines.push(format!("{prefix}\tPUSH_CONST_F2")),
-        Instruction::PushConstF3 => lines.push(format!("{prefix}\tPUSH_CONST_F3")),
-        Instruction::PushConstF4 => lines.push(format!("{prefix}\tPUSH_CONST_F4")),
-        Instruction::PushConstF5 => lines.push(format!("{prefix}\tPUSH_CONST_F5")),
-        Instruction::PushConstF6 => lines.push(format!("{prefix}\tPUSH_CONST_F6")),
-        Instruction::PushConstF7 => lines.push(format!("{prefix}\tPUSH_CONST_F7")),
-        Instruction::BitTest => lines.push(format!("{prefix}\tBITTEST"))
+          lines.push(format!("{prefix}\t{mnemonic} {buffer_size}"))
+        }
+        Instruction::PushConst0 => {
+          last_constant = 0;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst1 => {
+          last_constant = 1;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst2 => {
+          last_constant = 2;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst3 => {
+          last_constant = 3;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst4 => {
+          last_constant = 4;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst5 => {
+          last_constant = 5;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst6 => {
+          last_constant = 6;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        Instruction::PushConst7 => {
+          last_constant = 7;
+          lines.push(format!("{prefix}\t{mnemonic}"))
+        }
+        _ => lines.push(format!("{prefix}\t{mnemonic}"))
       }
     }
 
+    if self.align_operands {
+      align_operands(&mut lines);
+    }
+
     lines.join("\n")
   }
 }
@@ -413,34 +408,17 @@ fn create_labels(instructions: &[InstructionInfo]) -> HashMap<usize, String> {
   let mut result: HashMap<usize, String> = Default::default();
 
   for info in instructions {
-    match &info.instruction {
-      Instruction::Enter { name, .. } => {
-        result.insert(info.pos, name.clone());
-      }
-      Instruction::Jump { location }
-      | Instruction::JumpZero { location }
-      | Instruction::IfEqualJumpZero { location }
-      | Instruction::IfNotEqualJumpZero { location }
-      | Instruction::IfLowerThanJumpZero { location }
-      | Instruction::IfGreaterThanJumpZero { location }
-      | Instruction::IfLowerOrEqualJumpZero { location }
-      | Instruction::IfGreaterOrEqualJumpZero { location }
-      | Instruction::FunctionCall { location } => {
-        result
-          .entry(*location as usize)
-          .or_insert_with(|| format!("loc_{location:08X}"));
-      }
-      Instruction::Switch { cases } => {
-        for SwitchCase { location, .. } in cases {
-          result
-            .entry(*location as usize)
-            .or_insert_with(|| format!("loc_{location:08X}"));
-        }
-      }
-      _ => {}
+    if let Instruction::Enter { name, .. } = &info.instruction {
+      result.insert(info.pos, name.clone());
     }
   }
 
+  for (location, _) in collect_jump_targets(instructions) {
+    result
+      .entry(location)
+      .or_insert_with(|| format!("loc_{location:08X}"));
+  }
+
   result
 }
 
@@ -489,3 +467,221 @@ fn create_byte_string(code: &[u8], max_bytes: usize, count: usize) -> String {
 
   result
 }
+
+fn align_operands(lines: &mut [String]) {
+  let width = lines
+    .iter()
+    .filter_map(|line| split_instruction(line).map(|(_, mnemonic, _)| mnemonic.len()))
+    .max()
+    .unwrap_or_default();
+
+  for line in lines {
+    if let Some((prefix, mnemonic, operands)) = split_instruction(line) {
+      *line = format!("{prefix}\t{mnemonic:width$} {operands}");
+    }
+  }
+}
+
+/// Splits an instruction line into its prefix, mnemonic and operands.
+fn split_instruction(line: &str) -> Option<(&str, &str, &str)> {
+  let (prefix, instruction) = line.split_once('\t')?;
+  let (mnemonic, operands) = instruction.split_once(' ')?;
+  Some((prefix, mnemonic, operands))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use super::*;
+  use crate::{
+    decompiler::get_functions,
+    disassembler::{disassemble, opcodes::Opcode, JumpKind},
+    test_utils::Asm
+  };
+
+  fn two_functions() -> Vec<u8> {
+    Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build()
+  }
+
+  #[test]
+  fn function_separators_can_be_disabled() {
+    let code = two_functions();
+    let instructions = disassemble(&code).unwrap();
+    let formatter = AssemblyFormatter::new(&instructions, false, 0, &[]);
+
+    assert_eq!(
+      formatter
+        .format(&instructions, true)
+        .matches("F U N C T I O N")
+        .count(),
+      2
+    );
+    assert!(!formatter
+      .format(&instructions, false)
+      .contains("F U N C T I O N"));
+  }
+
+  #[test]
+  fn header_names_the_script_and_counts() {
+    let code = two_functions();
+    let instructions = disassemble(&code).unwrap();
+    let header = AssemblyFormatter::new(&instructions, false, 0, &[]).format_header(&ScriptInfo {
+      magic:           0xB3A8,
+      name:            "main".into(),
+      name_hash:       0x27EB33D7,
+      globals_version: 3,
+      parameter_count: 0,
+      static_count:    12,
+      global_count:    0
+    });
+
+    assert!(header.contains("; Script:     main"));
+    assert!(header.contains("; Hash:       0x27EB33D7"));
+    assert!(header.contains("; Statics:    12"));
+    assert!(header.contains("; Globals:    0 (version 3)"));
+  }
+
+  #[test]
+  fn every_instruction_is_shown_with_its_opcode_mnemonic() {
+    for opcode in Opcode::ALL {
+      // Zeroed operands give `ENTER` an empty name and `SWITCH` no cases
+      let mut code = vec![0; 5];
+      code[0] = (*opcode).into();
+      code.truncate(opcode.size(&code));
+      let instructions = disassemble(&code).unwrap();
+
+      let formatted =
+        AssemblyFormatter::new(&instructions, false, 0, &[]).format(&instructions, false);
+      let mnemonic = formatted
+        .lines()
+        .find_map(|line| line.strip_prefix('\t'))
+        .and_then(|line| line.split(' ').next());
+      assert_eq!(mnemonic, Some(opcode.mnemonic()));
+    }
+  }
+
+  #[test]
+  fn aligned_operands_share_a_column() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::PushConstU8)
+      .u8(7)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::Nop)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+
+    let formatted = AssemblyFormatter::new(&instructions, false, 0, &[])
+      .align_operands(true)
+      .format(&instructions, false);
+
+    assert_eq!(
+      formatted,
+      [
+        ".func_0:",
+        "\tENTER          0 3",
+        "\tPUSH_CONST_U8  7",
+        "\tLOCAL_U8_STORE 2",
+        "\tNOP",
+        "\tLEAVE          0 0"
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn u24_string_indices_are_looked_up() {
+    let mut strings = vec![0; 0x12345];
+    strings.extend(b"FAR_AWAY\0");
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConstU24)
+      .u24(0x12345)
+      .op(Opcode::String)
+      .op(Opcode::PushConstU24)
+      .u24(0x20000)
+      .op(Opcode::String)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+
+    let formatted =
+      AssemblyFormatter::new(&instructions, false, 0, &strings).format(&instructions, false);
+    assert!(formatted.contains("STRING ; \"FAR_AWAY\""), "{formatted}");
+    assert!(
+      formatted.contains("STRING ; \"<<INVALID STRING>>\""),
+      "{formatted}"
+    );
+  }
+
+  #[test]
+  fn labels_and_graph_nodes_start_at_the_same_jump_targets() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Switch)
+      .u8(2)
+      .u32(1)
+      .offset("call")
+      .u32(2)
+      .offset("test")
+      .jump(Opcode::Jump, "end")
+      .label("call")
+      .op(Opcode::FunctionCall)
+      .u24(39)
+      .jump(Opcode::Jump, "end")
+      .label("test")
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "end")
+      .label("end")
+      .leave(1, 0)
+      // 39
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+
+    let targets = collect_jump_targets(&instructions).collect::<Vec<_>>();
+    assert_eq!(
+      targets,
+      vec![
+        (24, JumpKind::SwitchCase),
+        (31, JumpKind::SwitchCase),
+        (36, JumpKind::Jump),
+        (39, JumpKind::Call),
+        (36, JumpKind::Jump),
+        (36, JumpKind::ConditionalJump)
+      ]
+    );
+
+    let mut labels = create_labels(&instructions).into_keys().collect::<Vec<_>>();
+    labels.sort();
+    assert_eq!(labels, vec![0, 24, 31, 36, 39]);
+
+    let functions = get_functions(&instructions);
+    let node_starts = functions[0]
+      .graph
+      .node_ranges()
+      .into_iter()
+      .map(|range| range.start)
+      .collect::<HashSet<_>>();
+    for (location, kind) in targets {
+      if kind != JumpKind::Call {
+        assert!(
+          node_starts.contains(&location),
+          "{location} {node_starts:?}"
+        );
+      }
+    }
+  }
+}
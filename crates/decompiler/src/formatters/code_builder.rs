@@ -1,22 +1,78 @@
+/// Columns a tab is counted as when measuring lines against [`CodeBuilder::with_max_width`].
+const TAB_WIDTH: usize = 4;
+
 #[derive(Default)]
 pub struct CodeBuilder {
   code:          String,
   indent:        u32,
-  indent_string: String
+  indent_string: String,
+  max_width:     Option<usize>
 }
 
 impl CodeBuilder {
+  pub fn with_indent(indent: u32) -> Self {
+    Self {
+      code: String::new(),
+      indent,
+      indent_string: "\t".repeat(indent as usize),
+      max_width: None
+    }
+  }
+
+  /// Lines wider than `max_width` are wrapped by putting the arguments of their outermost
+  /// call on separate lines, lines without such a call are left as they are.
+  pub fn with_max_width(mut self, max_width: Option<usize>) -> Self {
+    self.max_width = max_width;
+    self
+  }
+
   pub fn collect(self) -> String {
     self.code
   }
 
   pub fn line(&mut self, text: &str) -> &mut Self {
+    if let Some(max_width) = self.max_width {
+      let width = self.indent as usize * TAB_WIDTH + text.chars().count();
+      if width > max_width && !text.contains('\n') {
+        if let Some((head, args, tail)) = split_arguments(text) {
+          self.code.push_str(&self.indent_string);
+          self.code.push_str(head);
+          self.code.push('\n');
+
+          self.push_indent();
+          for (index, arg) in args.iter().enumerate() {
+            if index + 1 < args.len() {
+              self.line(&format!("{arg},"));
+            } else {
+              self.line(arg);
+            }
+          }
+          self.pop_indent();
+
+          return self.line(tail);
+        }
+      }
+    }
+
     self.code.push_str(&self.indent_string);
     self.code.push_str(text);
     self.code.push('\n');
     self
   }
 
+  /// Length of the code written so far, used as an offset for [`Self::append_to_line`].
+  pub fn len(&self) -> usize {
+    self.code.len()
+  }
+
+  /// Appends `text` to the first line written at or after `offset`.
+  pub fn append_to_line(&mut self, offset: usize, text: &str) -> &mut Self {
+    if let Some(end) = self.code[offset..].find('\n') {
+      self.code.insert_str(offset + end, text);
+    }
+    self
+  }
+
   pub fn branch(&mut self, cb: impl Fn(&mut Self)) -> &mut Self {
     self.push_indent();
     cb(self);
@@ -34,3 +90,109 @@ impl CodeBuilder {
     self.indent_string = "\t".repeat(self.indent as usize);
   }
 }
+
+struct Group {
+  open:   usize,
+  call:   bool,
+  commas: Vec<usize>
+}
+
+/// Splits `text` into the part up to and including the `(` of its outermost call with
+/// multiple arguments, the trimmed arguments, and the rest starting at the `)`. Commas in
+/// string literals, comments and nested brackets are not argument separators.
+fn split_arguments(text: &str) -> Option<(&str, Vec<&str>, &str)> {
+  let bytes = text.as_bytes();
+  let mut groups: Vec<Group> = vec![];
+  let mut outermost: Option<(Group, usize)> = None;
+
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      quote @ (b'"' | b'\'') => {
+        i += 1;
+        while i < bytes.len() && bytes[i] != quote {
+          if bytes[i] == b'\\' {
+            i += 1;
+          }
+          i += 1;
+        }
+      }
+      b'/' if bytes.get(i + 1) == Some(&b'*') => {
+        match text[i + 2..].find("*/") {
+          Some(end) => {
+            i += end + 4;
+            continue;
+          }
+          None => break
+        }
+      }
+      b'/' if bytes.get(i + 1) == Some(&b'/') => break,
+      b'(' | b'[' | b'{' => {
+        let call = bytes[i] == b'('
+          && i > 0
+          && (bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_');
+        groups.push(Group {
+          open: i,
+          call,
+          commas: vec![]
+        });
+      }
+      b')' | b']' | b'}' => {
+        let group = groups.pop()?;
+        if group.call && !group.commas.is_empty() {
+          let outer = match &outermost {
+            Some((current, _)) => group.open < current.open,
+            None => true
+          };
+          if outer {
+            outermost = Some((group, i));
+          }
+        }
+      }
+      b',' => {
+        if let Some(group) = groups.last_mut() {
+          group.commas.push(i);
+        }
+      }
+      _ => {}
+    }
+    i += 1;
+  }
+
+  let (group, close) = outermost?;
+  let mut args = vec![];
+  let mut start = group.open + 1;
+  for comma in group.commas {
+    args.push(text[start..comma].trim());
+    start = comma + 1;
+  }
+  args.push(text[start..close].trim());
+
+  Some((&text[..=group.open], args, &text[close..]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn wide_calls_wrap_at_their_arguments() {
+    let mut builder = CodeBuilder::with_indent(1).with_max_width(Some(40));
+    builder
+      .line("SET_ENTITY_COORDS(PLAYER_PED_ID(), 1.0f, \"a, b\", false);")
+      .line("WAIT(0);");
+
+    assert_eq!(
+      builder.collect(),
+      "\tSET_ENTITY_COORDS(\n\t\tPLAYER_PED_ID(),\n\t\t1.0f,\n\t\t\"a, b\",\n\t\tfalse\n\t);\n\tWAIT(0);\n"
+    );
+  }
+
+  #[test]
+  fn lines_without_calls_are_not_wrapped() {
+    let mut builder = CodeBuilder::with_indent(0).with_max_width(Some(10));
+    builder.line("local_0 = local_1 + local_2;");
+
+    assert_eq!(builder.collect(), "local_0 = local_1 + local_2;\n");
+  }
+}
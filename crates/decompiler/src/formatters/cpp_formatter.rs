@@ -1,41 +1,246 @@
-use std::matches;
+use std::{cell::Cell, fmt, matches};
 
 use itertools::Itertools;
 
-use crate::decompiler::{
-  decompiled::{DecompiledFunction, Statement, StatementInfo},
-  BinaryOperator, CaseValue, DecompilerData, LinkedValueType, Primitives, StackEntry,
-  StackEntryInfo, UnaryOperator, ValueType, ValueTypeInfo
+use crate::{
+  common::joaat,
+  decompiler::{
+    decompiled::{DecompiledFunction, OuterTarget, Statement, StatementInfo},
+    split_global_index, BinaryOperator, CaseValue, Confidence, Function, LinkedValueType,
+    Primitives, StackEntry, StackEntryInfo, UnaryOperator, ValueType, ValueTypeInfo,
+    DEFAULT_MAX_DEPTH
+  }
 };
 
-use super::code_builder::CodeBuilder;
+use super::{code_builder::CodeBuilder, FormatterData};
+
+/// How values of an unknown type (`Primitives::Unknown`) are rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownTypeStyle {
+  /// Render as `any`.
+  #[default]
+  Any,
+  /// Render as `any` and declare it with `typedef int any;` in the header.
+  Typedef,
+  /// Render as `int /* unknown */`.
+  Int
+}
+
+/// How truncating integer `/` and `%` are rendered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntegerDivisionStyle {
+  /// Render as `a / b`, same as float division.
+  #[default]
+  Plain,
+  /// Render as `a / b /* int */`.
+  Comment,
+  /// Render as `idiv(a, b)` and `imod(a, b)`, declared in the header.
+  Helper
+}
+
+/// How calls to addresses that aren't the start of a known function are named.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnknownFunctionNaming {
+  /// `prefix` followed by the address, `unk_fn0000ABCD` by default.
+  Address { prefix: String },
+  /// Named after the function containing the address, `func_12_0x1C`. Addresses outside of
+  /// all functions fall back to `prefix` followed by the address.
+  Containing { prefix: String }
+}
+
+impl Default for UnknownFunctionNaming {
+  fn default() -> Self {
+    Self::Address {
+      prefix: "unk_fn".to_owned()
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CppFormatterOptions {
+  pub unknown_types:      UnknownTypeStyle,
+  pub integer_division:   IntegerDivisionStyle,
+  /// End the first line of every statement with the positions of its instructions
+  pub annotate_offsets:   bool,
+  pub unknown_functions:  UnknownFunctionNaming,
+  /// Start the statements of every basic block with a `// --- block N ---` comment
+  pub block_comments:     bool,
+  /// Primitive types inferred with a lower confidence are rendered like unknown types
+  pub min_confidence:     Option<Confidence>,
+  /// Follow function names with a `/* @ 0x000123AB */` comment holding their location
+  pub function_offsets:   bool,
+  /// Precede native call arguments with a `/* name */` comment when the natives know the
+  /// names of their parameters
+  pub native_param_names: bool,
+  /// Render single bit tests like `(x & 0x4) != 0` as `has_flag(x, 0x4)`, declared in the
+  /// header
+  pub flag_tests:         bool,
+  /// Wrap lines wider than this many columns at the argument commas of calls
+  pub max_line_width:     Option<usize>,
+  /// Statements and expressions nested deeper than this are replaced by a comment instead
+  /// of risking a stack overflow, [`DEFAULT_MAX_DEPTH`] if `None`
+  pub max_depth:          Option<usize>
+}
 
 pub struct CppFormatter<'d, 'i, 'b> {
-  data: DecompilerData<'d, 'i, 'b>
+  data:             FormatterData<'d, 'i, 'b>,
+  options:          CppFormatterOptions,
+  /// The functions sorted by their location, for [`UnknownFunctionNaming::Containing`]
+  sorted:           Vec<&'d Function<'i, 'b>>,
+  /// The block of the last statement written, for [`CppFormatterOptions::block_comments`]
+  current_block:    Cell<Option<usize>>,
+  /// How many expressions the one being formatted is nested in
+  expression_depth: Cell<usize>,
+  /// How many statements are being written, counting the one being formatted
+  statement_depth:  Cell<usize>
 }
 
 impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
-  pub fn new(data: DecompilerData<'d, 'i, 'b>) -> Self {
-    Self { data }
+  pub fn new(data: impl Into<FormatterData<'d, 'i, 'b>>) -> Self {
+    Self::with_options(data, Default::default())
   }
 
-  pub fn format_function(&self, function: &DecompiledFunction) -> String {
+  pub fn with_options(
+    data: impl Into<FormatterData<'d, 'i, 'b>>,
+    options: CppFormatterOptions
+  ) -> Self {
+    let data = data.into();
+    let mut sorted = data.functions.values().copied().collect_vec();
+    sorted.sort_by_key(|function| function.location);
+
+    Self {
+      data,
+      options,
+      sorted,
+      current_block: Cell::new(None),
+      expression_depth: Cell::new(0),
+      statement_depth: Cell::new(0)
+    }
+  }
+
+  fn max_depth(&self) -> usize {
+    self.options.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+  }
+
+  /// Declarations that have to precede the formatted functions.
+  pub fn format_header(&self) -> String {
     let mut builder = CodeBuilder::default();
 
-    builder
-      .line(&self.create_signature(function))
-      .line("{")
-      .branch(|builder| {
-        self.declare_locals(function, builder);
-        for statement in &function.statements {
-          self.write_statement(statement, function, builder, false);
-        }
-      })
-      .line("}");
+    if self.options.unknown_types == UnknownTypeStyle::Typedef {
+      builder.line("typedef int any;").line("");
+    }
+
+    if self.options.integer_division == IntegerDivisionStyle::Helper {
+      builder
+        .line("int idiv(int a, int b);")
+        .line("int imod(int a, int b);")
+        .line("");
+    }
+
+    if self.options.flag_tests {
+      builder.line("bool has_flag(int value, int flag);").line("");
+    }
+
+    builder.collect()
+  }
+
+  /// Declarations of all statics, with their initial value unless it is zero.
+  pub fn format_statics(&self) -> String {
+    let mut builder = CodeBuilder::default();
+
+    let Some(statics) = self.data.statics else {
+      return builder.collect();
+    };
+
+    for index in 0..statics.len() {
+      let Some(ty) = statics.get_static(index) else {
+        continue;
+      };
+      let ty = ty.borrow();
+      let value = statics.get_initial_value(index).unwrap_or_default();
+
+      if value == 0 {
+        builder.line(&format!("static {} static_{index};", self.format_type(&ty)));
+      } else {
+        builder.line(&format!(
+          "static {} static_{index} = {};",
+          self.format_type(&ty),
+          format_static_value(value, &ty)
+        ));
+      }
+    }
+
+    if !statics.is_empty() {
+      builder.line("");
+    }
+
+    builder.collect()
+  }
+
+  /// Struct declarations for all globals accessed as structs, merging the fields used by
+  /// every function decompiled with the same globals.
+  pub fn format_globals(&self) -> String {
+    let mut builder = CodeBuilder::default();
+
+    let Some(globals) = self.data.globals else {
+      return builder.collect();
+    };
+
+    for (global, ty) in globals.globals() {
+      let ty = ty.borrow().get_concrete();
+      let ValueType::Struct { fields } = &ty.ty else {
+        continue;
+      };
+
+      builder
+        .line(&format!("struct {}_t", format_global(global)))
+        .line("{")
+        .branch(|builder| {
+          for (index, field) in fields.iter().enumerate() {
+            builder.line(&format!("{} f_{index};", self.format_type(&field.borrow())));
+          }
+        })
+        .line("};")
+        .line("");
+    }
 
     builder.collect()
   }
 
+  pub fn format_function(&self, function: &DecompiledFunction) -> String {
+    let mut code = String::new();
+    self
+      .format_function_to(function, &mut code)
+      .expect("writing to a String never fails");
+    code
+  }
+
+  /// Writes the function to `out` one top level statement at a time instead of building
+  /// the whole function in memory.
+  pub fn format_function_to(
+    &self,
+    function: &DecompiledFunction,
+    out: &mut dyn fmt::Write
+  ) -> fmt::Result {
+    self.current_block.set(None);
+
+    out.write_str(&self.declare_return_struct(function))?;
+    writeln!(out, "{}", self.create_signature(function))?;
+    writeln!(out, "{{")?;
+
+    let mut builder = CodeBuilder::with_indent(1).with_max_width(self.options.max_line_width);
+    self.declare_locals(function, &mut builder);
+    out.write_str(&builder.collect())?;
+
+    for statement in &function.statements {
+      let mut builder = CodeBuilder::with_indent(1).with_max_width(self.options.max_line_width);
+      self.write_statement(statement, function, &mut builder, false);
+      out.write_str(&builder.collect())?;
+    }
+
+    writeln!(out, "}}")
+  }
+
   fn create_signature(&self, function: &DecompiledFunction) -> String {
     let mut args = vec![];
 
@@ -49,17 +254,47 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
       let _ = iter.advance_by(p.borrow().size() - 1);
     }
     format!(
-      "{} {}({})",
-      function
-        .returns
-        .as_ref()
-        .map(|returns| self.format_type(&returns.borrow()))
-        .unwrap_or("void".to_owned()),
+      "{} {}{}({})",
+      match (&function.returns, return_struct_name(function)) {
+        (_, Some(name)) => name,
+        (Some(returns), None) => self.format_type(&returns.borrow()),
+        (None, None) => "void".to_owned()
+      },
       function.name,
+      if self.options.function_offsets {
+        format!(" /* @ 0x{:08X} */", function.location)
+      } else {
+        String::new()
+      },
       args.join(", ")
     )
   }
 
+  /// Functions returning multiple values return a struct named after the function.
+  fn declare_return_struct(&self, function: &DecompiledFunction) -> String {
+    let mut builder = CodeBuilder::default();
+
+    let (Some(name), Some(returns)) = (return_struct_name(function), &function.returns) else {
+      return builder.collect();
+    };
+    let ValueType::Struct { fields } = returns.borrow().get_concrete().ty else {
+      return builder.collect();
+    };
+
+    builder
+      .line(&format!("struct {name}"))
+      .line("{")
+      .branch(|builder| {
+        for (index, field) in fields.iter().enumerate() {
+          builder.line(&format!("{} f_{index};", self.format_type(&field.borrow())));
+        }
+      })
+      .line("};")
+      .line("");
+
+    builder.collect()
+  }
+
   fn declare_locals(&self, function: &DecompiledFunction, builder: &mut CodeBuilder) {
     let mut iter = function.locals.iter().enumerate();
     while let Some((i, p)) = iter.next() {
@@ -83,6 +318,55 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
     function: &DecompiledFunction,
     builder: &mut CodeBuilder,
     else_if: bool
+  ) {
+    // Functions that weren't depth checked while decompiling could overflow the stack. Counted
+    // instead of taken from the indentation, which doesn't grow along else if chains.
+    let depth = self.statement_depth.get() + 1;
+    if depth > self.max_depth() {
+      builder.line("/* nested too deep */");
+      return;
+    }
+    self.statement_depth.set(depth);
+
+    if self.options.block_comments {
+      let block = statement
+        .instructions
+        .first()
+        .and_then(|instruction| function.block_at(instruction.pos));
+      if let Some(index) = block.filter(|_| block != self.current_block.get()) {
+        self.current_block.set(block);
+        builder.line(&format!("// --- block {index} ---"));
+      }
+    }
+
+    let start = builder.len();
+
+    self.write_statement_code(statement, function, builder, else_if);
+
+    if self.options.annotate_offsets {
+      match statement.instructions {
+        [] => {}
+        [only] => {
+          builder.append_to_line(start, &format!(" // 0x{:08X}", only.pos));
+        }
+        [first, .., last] => {
+          builder.append_to_line(
+            start,
+            &format!(" // 0x{:08X}..0x{:08X}", first.pos, last.pos)
+          );
+        }
+      }
+    }
+
+    self.statement_depth.set(depth - 1);
+  }
+
+  fn write_statement_code(
+    &self,
+    statement: &StatementInfo,
+    function: &DecompiledFunction,
+    builder: &mut CodeBuilder,
+    else_if: bool
   ) {
     match &statement.statement {
       Statement::Nop => {}
@@ -90,6 +374,11 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
         destination,
         source
       } => {
+        if let Some(line) = self.format_compound_assign(destination, source, function) {
+          builder.line(&line);
+          return;
+        }
+
         builder.line(&format!(
           "{destination} = {source};",
           destination = self.format_stack_entry(destination, function),
@@ -112,14 +401,22 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
               .iter()
               .map(|v| self.format_stack_entry(v, function))
               .join(", ");
-            builder.line(&format!("return {{ {values} }}"));
+            match return_struct_name(function) {
+              Some(name) => builder.line(&format!("return {name}{{ {values} }};")),
+              None => builder.line(&format!("return {{ {values} }};"))
+            };
           }
         }
       }
-      Statement::Throw { value } => {
+      Statement::Throw { value, uncaught } => {
         builder.line(&format!(
-          "throw {};",
-          self.format_stack_entry(value, function)
+          "throw {};{}",
+          self.format_stack_entry(value, function),
+          if *uncaught {
+            " // terminates script"
+          } else {
+            ""
+          }
         ));
       }
       Statement::FunctionCall {
@@ -171,11 +468,20 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
           })
           .line("}");
 
-        match &els[..] {
-          [st @ StatementInfo {
-            statement: Statement::IfElse { .. } | Statement::If { .. },
-            ..
-          }] => self.write_statement(st, function, builder, true),
+        // Nops don't produce any output, so they shouldn't prevent flattening `else if` chains
+        let mut visible = els
+          .iter()
+          .filter(|st| !matches!(st.statement, Statement::Nop));
+        match (visible.next(), visible.next()) {
+          (
+            Some(
+              st @ StatementInfo {
+                statement: Statement::IfElse { .. } | Statement::If { .. },
+                ..
+              }
+            ),
+            None
+          ) => self.write_statement(st, function, builder, true),
           _ => {
             builder
               .line("else")
@@ -189,6 +495,32 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
           }
         }
       }
+      Statement::TryCatch {
+        body,
+        handler,
+        error_var
+      } => {
+        builder
+          .line("try")
+          .line("{")
+          .branch(|builder| {
+            for statement in body {
+              self.write_statement(statement, function, builder, false);
+            }
+          })
+          .line("}")
+          .line(&match error_var {
+            Some(error_var) => format!("catch ({})", self.format_stack_entry(error_var, function)),
+            None => "catch (...)".to_owned()
+          })
+          .line("{")
+          .branch(|builder| {
+            for statement in handler {
+              self.write_statement(statement, function, builder, false);
+            }
+          })
+          .line("}");
+      }
       Statement::WhileLoop { condition, body } => {
         builder
           .line(&format!(
@@ -211,10 +543,13 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
           ))
           .line("{")
           .branch(|builder| {
+            let hashed = matches!(condition.entry, StackEntry::StringHash(_));
             for (body, case_values) in cases {
               for case in case_values {
                 match case {
-                  CaseValue::Value(val) => builder.line(&format!("case {val}:")),
+                  CaseValue::Value(val) => {
+                    builder.line(&format!("case {}:", self.format_case_value(*val, hashed)))
+                  }
                   CaseValue::Default => builder.line("default:")
                 };
               }
@@ -227,11 +562,22 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
           })
           .line("}");
       }
-      Statement::Break => {
-        builder.line("break;");
+      Statement::Break { outer } => {
+        match outer {
+          Some(OuterTarget::Loop(pos)) => {
+            builder.line(&format!("break; // breaks loop at 0x{pos:X}"))
+          }
+          Some(OuterTarget::Switch(pos)) => {
+            builder.line(&format!("break; // breaks switch at 0x{pos:X}"))
+          }
+          None => builder.line("break;")
+        };
       }
-      Statement::Continue => {
-        builder.line("continue;");
+      Statement::Continue { outer } => {
+        match outer {
+          Some(pos) => builder.line(&format!("continue; // continues loop at 0x{pos:X}")),
+          None => builder.line("continue;")
+        };
       }
       Statement::StringCopy {
         destination,
@@ -284,7 +630,7 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
         ..
       } => {
         builder.line(&format!(
-          "mem_copy({}, {}, {});",
+          "text_label_copy({}, {}, {});",
           self.format_stack_entry(destination, function),
           {
             match &source[..] {
@@ -305,16 +651,102 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
     }
   }
 
+  /// Formats `x = x + 1` as `x++`, `x = x * y` as `x *= y`, etc.
+  fn format_compound_assign(
+    &self,
+    destination: &StackEntryInfo,
+    source: &StackEntryInfo,
+    function: &DecompiledFunction
+  ) -> Option<String> {
+    let StackEntry::BinaryOperator { lhs, rhs, op } = &source.entry else {
+      return None;
+    };
+
+    if !lhs.entry.is_same(&destination.entry) {
+      return None;
+    }
+
+    if is_float_modulo(source, *op)
+      || (self.options.integer_division != IntegerDivisionStyle::Plain
+        && is_integer_division(source, *op))
+    {
+      return None;
+    }
+
+    let destination = self.format_stack_entry(destination, function);
+    match (op, &rhs.entry) {
+      (BinaryOperator::Add, StackEntry::Int(1)) => return Some(format!("{destination}++;")),
+      (BinaryOperator::Subtract, StackEntry::Int(1)) => return Some(format!("{destination}--;")),
+      _ => {}
+    }
+
+    let op = match op {
+      BinaryOperator::Add => "+=",
+      BinaryOperator::Subtract => "-=",
+      BinaryOperator::Multiply => "*=",
+      BinaryOperator::Divide => "/=",
+      BinaryOperator::Modulo => "%=",
+      BinaryOperator::BitwiseAnd => "&=",
+      BinaryOperator::BitwiseOr => "|=",
+      BinaryOperator::BitwiseXor => "^=",
+      _ => return None
+    };
+
+    Some(format!(
+      "{destination} {op} {};",
+      self.format_stack_entry(rhs, function)
+    ))
+  }
+
+  /// Operators are parenthesized so they bind like a single value.
+  fn format_operand(&self, value: &StackEntryInfo, function: &DecompiledFunction) -> String {
+    match value.entry {
+      StackEntry::BinaryOperator { .. } | StackEntry::UnaryOperator { .. } => {
+        format!("({})", self.format_stack_entry(value, function))
+      }
+      _ => self.format_stack_entry(value, function)
+    }
+  }
+
   fn format_stack_entry(&self, value: &StackEntryInfo, function: &DecompiledFunction) -> String {
+    let depth = self.expression_depth.get();
+    if depth > self.max_depth() {
+      return "/* nested too deep */".to_owned();
+    }
+
+    self.expression_depth.set(depth + 1);
+    let formatted = self.format_stack_entry_unchecked(value, function);
+    self.expression_depth.set(depth);
+    formatted
+  }
+
+  fn format_stack_entry_unchecked(
+    &self,
+    value: &StackEntryInfo,
+    function: &DecompiledFunction
+  ) -> String {
     match &value.entry {
-      StackEntry::Int(i) => i.to_string(),
-      StackEntry::Float(f) => {
-        if f.trunc() == *f {
-          format!("{f}.f")
+      StackEntry::Int(i) => {
+        // Int constants are also used for floats and bools. The VM doesn't convert them, a
+        // float reads the same 32 bits.
+        match value.ty.borrow().get_concrete().ty {
+          ValueType::Primitive(Primitives::Float) => {
+            return format_float(f32::from_bits(*i as u32))
+          }
+          ValueType::Primitive(Primitives::Bool) if *i == 0 => return "false".to_owned(),
+          ValueType::Primitive(Primitives::Bool) if *i == 1 => return "true".to_owned(),
+          _ => {}
+        }
+
+        // Script ints are 32 bit and compared as signed values, so constants pushed through
+        // PUSH_CONST_U32 (hashes, flag masks) are shown with the value the VM operates on.
+        if *i > i32::MAX as i64 {
+          (*i as i32).to_string()
         } else {
-          format!("{f}f")
+          i.to_string()
         }
       }
+      StackEntry::Float(f) => format_float(*f),
       StackEntry::String(string) => format!("\"{string}\""),
       StackEntry::ResultStruct { values } => {
         let values = values
@@ -337,23 +769,52 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
             ..
           }
         ) {
-          format!("{}.f_{field}", self.format_stack_entry(source, function))
+          match &source.entry {
+            StackEntry::Deref(pointer) => {
+              format!("{}->f_{field}", self.format_stack_entry(pointer, function))
+            }
+            _ => format!("{}.f_{field}", self.format_stack_entry(source, function))
+          }
         } else {
           format!("{}", self.format_stack_entry(source, function))
         }
       }
       StackEntry::Offset { source, offset } => {
-        match &source.entry {
-          StackEntry::Ref(rf) => {
+        match (&source.entry, &offset.entry) {
+          // Negative offsets point in front of the source
+          (_, StackEntry::Int(value)) if *value < 0 => {
+            format!(
+              "*({} - {})",
+              self.format_stack_entry(source, function),
+              -value
+            )
+          }
+          (StackEntry::Ref(rf), StackEntry::Int(_)) => {
             format!(
               "{}.f_{}",
               self.format_stack_entry(rf, function),
               self.format_stack_entry(offset, function)
             )
           }
+          (_, StackEntry::Int(_)) => {
+            let arrow = matches!(
+              source.ty.borrow().get_concrete(),
+              ValueTypeInfo {
+                ty: ValueType::Ref(_),
+                ..
+              }
+            );
+            format!(
+              "{}{}f_{}",
+              self.format_stack_entry(source, function),
+              if arrow { "->" } else { "." },
+              self.format_stack_entry(offset, function)
+            )
+          }
+          // Offsets computed at runtime can't be named as a field
           _ => {
             format!(
-              "{}.f_{}",
+              "*({} + {})",
               self.format_stack_entry(source, function),
               self.format_stack_entry(offset, function)
             )
@@ -379,18 +840,83 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
         format!("{}", self.format_local(*local, function))
       }
       StackEntry::Static(stat) => format!("static_{stat}"),
-      StackEntry::Global(global) => format!("global_{global}"),
-      StackEntry::Deref(deref) => {
-        match &deref.entry {
-          StackEntry::Ref(rf) => self.format_stack_entry(rf, function),
-          _ => {
-            format!("*({})", self.format_stack_entry(deref, function))
-          }
-        }
-      }
+      StackEntry::Global(global) => format_global(*global),
+      StackEntry::Deref(deref) => format!("*({})", self.format_stack_entry(deref, function)),
       StackEntry::Ref(rf) => format!("&{}", self.format_stack_entry(rf, function)),
-      StackEntry::CatchValue => todo!(),
+      StackEntry::CatchValue => unformattable(&value.entry),
       StackEntry::BinaryOperator { lhs, rhs, op, .. } => {
+        if self.options.flag_tests {
+          if let Some((value, flag, negated)) = flag_test(value) {
+            return format!(
+              "{}has_flag({}, 0x{:X})",
+              if negated { "!" } else { "" },
+              self.format_stack_entry(value, function),
+              flag as u32
+            );
+          }
+        }
+
+        // `%` is not defined for floats
+        if is_float_modulo(value, *op) {
+          return format!(
+            "fmod({}, {})",
+            self.format_stack_entry(lhs, function),
+            self.format_stack_entry(rhs, function)
+          );
+        }
+
+        if self.options.integer_division != IntegerDivisionStyle::Plain
+          && is_integer_division(value, *op)
+        {
+          let lhs = self.format_stack_entry(lhs, function);
+          let rhs = self.format_stack_entry(rhs, function);
+          let (symbol, helper) = match op {
+            BinaryOperator::Divide => ("/", "idiv"),
+            _ => ("%", "imod")
+          };
+
+          return match self.options.integer_division {
+            IntegerDivisionStyle::Helper => format!("{helper}({lhs}, {rhs})"),
+            _ => format!("{lhs} {symbol} {rhs} /* int */")
+          };
+        }
+
+        // Addresses compared against zero are null checks
+        if let (BinaryOperator::Equal | BinaryOperator::NotEqual, true, StackEntry::Int(0)) =
+          (op, lhs.is_ref(), &rhs.entry)
+        {
+          return format!(
+            "{} {} NULL",
+            self.format_stack_entry(lhs, function),
+            if matches!(op, BinaryOperator::Equal) {
+              "=="
+            } else {
+              "!="
+            }
+          );
+        }
+
+        // `&`, `|` and `^` bind weaker than comparisons
+        let comparison = matches!(
+          op,
+          BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterOrEqual
+            | BinaryOperator::LowerThan
+            | BinaryOperator::LowerOrEqual
+        );
+        let format_side = |side: &StackEntryInfo| {
+          match side.entry {
+            StackEntry::BinaryOperator {
+              op:
+                BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor,
+              ..
+            } if comparison => self.format_operand(side, function),
+            _ => self.format_stack_entry(side, function)
+          }
+        };
+
         // TODO: Braces
         let op = match op {
           BinaryOperator::Add => "+",
@@ -503,18 +1029,14 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
           }
           BinaryOperator::BitTest => {
             return format!(
-              "BitTest({lhs}, {rhs})",
-              lhs = self.format_stack_entry(lhs, function),
-              rhs = self.format_stack_entry(rhs, function)
+              "(({lhs} >> {rhs}) & 1)",
+              lhs = self.format_operand(lhs, function),
+              rhs = self.format_operand(rhs, function)
             )
           }
         };
 
-        format!(
-          "{lhs} {op} {rhs}",
-          lhs = self.format_stack_entry(lhs, function),
-          rhs = self.format_stack_entry(rhs, function)
-        )
+        format!("{} {op} {}", format_side(lhs), format_side(rhs))
       }
       StackEntry::UnaryOperator { lhs, op, .. } => {
         let op = match op {
@@ -528,8 +1050,14 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
         let ty = self.format_type(&value.ty.borrow());
         format!("({ty}){}", self.format_stack_entry(source, function))
       }
+      // Hashes of literals are constants, the literal is kept in a comment
       StackEntry::StringHash(str) => {
-        format!("HASH({})", self.format_stack_entry(str, function))
+        match str.entry {
+          StackEntry::String(string) => {
+            format!("0x{:08X} /* \"{}\" */", joaat(string), comment_text(string))
+          }
+          _ => format!("HASH({})", self.format_stack_entry(str, function))
+        }
       }
       StackEntry::FunctionCallResult {
         args,
@@ -561,22 +1089,99 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
       .functions
       .get(&address)
       .map(|f| f.name.clone())
-      .unwrap_or_else(|| format!("unk_fn{address:08X}"));
+      .or_else(|| self.format_related_function(address))
+      .unwrap_or_else(|| self.format_unknown_function(address));
     format!("{function}({args})")
   }
 
+  /// Calls that don't land on a function of this script are resolved in the related
+  /// scripts, as long as only one of them has a function at `address`.
+  fn format_related_function(&self, address: usize) -> Option<String> {
+    self.data.related?.get(address).map(str::to_owned)
+  }
+
+  fn format_unknown_function(&self, address: usize) -> String {
+    match &self.options.unknown_functions {
+      UnknownFunctionNaming::Address { prefix } => format!("{prefix}{address:08X}"),
+      UnknownFunctionNaming::Containing { prefix } => {
+        // The last function starting at or before `address`
+        let index = self
+          .sorted
+          .partition_point(|function| function.location <= address);
+        let containing = index
+          .checked_sub(1)
+          .map(|index| self.sorted[index])
+          .filter(|function| {
+            matches!(
+              function.instructions.last(),
+              Some(last) if address < last.pos + last.bytes.len()
+            )
+          });
+
+        match containing {
+          Some(function) => format!("{}_0x{:X}", function.name, address - function.location),
+          None => format!("{prefix}{address:08X}")
+        }
+      }
+    }
+  }
+
+  fn format_case_value(&self, value: i64, hashed: bool) -> String {
+    // Case values are stored unsigned but compared against signed ints. Values this large
+    // in either direction are almost always hashes.
+    let signed = value as u32 as i32;
+    if !hashed && (-0x10000..0x10000).contains(&signed) {
+      return signed.to_string();
+    }
+
+    let hash = value as u32;
+    match self.data.hash_names.and_then(|names| names.get(hash)) {
+      Some(name) => format!("0x{hash:08X} /* \"{}\" */", comment_text(name)),
+      None => format!("0x{hash:08X}")
+    }
+  }
+
   fn format_native_call(
     &self,
     native_hash: u64,
     args: &[StackEntryInfo],
     function: &DecompiledFunction
   ) -> String {
+    let native = self.data.get_native(native_hash);
+    // Arguments only line up with the parameters when every vector was grouped
+    let param_names = match native {
+      Some(native) if self.options.native_param_names && native.params.len() == args.len() => {
+        Some(&native.params)
+      }
+      _ => None
+    };
+
     let args = args
       .iter()
-      .map(|arg| format!("{}", self.format_stack_entry(arg, function)))
+      .enumerate()
+      .map(|(index, arg)| {
+        let formatted = match &arg.entry {
+          // Grouped `Vector3` arguments
+          StackEntry::ResultStruct { values } => {
+            format!(
+              "{{ {} }}",
+              values
+                .iter()
+                .map(|value| self.format_stack_entry(value, function))
+                .join(", ")
+            )
+          }
+          _ => self.format_stack_entry(arg, function)
+        };
+
+        match param_names {
+          Some(params) => format!("/* {} */ {formatted}", comment_text(&params[index].name)),
+          None => formatted
+        }
+      })
       .join(", ");
 
-    if let Some(native) = self.data.natives.get_native(native_hash) {
+    if let Some(native) = native {
       format!("{}({args})", native.name)
     } else {
       format!("unk_0x{native_hash:016X}({args})")
@@ -594,7 +1199,6 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
     }
   }
 
-  #[allow(clippy::only_used_in_recursion)]
   fn format_type(&self, ty: &LinkedValueType) -> String {
     let ty = ty.get_concrete();
 
@@ -608,17 +1212,1724 @@ impl<'d, 'i, 'b> CppFormatter<'d, 'i, 'b> {
         format!("struct<{fields}>")
       }
       ValueType::Array { item_type } => format!("{}[]", self.format_type(&item_type.borrow())),
-      ValueType::Function { .. } => todo!(),
+      ValueType::Function { params, returns } => {
+        let params = params
+          .iter()
+          .map(|param| self.format_type(param))
+          .join(", ");
+        format!("{} (*)({params})", self.format_type(&returns.borrow()))
+      }
       ValueType::Primitive(primitive) => {
+        let primitive = match self.options.min_confidence {
+          Some(min) if ty.confidence < min => &Primitives::Unknown,
+          _ => primitive
+        };
         match primitive {
           Primitives::Float => "float".to_owned(),
           Primitives::Int => "int".to_owned(),
           Primitives::String => "const char*".to_owned(),
           Primitives::Bool => "bool".to_owned(),
-          Primitives::Unknown => "any".to_owned()
+          Primitives::Unknown => {
+            match self.options.unknown_types {
+              UnknownTypeStyle::Any | UnknownTypeStyle::Typedef => "any".to_owned(),
+              UnknownTypeStyle::Int => "int /* unknown */".to_owned()
+            }
+          }
         }
       }
       ValueType::Ref(t) => format!("{}*", self.format_type(&t.borrow()))
     }
   }
 }
+
+fn return_struct_name(function: &DecompiledFunction) -> Option<String> {
+  let returns = function.returns.as_ref()?;
+  matches!(returns.borrow().get_concrete().ty, ValueType::Struct { fields } if fields.len() > 1)
+    .then(|| format!("{}_result", function.name))
+}
+
+/// Placeholder for values the formatter can't render, so real world scripts never crash
+/// it.
+fn unformattable(value: &impl fmt::Debug) -> String {
+  format!("/* unformattable: {value:?} */")
+}
+
+/// `text` made safe to put in a block comment, names come from user supplied files.
+fn comment_text(text: &str) -> String {
+  text.replace("*/", "* /")
+}
+
+fn format_global(global: usize) -> String {
+  match split_global_index(global) {
+    (0, index) => format!("global_{index}"),
+    (block, index) => format!("global_{block}_{index}")
+  }
+}
+
+fn format_float(f: f32) -> String {
+  if f.trunc() == f {
+    format!("{f}.f")
+  } else {
+    format!("{f}f")
+  }
+}
+
+/// Renders a raw static slot as a literal of the static's type. Only the low 4 bytes of
+/// a slot are used by script values.
+fn format_static_value(value: u64, ty: &LinkedValueType) -> String {
+  match ty.get_concrete().ty {
+    ValueType::Primitive(Primitives::Float) => format_float(f32::from_bits(value as u32)),
+    ValueType::Primitive(Primitives::Bool) if value == 1 => "true".to_owned(),
+    _ => (value as u32 as i32).to_string()
+  }
+}
+
+/// The value and the mask of `value & flag`, `value & flag == 0` and `value & flag != 0`
+/// when `flag` is a single bit and the result is used as a bool. The bool is whether the
+/// test is negated.
+fn flag_test<'e, 'i>(value: &'e StackEntryInfo<'i>) -> Option<(&'e StackEntryInfo<'i>, i64, bool)> {
+  let (test, negated) = match &value.entry {
+    StackEntry::BinaryOperator {
+      lhs,
+      rhs,
+      op: op @ (BinaryOperator::Equal | BinaryOperator::NotEqual)
+    } if matches!(rhs.entry, StackEntry::Int(0)) => (&**lhs, matches!(op, BinaryOperator::Equal)),
+    _ if matches!(
+      value.ty.borrow().get_concrete().ty,
+      ValueType::Primitive(Primitives::Bool)
+    ) =>
+    {
+      (value, false)
+    }
+    _ => return None
+  };
+
+  match &test.entry {
+    StackEntry::BinaryOperator {
+      lhs,
+      rhs,
+      op: BinaryOperator::BitwiseAnd
+    } => {
+      match rhs.entry {
+        StackEntry::Int(flag) if (flag as u32).is_power_of_two() => Some((lhs, flag, negated)),
+        _ => None
+      }
+    }
+    _ => None
+  }
+}
+
+/// Whether `value` is a `%` of floats, which has to be rendered as `fmod`.
+fn is_float_modulo(value: &StackEntryInfo, op: BinaryOperator) -> bool {
+  matches!(op, BinaryOperator::Modulo)
+    && matches!(
+      value.ty.borrow().get_concrete().ty,
+      ValueType::Primitive(Primitives::Float)
+    )
+}
+
+/// Whether `value` is a `/` or `%` of integers, which truncates.
+fn is_integer_division(value: &StackEntryInfo, op: BinaryOperator) -> bool {
+  matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo)
+    && matches!(
+      value.ty.borrow().get_concrete().ty,
+      ValueType::Primitive(Primitives::Int)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    common::joaat,
+    decompiler::ScriptStatics,
+    disassembler::opcodes::Opcode,
+    resources::HashNames,
+    script::ScriptTables,
+    test_utils::{
+      decompile_cpp, decompile_cpp_with, decompile_with, decompile_with_data, Asm, NO_TABLES
+    }
+  };
+
+  /// A function whose only parameter is never used, so its type stays unknown.
+  fn unused_parameter() -> Vec<u8> {
+    Asm::default().enter(1, 3).leave(1, 0).build()
+  }
+
+  #[test]
+  fn unknown_types_typedef_declares_any_once() {
+    let options = CppFormatterOptions {
+      unknown_types: UnknownTypeStyle::Typedef,
+      ..Default::default()
+    };
+    let (header, function) = decompile_with(
+      &unused_parameter(),
+      NO_TABLES,
+      "{}",
+      options,
+      |formatter, functions| {
+        (
+          formatter.format_header(),
+          formatter.format_function(functions[0])
+        )
+      }
+    );
+
+    assert_eq!(header.matches("typedef int any;").count(), 1);
+    assert!(function.contains("void func_0(any parameter_0 /* 0 */)"));
+  }
+
+  #[test]
+  fn unknown_types_int_renders_commented_int() {
+    let options = CppFormatterOptions {
+      unknown_types: UnknownTypeStyle::Int,
+      ..Default::default()
+    };
+    let (header, function) = decompile_with(
+      &unused_parameter(),
+      NO_TABLES,
+      "{}",
+      options,
+      |formatter, functions| {
+        (
+          formatter.format_header(),
+          formatter.format_function(functions[0])
+        )
+      }
+    );
+
+    assert!(!header.contains("typedef"));
+    assert!(function.contains("void func_0(int /* unknown */ parameter_0 /* 0 */)"));
+    assert!(decompile_cpp(&unused_parameter(), Default::default()).contains("any parameter_0"));
+  }
+
+  #[test]
+  fn break_out_of_nested_loop_names_outer_loop() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .label("outer")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "end")
+      .label("inner")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "after_inner")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::PushConst2)
+      .jump(Opcode::IfNotEqualJumpZero, "inner")
+      .jump(Opcode::Jump, "end")
+      .label("after_inner")
+      .jump(Opcode::Jump, "outer")
+      .label("end")
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("break; // breaks loop at 0x5"));
+  }
+
+  #[test]
+  fn large_int_constants_compare_as_signed_values() {
+    let compare = |constant| {
+      let code = Asm::default()
+        .enter(1, 3)
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(Opcode::PushConstU32)
+        .u32(constant)
+        .op(Opcode::IntegerGreaterThan)
+        .leave(1, 1)
+        .build();
+      decompile_cpp(&code, Default::default())
+    };
+
+    assert!(compare(0xFFFFFFFF).contains("return parameter_0 > -1;"));
+    assert!(compare(0x80000000).contains("return parameter_0 > -2147483648;"));
+    assert!(compare(0x7FFFFFFF).contains("return parameter_0 > 2147483647;"));
+  }
+
+  #[test]
+  fn casts_of_constants_are_folded() {
+    let cast =
+      |value: Asm, cast| decompile_cpp(&value.op(cast).leave(1, 1).build(), Default::default());
+    let param = || Asm::default().enter(1, 3).op(Opcode::LocalU8Load).u8(0);
+    let int = || Asm::default().enter(1, 3).op(Opcode::PushConst2);
+    let float = || {
+      Asm::default()
+        .enter(1, 3)
+        .op(Opcode::PushConstFloat)
+        .u32(2.5f32.to_bits())
+    };
+
+    assert!(cast(int(), Opcode::IntegerToFloat).contains("return 2.f;"));
+    assert!(cast(float(), Opcode::FloatToInteger).contains("return 2;"));
+    assert!(cast(param(), Opcode::IntegerToFloat).contains("return (float)parameter_0;"));
+    assert!(cast(param(), Opcode::FloatToInteger).contains("return (int)parameter_0;"));
+  }
+
+  #[test]
+  fn argument_types_reach_unknown_callee_parameters() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConstFloat)
+      .u32(1.5f32.to_bits())
+      .op(Opcode::FunctionCall)
+      .u24(17)
+      .leave(0, 0)
+      // 17
+      .enter(1, 3)
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("func_1(1.5f);"));
+    assert!(cpp.contains("void func_1(float parameter_0 /* 0 */)"));
+  }
+
+  #[test]
+  fn hashed_switch_cases_are_hex_with_known_names() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Switch)
+      .u8(3)
+      .u32(joaat("PLAYER_ZERO"))
+      .offset("zero")
+      .u32(joaat("PLAYER_ONE"))
+      .offset("one")
+      .u32(5)
+      .offset("five")
+      .jump(Opcode::Jump, "end")
+      .label("zero")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .jump(Opcode::Jump, "end")
+      .label("one")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .jump(Opcode::Jump, "end")
+      .label("five")
+      .op(Opcode::PushConst3)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .label("end")
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_with_data(
+      &code,
+      NO_TABLES,
+      "{}",
+      |builder| builder.hash_names(HashNames::from_text("PLAYER_ZERO")),
+      Default::default(),
+      |formatter, functions| formatter.format_function(functions[0])
+    );
+
+    assert!(cpp.contains(&format!(
+      "case 0x{:08X} /* \"PLAYER_ZERO\" */:",
+      joaat("PLAYER_ZERO")
+    )));
+    assert!(cpp.contains(&format!("case 0x{:08X}:", joaat("PLAYER_ONE"))));
+    assert!(cpp.contains("case 5:"));
+    assert!(!cpp.contains("HASH("));
+  }
+
+  #[test]
+  fn read_modify_write_renders_as_compound_assignment() {
+    let function = |body: Asm| {
+      decompile_cpp(
+        &body.leave(0, 0).build(),
+        CppFormatterOptions {
+          unknown_types: UnknownTypeStyle::Int,
+          ..Default::default()
+        }
+      )
+    };
+    let local = || Asm::default().enter(0, 3).op(Opcode::LocalU8Load).u8(2);
+
+    // The address is duplicated so the store can reuse it
+    let increment = function(
+      Asm::default()
+        .enter(0, 3)
+        .op(Opcode::LocalU8)
+        .u8(2)
+        .op(Opcode::Dup)
+        .op(Opcode::Load)
+        .op(Opcode::PushConst1)
+        .op(Opcode::IntegerAdd)
+        .op(Opcode::StoreRev)
+        .op(Opcode::Drop)
+    );
+    let decrement = function(
+      local()
+        .op(Opcode::PushConst1)
+        .op(Opcode::IntegerSubtract)
+        .op(Opcode::LocalU8Store)
+        .u8(2)
+    );
+    let add = function(
+      local()
+        .op(Opcode::AddU8)
+        .u8(5)
+        .op(Opcode::LocalU8Store)
+        .u8(2)
+    );
+
+    assert!(increment.contains("local_0++;"), "{increment}");
+    assert!(decrement.contains("local_0--;"), "{decrement}");
+    assert!(add.contains("local_0 += 5;"), "{add}");
+  }
+
+  #[test]
+  fn else_if_chains_stay_flat() {
+    let arms = [
+      ("one", Opcode::PushConst1, "two"),
+      ("two", Opcode::PushConst2, "three"),
+      ("three", Opcode::PushConst3, "other")
+    ];
+    let mut code = Asm::default().enter(1, 3);
+    for (label, value, next) in arms {
+      code = code
+        .label(label)
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(value)
+        .op(Opcode::IntegerEquals)
+        .jump(Opcode::JumpZero, next)
+        .op(Opcode::PushConst5)
+        .op(Opcode::LocalU8Store)
+        .u8(0)
+        .jump(Opcode::Jump, "end");
+    }
+    let code = code
+      .label("other")
+      .op(Opcode::PushConst4)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .label("end")
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    let body = cpp.split_once("{\n").unwrap().1;
+    assert!(
+      body.starts_with(
+        "\tif (parameter_0 == 1)\n\t{\n\t\tparameter_0 = 5;\n\t}\n\
+         \telse if (parameter_0 == 2)\n\t{\n\t\tparameter_0 = 5;\n\t}\n\
+         \telse if (parameter_0 == 3)\n\t{\n\t\tparameter_0 = 5;\n\t}\n\
+         \telse\n\t{\n\t\tparameter_0 = 4;\n\t}\n}"
+      ),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn int_constants_passed_as_floats_keep_their_bits() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConstU32)
+      .u32(1.5f32.to_bits())
+      .op(Opcode::FunctionCall)
+      .u24(22)
+      .op(Opcode::PushConst0)
+      .op(Opcode::FunctionCall)
+      .u24(22)
+      .leave(0, 0)
+      // 22, adds its parameter to a float
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::PushConstF1)
+      .op(Opcode::FloatAdd)
+      .op(Opcode::Drop)
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("func_1(1.5f);"), "{cpp}");
+    assert!(cpp.contains("func_1(0.f);"), "{cpp}");
+  }
+
+  #[test]
+  fn trailing_void_returns_are_dropped_and_early_ones_kept() {
+    let trailing = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .leave(1, 0)
+      .build();
+    let early = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "end")
+      .leave(1, 0)
+      .label("end")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(&trailing, Default::default());
+    assert!(cpp.ends_with("{\n\tparameter_0 = 2;\n}\n"), "{cpp}");
+
+    let cpp = decompile_cpp(&early, Default::default());
+    assert!(
+      cpp.contains("\tif (parameter_0)\n\t{\n\t\treturn;\n\t}\n"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn negated_comparisons_are_flipped_unless_nan_matters() {
+    let negate = |comparison| {
+      let code = Asm::default()
+        .enter(2, 4)
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(Opcode::LocalU8Load)
+        .u8(1)
+        .op(comparison)
+        .op(Opcode::IntegerNot)
+        .leave(2, 1)
+        .build();
+      decompile_cpp(&code, Default::default())
+    };
+
+    let cases = [
+      (Opcode::IntegerEquals, "parameter_0 != parameter_1"),
+      (Opcode::IntegerNotEquals, "parameter_0 == parameter_1"),
+      (Opcode::IntegerGreaterThan, "parameter_0 <= parameter_1"),
+      (Opcode::IntegerGreaterOrEqual, "parameter_0 < parameter_1"),
+      (Opcode::IntegerLowerThan, "parameter_0 >= parameter_1"),
+      (Opcode::IntegerLowerOrEqual, "parameter_0 > parameter_1"),
+      (Opcode::FloatEquals, "parameter_0 != parameter_1"),
+      (Opcode::FloatNotEquals, "parameter_0 == parameter_1"),
+      (Opcode::FloatGreaterThan, "!(parameter_0 > parameter_1)"),
+      (Opcode::FloatGreaterOrEqual, "!(parameter_0 >= parameter_1)"),
+      (Opcode::FloatLowerThan, "!(parameter_0 < parameter_1)"),
+      (Opcode::FloatLowerOrEqual, "!(parameter_0 <= parameter_1)")
+    ];
+    for (comparison, expected) in cases {
+      let cpp = negate(comparison);
+      assert!(
+        cpp.contains(&format!("return {expected};")),
+        "{comparison:?}: {cpp}"
+      );
+    }
+  }
+
+  #[test]
+  fn throws_terminate_scripts_without_catch_handlers() {
+    let throw = || {
+      Asm::default()
+        .enter(0, 2)
+        .op(Opcode::PushConst1)
+        .op(Opcode::Throw)
+        .leave(0, 0)
+    };
+    let catching = throw()
+      .enter(0, 2)
+      .op(Opcode::Catch)
+      .op(Opcode::Drop)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&throw().build(), Default::default());
+    assert!(cpp.contains("throw 1; // terminates script"), "{cpp}");
+
+    let cpp = decompile_cpp(&catching, Default::default());
+    assert!(cpp.contains("throw 1;\n"), "{cpp}");
+  }
+
+  #[test]
+  fn integer_division_is_annotated_and_float_division_plain() {
+    let divide = |division, style| {
+      let code = Asm::default()
+        .enter(2, 4)
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(Opcode::LocalU8Load)
+        .u8(1)
+        .op(division)
+        .leave(2, 1)
+        .build();
+      decompile_cpp(
+        &code,
+        CppFormatterOptions {
+          integer_division: style,
+          ..Default::default()
+        }
+      )
+    };
+
+    let cpp = divide(Opcode::IntegerDivide, IntegerDivisionStyle::Comment);
+    assert!(
+      cpp.contains("return parameter_0 / parameter_1 /* int */;"),
+      "{cpp}"
+    );
+    let cpp = divide(Opcode::IntegerModulo, IntegerDivisionStyle::Helper);
+    assert!(
+      cpp.contains("return imod(parameter_0, parameter_1);"),
+      "{cpp}"
+    );
+    let cpp = divide(Opcode::IntegerDivide, IntegerDivisionStyle::Plain);
+    assert!(cpp.contains("return parameter_0 / parameter_1;"), "{cpp}");
+
+    for style in [IntegerDivisionStyle::Comment, IntegerDivisionStyle::Helper] {
+      let cpp = divide(Opcode::FloatDivide, style);
+      assert!(cpp.contains("return parameter_0 / parameter_1;"), "{cpp}");
+    }
+  }
+
+  #[test]
+  fn statics_are_declared_with_their_initial_values() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::StaticU8Load)
+      .u8(0)
+      .op(Opcode::AddU8)
+      .u8(1)
+      .op(Opcode::StaticU8Store)
+      .u8(1)
+      .leave(0, 0)
+      .build();
+
+    let statics = decompile_with_data(
+      &code,
+      NO_TABLES,
+      "{}",
+      |builder| builder.statics(ScriptStatics::with_values(&[42, 0])),
+      Default::default(),
+      |formatter, _| formatter.format_statics()
+    );
+
+    assert_eq!(
+      statics,
+      "static int static_0 = 42;\nstatic int static_1;\n\n"
+    );
+  }
+
+  #[test]
+  fn annotated_assignments_end_in_their_offsets() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::PushConstU8)
+      .u8(5)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(
+      &code,
+      CppFormatterOptions {
+        annotate_offsets: true,
+        ..Default::default()
+      }
+    );
+    assert!(cpp.contains("local_0 = 5; // 0x00000007"), "{cpp}");
+    assert!(!decompile_cpp(&code, Default::default()).contains("// 0x"));
+  }
+
+  #[test]
+  fn vector_native_arguments_render_as_one_argument() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConstF1)
+      .op(Opcode::PushConstFloat)
+      .u32(2f32.to_bits())
+      .op(Opcode::PushConstF0)
+      .op(Opcode::PushConstU8)
+      .u8(7)
+      .op(Opcode::NativeCall)
+      .u8(4 << 2)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x1234]
+      },
+      r#"{ "natives": { "0x1234": {
+        "name": "ADD_BLIP",
+        "params": [{ "type": "Vector3", "name": "coords" }, { "type": "int", "name": "sprite" }]
+      } } }"#,
+      Default::default()
+    );
+    assert!(cpp.contains("ADD_BLIP({ 1.f, 2.f, 0.f }, 7);"), "{cpp}");
+  }
+
+  #[test]
+  fn arguments_that_dont_line_up_with_vector_params_keep_their_types() {
+    let code = Asm::default()
+      .enter(0, 6)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::LocalU8Load)
+      .u8(3)
+      .op(Opcode::LocalU8Load)
+      .u8(4)
+      .op(Opcode::LocalU8Load)
+      .u8(5)
+      .op(Opcode::NativeCall)
+      .u8(4 << 2)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x1234]
+      },
+      r#"{ "natives": { "0x1234": {
+        "name": "ADD_BLIP",
+        "params": [{ "type": "Vector3", "name": "coords" }]
+      } } }"#,
+      Default::default()
+    );
+    assert!(
+      cpp.contains("ADD_BLIP(local_0, local_1, local_2, local_3);"),
+      "{cpp}"
+    );
+    assert!(!cpp.contains("float"), "{cpp}");
+  }
+
+  #[test]
+  fn calls_outside_of_known_functions_use_the_configured_naming() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::FunctionCall)
+      .u24(18)
+      .op(Opcode::FunctionCall)
+      .u24(0x100)
+      .leave(0, 0)
+      // 16
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let call_names = |unknown_functions| {
+      decompile_cpp(
+        &code,
+        CppFormatterOptions {
+          unknown_functions,
+          ..Default::default()
+        }
+      )
+    };
+
+    let cpp = call_names(UnknownFunctionNaming::Address {
+      prefix: "sub_".to_owned()
+    });
+    assert!(cpp.contains("sub_00000012();"), "{cpp}");
+    assert!(cpp.contains("sub_00000100();"), "{cpp}");
+
+    let cpp = call_names(UnknownFunctionNaming::Containing {
+      prefix: "ext_".to_owned()
+    });
+    assert!(cpp.contains("func_1_0x2();"), "{cpp}");
+    assert!(cpp.contains("ext_00000100();"), "{cpp}");
+  }
+
+  #[test]
+  fn block_comments_name_the_graph_nodes() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(1, 0)
+      .build();
+
+    let (cpp, blocks) = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      CppFormatterOptions {
+        block_comments: true,
+        ..Default::default()
+      },
+      |formatter, functions| {
+        (
+          formatter.format_function(functions[0]),
+          functions[0].blocks.clone()
+        )
+      }
+    );
+
+    // The stores of 1 and 2 are at 11 and 14
+    let block_of = |pos| {
+      blocks
+        .iter()
+        .position(|block| block.contains(&pos))
+        .unwrap()
+    };
+    let inner = format!("// --- block {} ---", block_of(11));
+    let after = format!("// --- block {} ---", block_of(14));
+    assert_ne!(inner, after);
+    let lines = cpp.lines().map(str::trim).collect::<Vec<_>>();
+    let line_of = |line: &str| lines.iter().position(|l| *l == line).expect(&cpp);
+    assert_eq!(line_of(&inner) + 1, line_of("local_0 = 1;"), "{cpp}");
+    assert_eq!(line_of(&after) + 1, line_of("local_0 = 2;"), "{cpp}");
+    assert!(!decompile_cpp(&code, Default::default()).contains("// --- block"));
+  }
+
+  #[test]
+  fn round_trip_casts_only_cancel_when_exact() {
+    let round_trip = |value: Asm, first, second| {
+      decompile_cpp(
+        &value.op(first).op(second).leave(2, 1).build(),
+        Default::default()
+      )
+    };
+    let param = || Asm::default().enter(2, 4).op(Opcode::LocalU8Load).u8(0);
+    let comparison = || {
+      param()
+        .op(Opcode::LocalU8Load)
+        .u8(1)
+        .op(Opcode::IntegerLowerThan)
+    };
+
+    let cpp = round_trip(param(), Opcode::IntegerToFloat, Opcode::FloatToInteger);
+    assert!(cpp.contains("return (int)(float)parameter_0;"), "{cpp}");
+    let cpp = round_trip(param(), Opcode::FloatToInteger, Opcode::IntegerToFloat);
+    assert!(cpp.contains("return (float)(int)parameter_0;"), "{cpp}");
+    let cpp = round_trip(comparison(), Opcode::IntegerToFloat, Opcode::FloatToInteger);
+    assert!(cpp.contains("return parameter_0 < parameter_1;"), "{cpp}");
+    let cpp = round_trip(
+      Asm::default().enter(2, 4).op(Opcode::PushConstU8).u8(5),
+      Opcode::IntegerToFloat,
+      Opcode::FloatToInteger
+    );
+    assert!(cpp.contains("return 5;"), "{cpp}");
+  }
+
+  #[test]
+  fn infinite_loops_starting_with_a_break_use_its_condition() {
+    let guarded = |comparison| {
+      let code = Asm::default()
+        .enter(2, 5)
+        .label("loop")
+        .op(Opcode::PushConst1)
+        .jump(Opcode::JumpZero, "end")
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(Opcode::LocalU8Load)
+        .u8(1)
+        .op(comparison)
+        .jump(Opcode::JumpZero, "body")
+        .jump(Opcode::Jump, "end")
+        .label("body")
+        .op(Opcode::PushConst1)
+        .op(Opcode::LocalU8Store)
+        .u8(4)
+        .jump(Opcode::Jump, "loop")
+        .label("end")
+        .leave(2, 0)
+        .build();
+      decompile_cpp(&code, Default::default())
+    };
+
+    let cpp = guarded(Opcode::IntegerLowerThan);
+    assert!(cpp.contains("while (parameter_0 >= parameter_1)"), "{cpp}");
+    assert!(!cpp.contains("break;"), "{cpp}");
+    assert!(cpp.contains("local_0 = 1;"), "{cpp}");
+    let cpp = guarded(Opcode::FloatLowerThan);
+    assert!(
+      cpp.contains("while (!(parameter_0 < parameter_1))"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn natural_loops_keep_their_condition() {
+    let code = Asm::default()
+      .enter(2, 5)
+      .label("loop")
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::LocalU8Load)
+      .u8(1)
+      .op(Opcode::IntegerLowerThan)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .jump(Opcode::Jump, "loop")
+      .label("end")
+      .leave(2, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("while (parameter_0 < parameter_1)"), "{cpp}");
+  }
+
+  #[test]
+  fn text_label_copies_list_their_sources_in_order() {
+    let code = Asm::default()
+      .enter(0, 6)
+      .op(Opcode::PushConst1)
+      .op(Opcode::PushConst2)
+      .op(Opcode::PushConst2)
+      .op(Opcode::PushConstU8)
+      .u8(16)
+      .op(Opcode::LocalU8)
+      .u8(2)
+      .op(Opcode::TextLabelCopy)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains("text_label_copy(&local_0, { 1, 2 }, 16);"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn globals_are_named_by_block_and_index() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::GlobalU24)
+      .u24(0x40005)
+      .op(Opcode::OffsetU8Load)
+      .u8(4)
+      .op(Opcode::GlobalU24)
+      .u24(5)
+      .op(Opcode::OffsetU8Store)
+      .u8(4)
+      .leave(0, 0)
+      .build();
+
+    let (globals, cpp) = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      Default::default(),
+      |formatter, functions| {
+        (
+          formatter.format_globals(),
+          formatter.format_function(functions[0])
+        )
+      }
+    );
+
+    assert!(cpp.contains("global_5.f_4 = global_1_5.f_4;"), "{cpp}");
+    assert!(globals.contains("struct global_1_5_t\n"), "{globals}");
+    assert!(globals.contains("struct global_5_t\n"), "{globals}");
+  }
+
+  #[test]
+  fn runtime_offsets_are_pointer_arithmetic() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::LocalU8)
+      .u8(3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Offset)
+      .op(Opcode::Load)
+      .leave(1, 1)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("return *(&local_0 + parameter_0);"), "{cpp}");
+    assert!(!cpp.contains(".f_"), "{cpp}");
+  }
+
+  #[test]
+  fn functions_stream_to_a_writer() {
+    let code = Asm::default()
+      .enter(1, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(1, 0)
+      .build();
+
+    let streamed = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      CppFormatterOptions {
+        block_comments: true,
+        ..Default::default()
+      },
+      |formatter, functions| {
+        let mut streamed = String::new();
+        formatter
+          .format_function_to(functions[0], &mut streamed)
+          .unwrap();
+        streamed
+      }
+    );
+
+    assert_eq!(
+      streamed,
+      concat!(
+        "void func_0(bool parameter_0 /* 0 */)\n",
+        "{\n",
+        "\tint local_0 /* 3 */;\n",
+        "\t\n",
+        "\t// --- block 0 ---\n",
+        "\tif (parameter_0)\n",
+        "\t{\n",
+        "\t\t// --- block 1 ---\n",
+        "\t\tlocal_0 = 1;\n",
+        "\t}\n",
+        "}\n"
+      )
+    );
+  }
+
+  #[test]
+  fn types_below_the_minimum_confidence_are_unknown() {
+    let code = Asm::default()
+      .enter(2, 6)
+      .op(Opcode::PushConstU8)
+      .u8(5)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::LocalU8Load)
+      .u8(1)
+      .op(Opcode::FloatAdd)
+      .op(Opcode::LocalU8Store)
+      .u8(5)
+      .leave(2, 0)
+      .build();
+    let declarations = |min_confidence| {
+      decompile_cpp(
+        &code,
+        CppFormatterOptions {
+          min_confidence,
+          ..Default::default()
+        }
+      )
+    };
+
+    let cpp = declarations(None);
+    assert!(cpp.contains("int local_0 /* 4 */;"), "{cpp}");
+    assert!(cpp.contains("float local_1 /* 5 */;"), "{cpp}");
+    let cpp = declarations(Some(Confidence::High));
+    assert!(cpp.contains("any local_0 /* 4 */;"), "{cpp}");
+    assert!(cpp.contains("float local_1 /* 5 */;"), "{cpp}");
+  }
+
+  #[test]
+  fn catch_checks_become_try_catch_blocks() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::Catch)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::PushConstM1)
+      .op(Opcode::IntegerEquals)
+      .jump(Opcode::JumpZero, "handler")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .jump(Opcode::Jump, "end")
+      .label("handler")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains(
+        "\ttry\n\t{\n\t\tlocal_1 = 1;\n\t}\n\tcatch (local_0)\n\t{\n\t\tlocal_1 = 2;\n\t}\n"
+      ),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn float_modulo_is_a_function_call() {
+    let code = Asm::default()
+      .enter(0, 5)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::PushConstFloat)
+      .u32(2f32.to_bits())
+      .op(Opcode::FloatModule)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::LocalU8Load)
+      .u8(3)
+      .op(Opcode::PushConstU8)
+      .u8(3)
+      .op(Opcode::IntegerModulo)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::PushConstF1)
+      .op(Opcode::FloatModule)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("local_0 = fmod(local_0, 2.f);"), "{cpp}");
+    assert!(cpp.contains("local_1 %= 3;"), "{cpp}");
+    assert!(cpp.contains("local_2 = fmod(local_0, 1.f);"), "{cpp}");
+    assert!(!cpp.contains("%= 2"), "{cpp}");
+  }
+
+  #[test]
+  fn store_rev_keeps_the_address_on_the_stack() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::LocalU8)
+      .u8(2)
+      .op(Opcode::PushConstU8)
+      .u8(5)
+      .op(Opcode::StoreRev)
+      .op(Opcode::Load)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains("\tlocal_0 = 5;\n\tlocal_1 = local_0;\n"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn u24_constants_index_past_the_first_64k_of_strings() {
+    let mut strings = vec![0; 0x12345];
+    strings.extend(b"FAR_AWAY\0");
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::PushConstU24)
+      .u24(0x12345)
+      .op(Opcode::String)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: &strings,
+        natives: &[]
+      },
+      "{}",
+      Default::default()
+    );
+    assert!(cpp.contains("local_0 = \"FAR_AWAY\";"), "{cpp}");
+  }
+
+  #[test]
+  fn function_offsets_follow_the_function_names() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .leave(0, 0)
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(
+      &code,
+      CppFormatterOptions {
+        function_offsets: true,
+        ..Default::default()
+      }
+    );
+    assert!(cpp.contains("func_0 /* @ 0x00000000 */()"), "{cpp}");
+    assert!(cpp.contains("func_1 /* @ 0x00000008 */()"), "{cpp}");
+  }
+
+  #[test]
+  fn cases_sharing_a_body_are_grouped() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Switch)
+      .u8(6);
+    // The shared body's values aren't adjacent in the jump table
+    let code = [
+      (1, "shared"),
+      (2, "shared"),
+      (9, "other"),
+      (3, "shared"),
+      (4, "shared"),
+      (5, "shared")
+    ]
+    .into_iter()
+    .fold(code, |code, (value, label)| code.u32(value).offset(label))
+    .jump(Opcode::Jump, "end")
+    .label("shared")
+    .op(Opcode::PushConst1)
+    .op(Opcode::LocalU8Store)
+    .u8(0)
+    .jump(Opcode::Jump, "end")
+    .label("other")
+    .op(Opcode::PushConst2)
+    .op(Opcode::LocalU8Store)
+    .u8(0)
+    .label("end")
+    .leave(1, 0)
+    .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains(
+        "\t\tcase 1:\n\t\tcase 2:\n\t\tcase 3:\n\t\tcase 4:\n\t\tcase 5:\n\t\t\tparameter_0 = 1;\n"
+      ),
+      "{cpp}"
+    );
+    assert_eq!(cpp.matches("parameter_0 = 1;").count(), 1, "{cpp}");
+    assert!(
+      cpp.contains("\t\tcase 9:\n\t\t\tparameter_0 = 2;\n"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn native_arguments_are_named_after_their_parameters() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConst1)
+      .op(Opcode::PushConstF1)
+      .op(Opcode::NativeCall)
+      .u8(2 << 2)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x1234]
+      },
+      r#"{ "natives": { "0x1234": {
+        "name": "SET_ENTITY_ALPHA",
+        "params": [{ "type": "Entity", "name": "entity" }, { "type": "float", "name": "*/ alpha" }]
+      } } }"#,
+      CppFormatterOptions {
+        native_param_names: true,
+        ..Default::default()
+      }
+    );
+    assert!(
+      cpp.contains("SET_ENTITY_ALPHA(/* entity */ 1, /* * / alpha */ 1.f);"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn hash_names_cannot_end_their_comment() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Switch)
+      .u8(1)
+      .u32(joaat("A*/B"))
+      .offset("case")
+      .jump(Opcode::Jump, "end")
+      .label("case")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .label("end")
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_with_data(
+      &code,
+      NO_TABLES,
+      "{}",
+      |builder| builder.hash_names(HashNames::from_text("A*/B")),
+      Default::default(),
+      |formatter, functions| formatter.format_function(functions[0])
+    );
+    assert!(
+      cpp.contains(&format!("case 0x{:08X} /* \"A* /B\" */:", joaat("A*/B"))),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn hashes_of_string_literals_are_folded() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::PushConst0)
+      .op(Opcode::String)
+      .op(Opcode::StringHash)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::PushConstU8)
+      .u8(14)
+      .op(Opcode::String)
+      .op(Opcode::StringHash)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: b"weapon_pistol\0A*/B\0",
+        ..NO_TABLES
+      },
+      "{}",
+      Default::default()
+    );
+    assert!(
+      cpp.contains(&format!(
+        "local_0 = 0x{:08X} /* \"weapon_pistol\" */;",
+        joaat("weapon_pistol")
+      )),
+      "{cpp}"
+    );
+    assert!(
+      cpp.contains(&format!(
+        "local_1 = 0x{:08X} /* \"A* /B\" */;",
+        joaat("A*/B")
+      )),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn nesting_past_the_max_depth_is_cut_off() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(0, 0)
+      .build();
+    let options = |max_depth| {
+      CppFormatterOptions {
+        max_depth,
+        ..Default::default()
+      }
+    };
+
+    let cpp = decompile_cpp(&code, options(None));
+    assert!(cpp.contains("local_1 = 1;"), "{cpp}");
+    let cpp = decompile_cpp(&code, options(Some(1)));
+    assert!(
+      cpp.contains("\tif (local_0)\n\t{\n\t\t/* nested too deep */\n\t}\n"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn else_if_chains_past_the_max_depth_are_cut_off() {
+    let code = Asm::default()
+      .enter(0, 6)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .jump(Opcode::JumpZero, "second")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(5)
+      .jump(Opcode::Jump, "end")
+      .label("second")
+      .op(Opcode::LocalU8Load)
+      .u8(3)
+      .jump(Opcode::JumpZero, "third")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(5)
+      .jump(Opcode::Jump, "end")
+      .label("third")
+      .op(Opcode::LocalU8Load)
+      .u8(4)
+      .jump(Opcode::JumpZero, "end")
+      .op(Opcode::PushConst3)
+      .op(Opcode::LocalU8Store)
+      .u8(5)
+      .label("end")
+      .leave(0, 0)
+      .build();
+    let options = |max_depth| {
+      CppFormatterOptions {
+        max_depth,
+        ..Default::default()
+      }
+    };
+
+    let cpp = decompile_cpp(&code, options(None));
+    assert!(cpp.contains("else if (local_2)"), "{cpp}");
+    let cpp = decompile_cpp(&code, options(Some(2)));
+    assert!(cpp.contains("else if (local_1)"), "{cpp}");
+    assert!(cpp.contains("/* nested too deep */"), "{cpp}");
+    assert!(!cpp.contains("if (local_2)"), "{cpp}");
+  }
+
+  #[test]
+  fn expressions_past_the_max_depth_are_cut_off() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::IntegerAdd)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::IntegerAdd)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::IntegerAdd)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(0, 0)
+      .build();
+    let options = |max_depth| {
+      CppFormatterOptions {
+        max_depth,
+        ..Default::default()
+      }
+    };
+
+    let cpp = decompile_cpp(&code, options(None));
+    assert!(
+      cpp.contains("local_1 = local_0 + local_0 + local_0 + local_0;"),
+      "{cpp}"
+    );
+    let cpp = decompile_cpp(&code, options(Some(2)));
+    assert!(
+      cpp.contains("local_1 = /* nested too deep */ + /* nested too deep */ + /* nested too deep */ + local_0;"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn constant_adds_to_addresses_are_offsets() {
+    let code = Asm::default()
+      .enter(0, 8)
+      .op(Opcode::LocalU8)
+      .u8(5)
+      .op(Opcode::AddU8)
+      .u8(16)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::AddU8)
+      .u8(16)
+      .op(Opcode::LocalU8Store)
+      .u8(4)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("local_1 = &local_3.f_2;"), "{cpp}");
+    assert!(cpp.contains("local_2 = local_0 + 16;"), "{cpp}");
+  }
+
+  #[test]
+  fn global_structs_merge_the_fields_of_all_functions() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConstF1)
+      .op(Opcode::GlobalU24)
+      .u24(5)
+      .op(Opcode::OffsetU8Store)
+      .u8(1)
+      .leave(0, 0)
+      .enter(0, 2)
+      .op(Opcode::PushConstU8)
+      .u8(7)
+      .op(Opcode::GlobalU24)
+      .u24(5)
+      .op(Opcode::OffsetU8Store)
+      .u8(3)
+      .leave(0, 0)
+      .build();
+
+    let globals = decompile_with(
+      &code,
+      NO_TABLES,
+      "{}",
+      Default::default(),
+      |formatter, functions| {
+        assert_eq!(functions.len(), 2);
+        formatter.format_globals()
+      }
+    );
+
+    assert_eq!(globals.matches("struct global_5_t").count(), 1, "{globals}");
+    assert!(globals.contains("\tfloat f_1;\n"), "{globals}");
+    assert!(globals.contains("\tint f_3;\n"), "{globals}");
+  }
+
+  #[test]
+  fn large_case_values_are_signed() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::Switch)
+      .u8(2)
+      .u32(0xFFFFFFFF)
+      .offset("minus_one")
+      .u32(0xFFFFFFFE)
+      .offset("minus_two")
+      .jump(Opcode::Jump, "end")
+      .label("minus_one")
+      .op(Opcode::PushConst1)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .jump(Opcode::Jump, "end")
+      .label("minus_two")
+      .op(Opcode::PushConst2)
+      .op(Opcode::LocalU8Store)
+      .u8(0)
+      .label("end")
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("case -1:"), "{cpp}");
+    assert!(cpp.contains("case -2:"), "{cpp}");
+    assert!(!cpp.contains("4294967295"), "{cpp}");
+  }
+
+  #[test]
+  fn unformattable_entries_become_comments() {
+    // A caught error that isn't checked doesn't turn into a `try`
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::Catch)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains("local_0 = /* unformattable: CatchValue */;"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn multiple_return_values_use_a_named_struct() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::PushConst1)
+      .op(Opcode::PushConstF1)
+      .leave(0, 2)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains("struct func_0_result\n{\n\tint f_0;\n\tfloat f_1;\n};\n"),
+      "{cpp}"
+    );
+    assert!(cpp.contains("func_0_result func_0()"), "{cpp}");
+    assert!(cpp.contains("return func_0_result{ 1, 1.f };"), "{cpp}");
+  }
+
+  #[test]
+  fn single_bit_tests_can_use_a_helper() {
+    let function = |mask: u8, compare: Opcode| {
+      let code = Asm::default()
+        .enter(1, 3)
+        .op(Opcode::LocalU8Load)
+        .u8(0)
+        .op(Opcode::PushConstU8)
+        .u8(mask)
+        .op(Opcode::BitwiseAnd)
+        .op(Opcode::PushConst0)
+        .op(compare)
+        .leave(1, 1)
+        .build();
+      decompile_cpp(
+        &code,
+        CppFormatterOptions {
+          flag_tests: true,
+          ..Default::default()
+        }
+      )
+    };
+
+    let cpp = function(4, Opcode::IntegerNotEquals);
+    assert!(cpp.contains("return has_flag(parameter_0, 0x4);"), "{cpp}");
+    let cpp = function(4, Opcode::IntegerEquals);
+    assert!(cpp.contains("return !has_flag(parameter_0, 0x4);"), "{cpp}");
+    // Masks of multiple bits aren't flag tests
+    let cpp = function(6, Opcode::IntegerNotEquals);
+    assert!(cpp.contains("return (parameter_0 & 6) != 0;"), "{cpp}");
+  }
+
+  #[test]
+  fn masked_values_are_not_flag_tests() {
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::PushConstU8)
+      .u8(4)
+      .op(Opcode::BitwiseAnd)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .leave(1, 0)
+      .build();
+
+    let cpp = decompile_cpp(
+      &code,
+      CppFormatterOptions {
+        flag_tests: true,
+        ..Default::default()
+      }
+    );
+    assert!(cpp.contains("local_0 = parameter_0 & 4;"), "{cpp}");
+  }
+
+  #[test]
+  fn fields_of_pointer_locals_use_arrows() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::OffsetU8Load)
+      .u8(1)
+      .leave(0, 1)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("return local_0->f_1;"), "{cpp}");
+  }
+
+  #[test]
+  fn fields_of_returned_pointers_use_arrows() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::NativeCall)
+      .u8(1)
+      .u8(0)
+      .u8(0)
+      .op(Opcode::OffsetU8Load)
+      .u8(1)
+      .leave(0, 1)
+      .build();
+
+    let cpp = decompile_cpp_with(
+      &code,
+      ScriptTables {
+        strings: &[],
+        natives: &[0x1234]
+      },
+      r#"{ "natives": { "0x1234": { "name": "GET_POINTER" } } }"#,
+      Default::default()
+    );
+    assert!(cpp.contains("return GET_POINTER()->f_1;"), "{cpp}");
+  }
+
+  #[test]
+  fn pointers_compared_against_zero_are_null_checks() {
+    let code = Asm::default()
+      .enter(0, 4)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::PushConst0)
+      .jump(Opcode::IfNotEqualJumpZero, "end")
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::OffsetU8Load)
+      .u8(1)
+      .op(Opcode::LocalU8Store)
+      .u8(3)
+      .label("end")
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(
+      cpp.contains("if (local_0 != NULL)\n\t{\n\t\tlocal_1 = local_0->f_1;"),
+      "{cpp}"
+    );
+  }
+
+  #[test]
+  fn self_assignments_are_dropped_unless_shared() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::LocalU8Store)
+      .u8(2)
+      .op(Opcode::GlobalU16Load)
+      .u16(5)
+      .op(Opcode::GlobalU16Store)
+      .u16(5)
+      .leave(0, 0)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(!cpp.contains("local_0 = local_0;"), "{cpp}");
+    assert!(cpp.contains("global_5 = global_5;"), "{cpp}");
+  }
+
+  #[test]
+  fn bit_tests_shift_the_value_by_the_bit() {
+    // The value is pushed before the bit
+    let code = Asm::default()
+      .enter(1, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(0)
+      .op(Opcode::PushConst3)
+      .op(Opcode::BitTest)
+      .leave(1, 1)
+      .build();
+
+    let cpp = decompile_cpp(&code, Default::default());
+    assert!(cpp.contains("return ((parameter_0 >> 3) & 1);"), "{cpp}");
+  }
+}
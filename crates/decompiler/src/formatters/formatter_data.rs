@@ -0,0 +1,109 @@
+use crate::{
+  decompiler::{DecompilerData, FunctionMap, RelatedScripts, ScriptGlobals, ScriptStatics},
+  resources::{CrossMap, HashNames, NativeInfo, Natives}
+};
+
+/// The resources used by formatters. Unlike [`DecompilerData`] only natives and functions
+/// are required.
+#[derive(Clone, Copy)]
+pub struct FormatterData<'d, 'i, 'b> {
+  pub natives:    &'d Natives,
+  pub functions:  &'d FunctionMap<'d, 'i, 'b>,
+  pub statics:    Option<&'d ScriptStatics>,
+  pub globals:    Option<&'d ScriptGlobals>,
+  pub cross_map:  Option<&'d CrossMap>,
+  pub hash_names: Option<&'d HashNames>,
+  /// Functions of related scripts, see [`crate::decompiler::MultiScript`]
+  pub related:    Option<RelatedScripts<'d>>
+}
+
+impl<'d, 'i, 'b> FormatterData<'d, 'i, 'b> {
+  pub fn new(natives: &'d Natives, functions: &'d FunctionMap<'d, 'i, 'b>) -> Self {
+    Self {
+      natives,
+      functions,
+      statics: None,
+      globals: None,
+      cross_map: None,
+      hash_names: None,
+      related: None
+    }
+  }
+
+  pub fn get_native(&self, hash: u64) -> Option<&'d NativeInfo> {
+    match self.cross_map {
+      Some(cross_map) => self.natives.get_native_resolved(hash, cross_map),
+      None => self.natives.get_native(hash)
+    }
+  }
+}
+
+impl<'d, 'i, 'b> From<DecompilerData<'d, 'i, 'b>> for FormatterData<'d, 'i, 'b> {
+  fn from(data: DecompilerData<'d, 'i, 'b>) -> Self {
+    Self {
+      natives:    data.natives,
+      functions:  data.functions,
+      statics:    Some(data.statics),
+      globals:    Some(data.globals),
+      cross_map:  Some(data.cross_map),
+      hash_names: data.hash_names,
+      related:    None
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{function_map, get_functions, DecompilerDataBuilder},
+    disassembler::{disassemble, opcodes::Opcode},
+    formatters::CppFormatter,
+    script::ScriptTables,
+    test_utils::Asm
+  };
+
+  #[test]
+  fn formats_with_only_natives_and_functions() {
+    let code = Asm::default()
+      .enter(0, 2)
+      .op(Opcode::FunctionCall)
+      .u24(16)
+      .op(Opcode::NativeCall)
+      .u8(0)
+      .u8(0)
+      .u8(0)
+      .leave(0, 0)
+      // 16
+      .enter(0, 2)
+      .leave(0, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let natives_json = r#"{ "natives": { "0x4EDE34FBADD967A6": { "name": "WAIT" } } }"#;
+    let builder = DecompilerDataBuilder::new(
+      Natives::from_json(natives_json).unwrap(),
+      CrossMap::identity()
+    )
+    .functions(&functions);
+    let decompiled = functions[0]
+      .decompile(
+        ScriptTables {
+          strings: &[],
+          natives: &[0x4EDE34FBADD967A6]
+        },
+        &builder.build()
+      )
+      .unwrap();
+
+    let natives = Natives::from_json(natives_json).unwrap();
+    let functions = function_map(&functions);
+    let formatter = CppFormatter::new(FormatterData::new(&natives, &functions));
+
+    let cpp = formatter.format_function(&decompiled);
+    assert!(cpp.contains("func_1();"), "{cpp}");
+    assert!(cpp.contains("WAIT();"), "{cpp}");
+    assert_eq!(formatter.format_statics(), "");
+    assert_eq!(formatter.format_globals(), "");
+  }
+}
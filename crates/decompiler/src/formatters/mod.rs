@@ -1,6 +1,8 @@
 mod assembly_formatter;
 mod code_builder;
 mod cpp_formatter;
+mod formatter_data;
 
 pub use assembly_formatter::*;
 pub use cpp_formatter::*;
+pub use formatter_data::*;
@@ -1,3 +1,4 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![feature(
   assert_matches,
   if_let_guard,
@@ -9,9 +10,18 @@
   let_chains
 )]
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
 mod common;
+#[cfg(feature = "std")]
 pub mod decompiler;
 pub mod disassembler;
+#[cfg(feature = "std")]
 pub mod formatters;
+#[cfg(feature = "std")]
 pub mod resources;
+#[cfg(feature = "std")]
 pub mod script;
+#[cfg(all(test, feature = "std"))]
+mod test_utils;
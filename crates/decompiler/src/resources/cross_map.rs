@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap, fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
 use itertools::Itertools;
 use serde::Deserialize;
@@ -10,16 +10,17 @@ use super::FromJsonFileError;
 struct Json<'i>(Vec<Vec<&'i str>>);
 
 pub struct CrossMap {
-  hashes:         Vec<Vec<u64>>,
-  original_cache: RefCell<HashMap<u64, u64>>
+  hashes: Vec<Vec<u64>>,
+  /// The index of the first history containing each hash
+  index:  HashMap<u64, usize>
 }
 
 impl CrossMap {
   pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
     let json = serde_json::from_str::<Json>(json)?;
 
-    Ok(Self {
-      hashes:         json
+    Ok(Self::new(
+      json
         .0
         .into_iter()
         .map(|history| {
@@ -28,9 +29,13 @@ impl CrossMap {
             .map(|hash| u64::from_str_radix(hash.trim_start_matches("0x"), 16).unwrap())
             .collect_vec()
         })
-        .collect_vec(),
-      original_cache: Default::default()
-    })
+        .collect_vec()
+    ))
+  }
+
+  /// A cross map that returns all hashes unchanged, for scripts that use the original hashes.
+  pub fn identity() -> Self {
+    Self::new(Default::default())
   }
 
   pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, FromJsonFileError> {
@@ -39,22 +44,76 @@ impl CrossMap {
     Ok(Self::from_json(&contents)?)
   }
 
+  fn new(hashes: Vec<Vec<u64>>) -> Self {
+    let mut result = Self {
+      hashes,
+      index: Default::default()
+    };
+    result.build_index();
+    result
+  }
+
+  fn build_index(&mut self) {
+    self.index.clear();
+    for (index, history) in self.hashes.iter().enumerate() {
+      for hash in history {
+        self.index.entry(*hash).or_insert(index);
+      }
+    }
+  }
+
+  /// Adds the hash histories of `other`, which take precedence over the existing ones.
+  pub fn merge(&mut self, other: CrossMap) {
+    self.hashes.splice(0..0, other.hashes);
+    self.build_index();
+  }
+
+  fn history(&self, hash: u64) -> Option<&[u64]> {
+    self.index.get(&hash).map(|index| &self.hashes[*index][..])
+  }
+
+  /// All hashes `hash` had across game versions, including `hash` itself.
+  pub fn related_hashes(&self, hash: u64) -> Vec<u64> {
+    self
+      .history(hash)
+      .map(|history| history.iter().copied().filter(|h| *h != 0).collect())
+      .unwrap_or_else(|| vec![hash])
+  }
+
   pub fn get_original_hash(&self, current: u64) -> u64 {
-    *self
-      .original_cache
-      .borrow_mut()
-      .entry(current)
-      .or_insert_with(|| {
-        let history = self
-          .hashes
-          .iter()
-          .find(|history| history.contains(&current));
-
-        if let Some(history) = history {
-          *history.iter().find(|h| **h != 0).unwrap_or(&current)
-        } else {
-          current
-        }
-      })
+    self
+      .history(current)
+      .and_then(|history| history.iter().find(|h| **h != 0))
+      .copied()
+      .unwrap_or(current)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn identity_keeps_hashes() {
+    let cross_map = CrossMap::identity();
+
+    for hash in [0, 0x4EDE34FBADD967A6, u64::MAX] {
+      assert_eq!(cross_map.get_original_hash(hash), hash);
+      assert_eq!(cross_map.related_hashes(hash), vec![hash]);
+    }
+  }
+
+  #[test]
+  fn histories_are_looked_up_by_any_of_their_hashes() {
+    let mut cross_map = CrossMap::from_json(r#"[["0x0", "0xA", "0xB"], ["0xC", "0xD"]]"#).unwrap();
+
+    assert_eq!(cross_map.get_original_hash(0xB), 0xA);
+    assert_eq!(cross_map.get_original_hash(0xD), 0xC);
+    assert_eq!(cross_map.related_hashes(0xA), vec![0xA, 0xB]);
+    assert_eq!(cross_map.related_hashes(0xE), vec![0xE]);
+
+    cross_map.merge(CrossMap::from_json(r#"[["0xE", "0xD"]]"#).unwrap());
+    assert_eq!(cross_map.get_original_hash(0xD), 0xE);
+    assert_eq!(cross_map.get_original_hash(0xB), 0xA);
   }
 }
@@ -0,0 +1,33 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::common::joaat;
+
+/// Reverse lookup table from JOAAT hashes to the strings they were created from.
+#[derive(Default)]
+pub struct HashNames {
+  names: HashMap<u32, String>
+}
+
+impl HashNames {
+  /// Parses a list of strings, one per line.
+  pub fn from_text(text: &str) -> Self {
+    Self {
+      names: text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| (joaat(line), line.to_owned()))
+        .collect()
+    }
+  }
+
+  pub fn from_text_file(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+    let contents = fs::read_to_string(path)?;
+
+    Ok(Self::from_text(&contents))
+  }
+
+  pub fn get(&self, hash: u32) -> Option<&str> {
+    self.names.get(&hash).map(String::as_str)
+  }
+}
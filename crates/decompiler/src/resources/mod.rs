@@ -1,7 +1,11 @@
 mod cross_map;
 mod from_json_file_error;
+mod hash_names;
 mod natives;
+mod signatures;
 
 pub use cross_map::*;
 pub use from_json_file_error::*;
+pub use hash_names::*;
 pub use natives::*;
+pub use signatures::*;
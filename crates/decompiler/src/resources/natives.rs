@@ -1,30 +1,131 @@
 use std::{collections::HashMap, fs, path::Path};
 
-use nativedocgen_model::{DocumentRoot, Native};
+use serde::Deserialize;
 
-use super::FromJsonFileError;
+use super::{CrossMap, FromJsonFileError};
+
+#[derive(Debug, Clone)]
+pub struct NativeInfo {
+  pub hash:        u64,
+  pub namespace:   Option<String>,
+  pub name:        String,
+  pub params:      Vec<NativeParam>,
+  pub return_type: Option<String>
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NativeParam {
+  #[serde(rename = "type")]
+  pub ty:   String,
+  pub name: String
+}
+
+/// The native documentation generator's format, `{ "natives": { "0xHASH": { ... } } }`.
+#[derive(Deserialize)]
+struct DocumentRoot {
+  natives: HashMap<String, DocumentNative>
+}
+
+#[derive(Deserialize)]
+struct DocumentNative {
+  name:        String,
+  #[serde(default)]
+  params:      Vec<NativeParam>,
+  return_type: Option<String>
+}
+
+/// The namespaced natives.json format, `{ "NAMESPACE": { "0xHASH": { ... } } }`.
+#[derive(Deserialize)]
+struct NamespacedNative {
+  name:        String,
+  /// The hashes the native had in older builds
+  #[serde(default, alias = "jhash")]
+  old_hashes:  OldHashes,
+  #[serde(default)]
+  params:      Vec<NativeParam>,
+  return_type: Option<String>
+}
+
+/// natives.json has a single `jhash`, other dumps list every hash a native had.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OldHashes {
+  One(String),
+  Many(Vec<String>)
+}
+
+impl Default for OldHashes {
+  fn default() -> Self {
+    Self::Many(Vec::new())
+  }
+}
+
+impl OldHashes {
+  fn parse(&self) -> Vec<u64> {
+    match self {
+      Self::One(hash) => parse_hash(hash).into_iter().collect(),
+      Self::Many(hashes) => hashes.iter().filter_map(|hash| parse_hash(hash)).collect()
+    }
+  }
+}
 
 pub struct Natives {
-  _document: DocumentRoot,
-  natives:   HashMap<u64, Native>
+  natives: Vec<NativeInfo>,
+  hashes:  HashMap<u64, usize>,
+  names:   HashMap<String, usize>
 }
 
 impl Natives {
   pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-    let document = serde_json::from_str::<DocumentRoot>(json)?;
-
-    Ok(Self {
-      natives:   document
-        .natives
-        .iter()
-        .filter_map(|(key, value)| {
-          u64::from_str_radix(key.trim_start_matches("0x"), 16)
-            .map(|hash| (hash, (*value).clone()))
-            .ok()
-        })
-        .collect(),
-      _document: document
-    })
+    let mut result = Self {
+      natives: Default::default(),
+      hashes:  Default::default(),
+      names:   Default::default()
+    };
+
+    // Told apart up front so errors point at the actual problem instead of reporting that
+    // neither format matched
+    let value = serde_json::from_str::<serde_json::Value>(json)?;
+    if value.get("natives").is_some() {
+      let document = serde_json::from_value::<DocumentRoot>(value)?;
+      for (key, native) in document.natives {
+        if let Some(hash) = parse_hash(&key) {
+          result.insert(
+            NativeInfo {
+              hash,
+              namespace: None,
+              name: native.name,
+              params: native.params,
+              return_type: native.return_type
+            },
+            &[]
+          );
+        }
+      }
+    } else {
+      let namespaces =
+        serde_json::from_value::<HashMap<String, HashMap<String, NamespacedNative>>>(value)?;
+      for (namespace, natives) in namespaces {
+        for (key, native) in natives {
+          let Some(hash) = parse_hash(&key) else {
+            continue;
+          };
+
+          result.insert(
+            NativeInfo {
+              hash,
+              namespace: Some(namespace.clone()),
+              name: native.name,
+              params: native.params,
+              return_type: native.return_type
+            },
+            &native.old_hashes.parse()
+          );
+        }
+      }
+    }
+
+    Ok(result)
   }
 
   pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, FromJsonFileError> {
@@ -33,7 +134,224 @@ impl Natives {
     Ok(Self::from_json(&contents)?)
   }
 
-  pub fn get_native(&self, hash: u64) -> Option<&Native> {
-    self.natives.get(&hash)
+  pub fn get_native(&self, hash: u64) -> Option<&NativeInfo> {
+    self.hashes.get(&hash).map(|index| &self.natives[*index])
+  }
+
+  /// Looks up `hash`, falling back to the other hashes `cross_map` knows for the native.
+  pub fn get_native_resolved(&self, hash: u64, cross_map: &CrossMap) -> Option<&NativeInfo> {
+    self
+      .get_native(hash)
+      .or_else(|| self.get_native(cross_map.get_original_hash(hash)))
+      .or_else(|| {
+        cross_map
+          .related_hashes(hash)
+          .into_iter()
+          .find_map(|related| self.get_native(related))
+      })
+  }
+
+  pub fn get_by_name(&self, name: &str) -> Option<&NativeInfo> {
+    self.names.get(name).map(|index| &self.natives[*index])
+  }
+
+  /// Adds the natives of `other`, which take precedence on hash or name collisions.
+  pub fn merge(&mut self, other: Natives) {
+    let offset = self.natives.len();
+
+    for (hash, index) in other.hashes {
+      // A renamed native can't be found by its old name anymore
+      if let Some(old) = self.hashes.insert(hash, offset + index) {
+        let name = &self.natives[old].name;
+        if self.names.get(name) == Some(&old) {
+          self.names.remove(name);
+        }
+      }
+    }
+    for (name, index) in other.names {
+      self.names.insert(name, offset + index);
+    }
+
+    self.natives.extend(other.natives);
+  }
+
+  fn insert(&mut self, native: NativeInfo, old_hashes: &[u64]) {
+    let index = self.natives.len();
+
+    self.hashes.insert(native.hash, index);
+    for hash in old_hashes {
+      self.hashes.entry(*hash).or_insert(index);
+    }
+    self.names.insert(native.name.clone(), index);
+
+    self.natives.push(native);
+  }
+}
+
+fn parse_hash(hash: &str) -> Option<u64> {
+  u64::from_str_radix(hash.trim_start_matches("0x"), 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_namespaced_natives_with_old_hashes() {
+    let natives = Natives::from_json(
+      r#"{
+        "PLAYER": {
+          "0xD80958FC74E988A6": {
+            "name": "PLAYER_PED_ID",
+            "jhash": "0xFA92E226",
+            "params": [],
+            "return_type": "Ped"
+          },
+          "0x4F8644AF03D0E0D6": {
+            "name": "PLAYER_ID",
+            "params": [{ "type": "int", "name": "unused" }],
+            "return_type": "Player"
+          }
+        }
+      }"#
+    )
+    .unwrap();
+
+    let native = natives.get_native(0xD80958FC74E988A6).unwrap();
+    assert_eq!(native.name, "PLAYER_PED_ID");
+    assert_eq!(native.namespace.as_deref(), Some("PLAYER"));
+    assert_eq!(native.return_type.as_deref(), Some("Ped"));
+    assert_eq!(
+      natives.get_native(0xFA92E226).unwrap().name,
+      "PLAYER_PED_ID"
+    );
+    assert_eq!(
+      natives.get_by_name("PLAYER_ID").unwrap().hash,
+      0x4F8644AF03D0E0D6
+    );
+    assert_eq!(
+      natives.get_by_name("PLAYER_ID").unwrap().params[0].name,
+      "unused"
+    );
+  }
+
+  #[test]
+  fn parses_the_natives_json_dump() {
+    let natives = Natives::from_json(include_str!("../../tests/fixtures/natives.json")).unwrap();
+
+    let wait = natives.get_native(0x4EDE34FBADD967A6).unwrap();
+    assert_eq!(wait.namespace.as_deref(), Some("SYSTEM"));
+    assert_eq!(wait.params[0].name, "ms");
+    assert_eq!(natives.get_native(0x7715C03B).unwrap().name, "WAIT");
+
+    let start_new_script = natives.get_by_name("START_NEW_SCRIPT").unwrap();
+    assert_eq!(start_new_script.params[0].ty, "const char*");
+    assert_eq!(start_new_script.return_type.as_deref(), Some("int"));
+
+    assert_eq!(natives.get_native(0x8AEDFAB2).unwrap().name, "PLAYER_ID");
+  }
+
+  #[test]
+  fn parses_lists_of_old_hashes() {
+    let natives = Natives::from_json(
+      r#"{
+        "SYSTEM": {
+          "0x4EDE34FBADD967A6": {
+            "name": "WAIT",
+            "old_hashes": ["0x7715C03B", "0x1234"]
+          }
+        }
+      }"#
+    )
+    .unwrap();
+
+    assert_eq!(natives.get_native(0x7715C03B).unwrap().name, "WAIT");
+    assert_eq!(natives.get_native(0x1234).unwrap().name, "WAIT");
+  }
+
+  #[test]
+  fn parse_errors_point_at_the_invalid_native() {
+    let error = Natives::from_json(r#"{ "natives": { "0x4EDE34FBADD967A6": { "params": [] } } }"#)
+      .err()
+      .unwrap();
+    assert!(
+      error.to_string().contains("missing field `name`"),
+      "{error}"
+    );
+
+    let error = Natives::from_json(r#"{ "SYSTEM": { "0x4EDE34FBADD967A6": { "name": 1 } } }"#)
+      .err()
+      .unwrap();
+    assert!(error.to_string().contains("invalid type"), "{error}");
+  }
+
+  #[test]
+  fn parses_document_params() {
+    let natives = Natives::from_json(
+      r#"{
+        "natives": {
+          "0x4F8644AF03D0E0D6": {
+            "name": "WAIT",
+            "params": [{ "type": "int", "name": "ms" }],
+            "return_type": "void"
+          }
+        }
+      }"#
+    )
+    .unwrap();
+
+    let native = natives.get_native(0x4F8644AF03D0E0D6).unwrap();
+    assert_eq!(native.name, "WAIT");
+    assert_eq!(native.params.len(), 1);
+    assert_eq!(native.params[0].ty, "int");
+    assert_eq!(native.params[0].name, "ms");
+    assert_eq!(native.return_type.as_deref(), Some("void"));
+  }
+
+  #[test]
+  fn resolved_lookups_fall_back_to_translated_hashes() {
+    let natives =
+      Natives::from_json(r#"{ "natives": { "0x4EDE34FBADD967A6": { "name": "WAIT" } } }"#).unwrap();
+    let cross_map = CrossMap::from_json(r#"[["0x4EDE34FBADD967A6", "0x1234"]]"#).unwrap();
+
+    assert!(natives.get_native(0x1234).is_none());
+    assert_eq!(
+      natives
+        .get_native_resolved(0x1234, &cross_map)
+        .unwrap()
+        .name,
+      "WAIT"
+    );
+    assert!(natives.get_native_resolved(0x5678, &cross_map).is_none());
+  }
+
+  #[test]
+  fn merged_natives_override_earlier_names() {
+    let mut natives = Natives::from_json(
+      r#"{ "natives": {
+        "0x4EDE34FBADD967A6": { "name": "WAIT" },
+        "0xD80958FC74E988A6": { "name": "_0xD80958FC74E988A6" }
+      } }"#
+    )
+    .unwrap();
+    natives.merge(
+      Natives::from_json(r#"{ "natives": { "0xD80958FC74E988A6": { "name": "PLAYER_PED_ID" } } }"#)
+        .unwrap()
+    );
+
+    assert_eq!(natives.get_native(0x4EDE34FBADD967A6).unwrap().name, "WAIT");
+    assert_eq!(
+      natives.get_native(0xD80958FC74E988A6).unwrap().name,
+      "PLAYER_PED_ID"
+    );
+    assert_eq!(
+      natives.get_by_name("PLAYER_PED_ID").unwrap().hash,
+      0xD80958FC74E988A6
+    );
+    assert!(natives.get_by_name("_0xD80958FC74E988A6").is_none());
+    assert_eq!(
+      natives.get_by_name("WAIT").unwrap().hash,
+      0x4EDE34FBADD967A6
+    );
   }
 }
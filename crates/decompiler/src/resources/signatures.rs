@@ -0,0 +1,156 @@
+use std::{cell::RefCell, collections::HashMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::decompiler::{
+  Confidence, Function, LinkedValueType, Primitives, ValueType, ValueTypeInfo
+};
+
+use super::FromJsonFileError;
+
+/// Known types of a function, `null` or `"any"` leaves a type to inference.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Signature {
+  #[serde(default)]
+  pub params:  Vec<Option<String>>,
+  pub returns: Option<String>
+}
+
+/// Function signatures by function name, `{ "func_12": { "params": ["float"], "returns": "bool" } }`.
+/// Names qualified as `script::func_12` only apply to that script and take precedence.
+#[derive(Default)]
+pub struct SignatureMap {
+  signatures: HashMap<String, Signature>
+}
+
+impl SignatureMap {
+  pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+    Ok(Self {
+      signatures: serde_json::from_str(json)?
+    })
+  }
+
+  pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, FromJsonFileError> {
+    let contents = fs::read_to_string(path)?;
+
+    Ok(Self::from_json(&contents)?)
+  }
+
+  pub fn get(&self, script: &str, function: &str) -> Option<&Signature> {
+    self
+      .signatures
+      .get(&format!("{script}::{function}"))
+      .or_else(|| self.signatures.get(function))
+  }
+
+  /// Pins the parameter and return types of `function` to its signature, so inference
+  /// propagates them to callers and callees. Has to be called before decompiling.
+  pub fn apply(&self, script: &str, function: &Function) {
+    let Some(signature) = self.get(script, &function.name) else {
+      return;
+    };
+
+    for (param, ty) in function.parameters.iter().zip(&signature.params) {
+      if let Some(primitive) = ty.as_deref().and_then(parse_primitive) {
+        pin(param, primitive);
+      }
+    }
+
+    // Multiple return values are a struct, only single values can be pinned
+    if let (Some(returns), 1) = (&function.returns, function.return_count) {
+      if let Some(primitive) = signature.returns.as_deref().and_then(parse_primitive) {
+        pin(returns, primitive);
+      }
+    }
+  }
+}
+
+/// Hints instead of replacing the type, so types already linked to it are typed as well.
+fn pin(ty: &RefCell<LinkedValueType>, primitive: Primitives) {
+  ty.borrow_mut().hint(ValueTypeInfo {
+    ty:         ValueType::Primitive(primitive),
+    confidence: Confidence::High
+  });
+}
+
+fn parse_primitive(ty: &str) -> Option<Primitives> {
+  match ty {
+    "int" => Some(Primitives::Int),
+    "float" => Some(Primitives::Float),
+    "bool" => Some(Primitives::Bool),
+    "string" | "const char*" => Some(Primitives::String),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    decompiler::{decompile_all, get_functions, DecompilerDataBuilder},
+    disassembler::{disassemble, opcodes::Opcode},
+    formatters::CppFormatter,
+    resources::{CrossMap, Natives},
+    test_utils::{Asm, NO_TABLES}
+  };
+
+  #[test]
+  fn pinned_parameters_type_the_arguments_of_callers() {
+    let code = Asm::default()
+      .enter(0, 3)
+      .op(Opcode::LocalU8Load)
+      .u8(2)
+      .op(Opcode::FunctionCall)
+      .u24(14)
+      .leave(0, 0)
+      // 14
+      .enter(1, 3)
+      .leave(1, 0)
+      .build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+
+    let signatures =
+      SignatureMap::from_json(r#"{ "test::func_1": { "params": ["float"] } }"#).unwrap();
+    for function in &functions {
+      signatures.apply("test", function);
+    }
+
+    let builder =
+      DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+        .functions(&functions);
+    let data = builder.build();
+    let report = decompile_all(&functions, NO_TABLES, &data);
+    let formatter = CppFormatter::new(data);
+    let succeeded = report.succeeded().collect::<Vec<_>>();
+
+    let caller = formatter.format_function(succeeded[0]);
+    assert!(caller.contains("float local_0 /* 2 */;"), "{caller}");
+    let callee = formatter.format_function(succeeded[1]);
+    assert!(
+      callee.contains("void func_1(float parameter_0 /* 0 */)"),
+      "{callee}"
+    );
+  }
+
+  #[test]
+  fn pinning_keeps_links() {
+    let code = Asm::default().enter(1, 3).leave(1, 0).build();
+    let instructions = disassemble(&code).unwrap();
+    let functions = get_functions(&instructions);
+    let linked = LinkedValueType::new_primitive(Primitives::Unknown).make_shared();
+    LinkedValueType::link(&functions[0].parameters[0], &linked);
+
+    SignatureMap::from_json(r#"{ "func_0": { "params": ["float"] } }"#)
+      .unwrap()
+      .apply("test", &functions[0]);
+
+    assert!(matches!(
+      linked.borrow().get_concrete(),
+      ValueTypeInfo {
+        ty:         ValueType::Primitive(Primitives::Float),
+        confidence: Confidence::High
+      }
+    ));
+  }
+}
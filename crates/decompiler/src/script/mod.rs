@@ -1,16 +1,31 @@
 mod ysc;
 
-use std::ffi::CStr;
+use std::{collections::HashMap, ffi::CStr};
+
+use serde::Serialize;
 
 pub use ysc::*;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ScriptInfo {
+  /// Identifies the game build the script was compiled for
+  pub magic:           u32,
   pub name:            String,
   pub name_hash:       u32,
   pub globals_version: u32,
   pub parameter_count: u32,
-  pub static_count:    u32
+  pub static_count:    u32,
+  pub global_count:    u32
+}
+
+/// The header of a script together with the sizes of its tables.
+#[derive(Debug, Serialize)]
+pub struct ScriptSummary<'a> {
+  #[serde(flatten)]
+  pub header:       &'a ScriptInfo,
+  pub code_size:    usize,
+  pub native_count: usize,
+  pub string_count: usize
 }
 
 #[derive(Debug)]
@@ -18,13 +33,146 @@ pub struct Script {
   pub header:  ScriptInfo,
   pub code:    Vec<u8>,
   pub strings: Vec<u8>,
-  pub natives: Vec<u64>
+  pub natives: Vec<u64>,
+  /// Initial values of the statics, one 8 byte slot per static
+  pub statics: Vec<u64>
 }
 
-impl Script {
-  pub fn get_string(&self, index: usize) -> Option<&str> {
-    CStr::from_bytes_until_nul(&self.strings[index..])
+/// The tables of a script that code refers to by index.
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptTables<'a> {
+  pub strings: &'a [u8],
+  pub natives: &'a [u64]
+}
+
+impl<'a> ScriptTables<'a> {
+  pub fn get_string(&self, index: usize) -> Option<&'a str> {
+    CStr::from_bytes_until_nul(self.strings.get(index..)?)
       .ok()
       .and_then(|cstr| cstr.to_str().ok())
   }
+
+  pub fn get_native(&self, index: usize) -> Option<u64> {
+    self.natives.get(index).copied()
+  }
+}
+
+impl<'a> From<&'a Script> for ScriptTables<'a> {
+  fn from(script: &'a Script) -> Self {
+    script.tables()
+  }
+}
+
+impl Script {
+  /// See [`parse_ysc_lenient`].
+  pub fn from_bytes_lenient(bytes: &[u8]) -> (Option<Self>, Vec<ParseWarning>) {
+    parse_ysc_lenient(bytes)
+  }
+
+  pub fn tables(&self) -> ScriptTables<'_> {
+    ScriptTables {
+      strings: &self.strings,
+      natives: &self.natives
+    }
+  }
+
+  pub fn get_string(&self, index: usize) -> Option<&str> {
+    self.tables().get_string(index)
+  }
+
+  /// Iterates over the `(offset, string)` pairs in the string table.
+  /// Empty entries (block padding) and strings that aren't valid UTF-8 are skipped.
+  pub fn strings_iter(&self) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    self
+      .strings
+      .split(|byte| *byte == 0)
+      .filter_map(move |bytes| {
+        let start = offset;
+        offset += bytes.len() + 1;
+
+        if bytes.is_empty() {
+          return None;
+        }
+
+        std::str::from_utf8(bytes)
+          .ok()
+          .map(|string| (start, string))
+      })
+  }
+
+  pub fn string_count(&self) -> usize {
+    self.strings_iter().count()
+  }
+
+  pub fn summary(&self) -> ScriptSummary<'_> {
+    ScriptSummary {
+      header:       &self.header,
+      code_size:    self.code.len(),
+      native_count: self.natives.len(),
+      string_count: self.string_count()
+    }
+  }
+
+  /// Maps string table offsets to the index of the string in [`Script::strings_iter`].
+  pub fn string_index_map(&self) -> HashMap<usize, usize> {
+    self
+      .strings_iter()
+      .enumerate()
+      .map(|(index, (offset, _))| (offset, index))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn script_with_strings(strings: &[u8]) -> Script {
+    Script {
+      header:  ScriptInfo {
+        magic:           0xB3A8,
+        name:            "test".to_owned(),
+        name_hash:       0,
+        globals_version: 0,
+        parameter_count: 0,
+        static_count:    0,
+        global_count:    0
+      },
+      code:    vec![],
+      strings: strings.to_vec(),
+      natives: vec![],
+      statics: vec![]
+    }
+  }
+
+  #[test]
+  fn strings_iter_yields_offsets_and_skips_padding_and_invalid_utf8() {
+    let script = script_with_strings(b"first\0\0second\0\xFF\xFE\0third\0\0\0");
+
+    assert_eq!(
+      script.strings_iter().collect::<Vec<_>>(),
+      vec![(0, "first"), (7, "second"), (17, "third")]
+    );
+    assert_eq!(script.string_count(), 3);
+    assert_eq!(
+      script.string_index_map(),
+      HashMap::from([(0, 0), (7, 1), (17, 2)])
+    );
+    assert_eq!(script.get_string(7), Some("second"));
+  }
+
+  #[test]
+  fn summaries_serialize_the_header_and_table_sizes() {
+    let mut script = script_with_strings(b"first\0second\0");
+    script.header.static_count = 3;
+    script.code = vec![0; 8];
+
+    let json = serde_json::to_value(script.summary()).unwrap();
+    assert_eq!(json["name"], "test");
+    assert_eq!(json["static_count"], 3);
+    assert_eq!(json["code_size"], 8);
+    assert_eq!(json["native_count"], 0);
+    assert_eq!(json["string_count"], 2);
+  }
 }
@@ -1,7 +1,7 @@
 use binary_layout::define_layout;
 use binary_reader::{BinaryReader, Endian};
 
-use crate::script::{OpcodeVersion, YscHeaderParser, YscScriptHeader};
+use crate::script::{read_bytes, OpcodeVersion, Rsc7Header, YscHeaderParser, YscScriptHeader};
 
 use super::read_pointer::ReadPointer;
 
@@ -63,12 +63,21 @@ impl YscHeaderParser for PcYscHeaderParser {
 
     let offset = rsc7_offset.unwrap_or_default();
 
-    reader.jmp(offset as usize);
+    let rsc7 = rsc7_offset
+      .map(|_| {
+        anyhow::Ok(Rsc7Header {
+          version:        reader.read_u32()?,
+          virtual_flags:  reader.read_u32()?,
+          physical_flags: reader.read_u32()?
+        })
+      })
+      .transpose()?;
 
-    let pc_header = pc_header::View::new(reader.read_bytes(pc_header::SIZE.unwrap())?.to_vec());
+    let pc_header =
+      pc_header::View::new(read_bytes(bytes, offset as usize, pc_header::SIZE.unwrap())?.to_vec());
 
     reader.jmp((offset + pc_header.strings_offset().read_as_pointer()) as usize);
-    let string_blocks = (pc_header.strings_size().read() + 0x3FFF) >> 14;
+    let string_blocks = pc_header.strings_size().read().div_ceil(0x4000);
     let string_table_offsets = (0..string_blocks)
       .map(|_| {
         let res = reader.read_u32().map(|v| (v & 0xFFFFFF) + offset);
@@ -78,7 +87,7 @@ impl YscHeaderParser for PcYscHeaderParser {
       .collect::<Result<_, _>>()?;
 
     reader.jmp((offset + pc_header.code_blocks_offset().read_as_pointer()) as usize);
-    let code_blocks = (pc_header.code_size().read() + 0x3FFF) >> 14;
+    let code_blocks = pc_header.code_size().read().div_ceil(0x4000);
     let code_table_offsets = (0..code_blocks)
       .map(|_| {
         let res = reader.read_u32().map(|v| (v & 0xFFFFFF) + offset);
@@ -105,6 +114,7 @@ impl YscHeaderParser for PcYscHeaderParser {
       string_offset: pc_header.strings_offset().read_as_pointer(),
       strings_size: pc_header.strings_size().read(),
       rsc7_offset,
+      rsc7,
       string_table_offsets,
       code_table_offsets,
       string_blocks,
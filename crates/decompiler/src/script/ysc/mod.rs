@@ -4,8 +4,10 @@ mod ysc_header;
 mod ysc_header_parser;
 mod ysc_header_parser_factory;
 mod ysc_parser;
+mod ysc_writer;
 
 pub use ysc_header::*;
 pub use ysc_header_parser::*;
 pub use ysc_header_parser_factory::*;
 pub use ysc_parser::*;
+pub use ysc_writer::*;
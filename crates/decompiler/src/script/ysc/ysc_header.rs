@@ -17,9 +17,57 @@ pub struct YscScriptHeader {
   pub string_offset:        u32,
   pub strings_size:         u32,
   pub rsc7_offset:          Option<u32>,
+  pub rsc7:                 Option<Rsc7Header>,
   pub string_table_offsets: Vec<u32>,
   pub code_table_offsets:   Vec<u32>,
   pub string_blocks:        u32,
   pub code_blocks:          u32,
   pub script_name:          String
 }
+
+/// The header of the `RSC7` resource container scripts extracted from archives are wrapped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsc7Header {
+  pub version:        u32,
+  /// Page layout of the virtual (system) segment, which holds the script
+  pub virtual_flags:  u32,
+  /// Page layout of the physical (graphics) segment
+  pub physical_flags: u32
+}
+
+impl Rsc7Header {
+  pub fn virtual_size(&self) -> u32 {
+    resource_size(self.virtual_flags)
+  }
+
+  pub fn physical_size(&self) -> u32 {
+    resource_size(self.physical_flags)
+  }
+}
+
+impl YscScriptHeader {
+  /// Whether the code and strings fit into the virtual segment of the resource. Always true
+  /// for scripts that aren't wrapped in a resource container.
+  pub fn fits_rsc7(&self) -> bool {
+    match self.rsc7 {
+      Some(rsc7) => self.code_size as u64 + self.strings_size as u64 <= rsc7.virtual_size() as u64,
+      None => true
+    }
+  }
+}
+
+/// The total size of the pages described by resource page `flags`.
+fn resource_size(flags: u32) -> u32 {
+  let page_counts = ((flags >> 27) & 0x1)
+    + (((flags >> 26) & 0x1) << 1)
+    + (((flags >> 25) & 0x1) << 2)
+    + (((flags >> 24) & 0x1) << 3)
+    + (((flags >> 17) & 0x7F) << 4)
+    + (((flags >> 11) & 0x3F) << 5)
+    + (((flags >> 7) & 0xF) << 6)
+    + (((flags >> 5) & 0x3) << 7)
+    + (((flags >> 4) & 0x1) << 8);
+  let base_size = 0x200u32 << (flags & 0xF);
+
+  base_size.saturating_mul(page_counts)
+}
@@ -1,12 +1,24 @@
 use super::YscScriptHeader;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OpcodeVersion {
   B2628,
   B2699,
   B2802
 }
 
+impl OpcodeVersion {
+  /// The version of PC scripts starting with `magic`, only its low 16 bits are checked.
+  pub fn from_magic(magic: u32) -> Option<Self> {
+    match magic & 0xFFFF {
+      0xB0B8 => Some(Self::B2628), // GTA V b2628
+      0x2699 => Some(Self::B2699), // GTA V b2699
+      0xB3A8 => Some(Self::B2802), // GTA V b2802
+      _ => None
+    }
+  }
+}
+
 pub trait YscHeaderParser {
   fn opcode_version(&self) -> OpcodeVersion;
 
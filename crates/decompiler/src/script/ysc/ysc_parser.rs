@@ -4,13 +4,46 @@ use binary_reader::BinaryReader;
 use thiserror::Error;
 
 use crate::{
-  disassembler::opcodes::Opcode,
-  script::{Script, ScriptInfo}
+  disassembler::{remap_opcodes, DisassembleError},
+  script::{OpcodeVersion, Script, ScriptInfo}
 };
 
-use super::{OpcodeVersion, UnknownMagicError, YscHeaderParserFactory};
+use super::{UnknownMagicError, YscHeaderParserFactory};
 
 pub fn parse_ysc(bytes: &[u8]) -> Result<Script, ParseYscError> {
+  parse_ysc_with_version(bytes, None)
+}
+
+/// Parses a ysc file, remapping its opcodes for `version` instead of the version derived
+/// from the header if one is given.
+pub fn parse_ysc_with_version(
+  bytes: &[u8],
+  version: Option<OpcodeVersion>
+) -> Result<Script, ParseYscError> {
+  parse(bytes, version, None)
+}
+
+/// Parses a ysc file, truncating tables that exceed the input instead of failing. The
+/// anomalies that were worked around are returned as warnings, as is the error if the
+/// script could not be parsed at all.
+pub fn parse_ysc_lenient(bytes: &[u8]) -> (Option<Script>, Vec<ParseWarning>) {
+  let mut warnings = vec![];
+
+  match parse(bytes, None, Some(&mut warnings)) {
+    Ok(script) => (Some(script), warnings),
+    Err(source) => {
+      warnings.push(ParseWarning::Failed { source });
+      (None, warnings)
+    }
+  }
+}
+
+/// Strict if `warnings` is `None`, otherwise recoverable anomalies are pushed to it.
+fn parse(
+  bytes: &[u8],
+  version: Option<OpcodeVersion>,
+  mut warnings: Option<&mut Vec<ParseWarning>>
+) -> Result<Script, ParseYscError> {
   let header_parser = YscHeaderParserFactory::create(bytes)?;
   let header = header_parser.parse(bytes)?;
 
@@ -22,9 +55,14 @@ pub fn parse_ysc(bytes: &[u8]) -> Result<Script, ParseYscError> {
       .iter()
       .map(|i| *i as usize)
       .collect::<Vec<_>>(),
-    0x4000
-  );
-  patch_opcodes(header_parser.opcode_version(), &mut code)?;
+    0x4000,
+    "code",
+    warnings.as_deref_mut()
+  )?;
+  remap_opcodes(
+    version.unwrap_or_else(|| header_parser.opcode_version()),
+    &mut code
+  )?;
 
   let strings = flatten_table(
     bytes,
@@ -34,17 +72,32 @@ pub fn parse_ysc(bytes: &[u8]) -> Result<Script, ParseYscError> {
       .iter()
       .map(|i| *i as usize)
       .collect::<Vec<_>>(),
-    0x4000
-  );
+    0x4000,
+    "string",
+    warnings.as_deref_mut()
+  )?;
+
+  let natives_offset = table_offset("natives", header.natives_offset, header.rsc7_offset)?;
+  let mut natives_count = header.natives_count;
+  if let Some(warnings) = warnings.as_deref_mut() {
+    let available = (bytes.len().saturating_sub(natives_offset) / 8) as u32;
+    if available < natives_count {
+      warnings.push(ParseWarning::NativeCountMismatch {
+        expected: natives_count,
+        available
+      });
+      natives_count = available;
+    }
+  }
 
   let mut reader = BinaryReader::from_u8(bytes);
   reader.set_endian(binary_reader::Endian::Little);
-  reader.adv(header.natives_offset as usize + header.rsc7_offset.unwrap_or_default() as usize);
-  let natives = (0..header.natives_count)
+  reader.adv(natives_offset);
+  let natives = (0..natives_count)
     .map(|i| {
       reader
         .read_u64()
-        .map(|hash| rotl_native_hash(hash, header.code_size + i))
+        .map(|hash| rotl_native_hash(hash, header.code_size.wrapping_add(i)))
     })
     .collect::<Result<Vec<_>, _>>()
     .map_err(|e| {
@@ -55,21 +108,51 @@ pub fn parse_ysc(bytes: &[u8]) -> Result<Script, ParseYscError> {
       }
     })?;
 
+  let statics_offset = table_offset("statics", header.statics_offset, header.rsc7_offset)?;
+  let mut statics_count = header.statics_count as usize;
+  if let Some(warnings) = warnings {
+    let available = bytes.len().saturating_sub(statics_offset) / 8;
+    if available < statics_count {
+      warnings.push(ParseWarning::TableClamped {
+        table:     "statics",
+        expected:  statics_count.saturating_mul(8),
+        available: available * 8
+      });
+      statics_count = available;
+    }
+  }
+
+  let statics = read_bytes(bytes, statics_offset, statics_count.saturating_mul(8))?
+    .chunks_exact(8)
+    .map(|slot| u64::from_le_bytes(slot.try_into().unwrap()))
+    .collect();
+
   Ok(Script {
     header: ScriptInfo {
+      magic:           header.magic,
       name:            header.script_name,
       name_hash:       header.name_hash,
       globals_version: header.globals_version,
       parameter_count: header.parameter_count,
-      static_count:    header.statics_count
+      static_count:    header.statics_count,
+      global_count:    header.globals_count
     },
     code,
     strings,
-    natives
+    natives,
+    statics
   })
 }
 
 pub fn parse_ysc_file(path: impl AsRef<Path> + Debug) -> Result<Script, ParseYscFileError> {
+  parse_ysc_file_with_version(path, None)
+}
+
+/// File version of [`parse_ysc_with_version`].
+pub fn parse_ysc_file_with_version(
+  path: impl AsRef<Path> + Debug,
+  version: Option<OpcodeVersion>
+) -> Result<Script, ParseYscFileError> {
   let path_ref = path.as_ref();
 
   let contents = fs::read(path_ref).map_err(|e| {
@@ -79,7 +162,7 @@ pub fn parse_ysc_file(path: impl AsRef<Path> + Debug) -> Result<Script, ParseYsc
     }
   })?;
 
-  parse_ysc(&contents).map_err(|e| {
+  parse_ysc_with_version(&contents, version).map_err(|e| {
     ParseYscFileError::ParseError {
       path:   path_ref.to_str().map(str::to_owned),
       source: e
@@ -87,21 +170,68 @@ pub fn parse_ysc_file(path: impl AsRef<Path> + Debug) -> Result<Script, ParseYsc
   })
 }
 
+/// The position of a table in the file, offsets of resource files are relative to the
+/// RSC7 header.
+fn table_offset(
+  table: &'static str,
+  offset: u32,
+  rsc7_offset: Option<u32>
+) -> Result<usize, ParseYscError> {
+  (offset as usize)
+    .checked_add(rsc7_offset.unwrap_or_default() as usize)
+    .ok_or(ParseYscError::InvalidOffset { table, offset })
+}
+
+/// Returns `needed` bytes starting at `offset`, or [`ParseYscError::Truncated`] if the
+/// input is too short.
+pub(crate) fn read_bytes(
+  bytes: &[u8],
+  offset: usize,
+  needed: usize
+) -> Result<&[u8], ParseYscError> {
+  offset
+    .checked_add(needed)
+    .and_then(|end| bytes.get(offset..end))
+    .ok_or(ParseYscError::Truncated {
+      offset,
+      needed,
+      available: bytes.len().saturating_sub(offset)
+    })
+}
+
+/// Joins the blocks of a table. With `warnings` the table is cut off at the first block
+/// that exceeds `bytes` instead of failing.
 fn flatten_table(
   bytes: &[u8],
   total_size: usize,
   block_offsets: &[usize],
-  block_size: usize
-) -> Vec<u8> {
-  block_offsets
-    .iter()
-    .enumerate()
-    .flat_map(|(index, offset)| {
-      let to_take = cmp::min(total_size - index * block_size, block_size);
-      let end_offset = *offset + to_take;
-      bytes[(*offset)..end_offset].to_vec()
-    })
-    .collect::<Vec<_>>()
+  block_size: usize,
+  table: &'static str,
+  mut warnings: Option<&mut Vec<ParseWarning>>
+) -> Result<Vec<u8>, ParseYscError> {
+  let mut result = Vec::with_capacity(cmp::min(total_size, bytes.len()));
+
+  for (index, offset) in block_offsets.iter().enumerate() {
+    let to_take = cmp::min(
+      total_size.saturating_sub(index.saturating_mul(block_size)),
+      block_size
+    );
+    match (read_bytes(bytes, *offset, to_take), warnings.as_deref_mut()) {
+      (Ok(block), _) => result.extend_from_slice(block),
+      (Err(ParseYscError::Truncated { .. }), Some(warnings)) => {
+        result.extend_from_slice(bytes.get(*offset..).unwrap_or_default());
+        warnings.push(ParseWarning::TableClamped {
+          table,
+          expected: total_size,
+          available: result.len()
+        });
+        break;
+      }
+      (Err(e), _) => return Err(e)
+    }
+  }
+
+  Ok(result)
 }
 
 fn rotl_native_hash(hash: u64, rotate: u32) -> u64 {
@@ -122,8 +252,21 @@ pub enum ParseYscError {
     source: anyhow::Error
   },
 
-  #[error("Invalid opcode {opcode} at {position}")]
-  InvalidOpcode { opcode: u8, position: usize },
+  #[error("Invalid code: {source}")]
+  InvalidCode {
+    #[from]
+    source: DisassembleError
+  },
+
+  #[error("Unexpected end of file: needed {needed} bytes at 0x{offset:X}, {available} available")]
+  Truncated {
+    offset:    usize,
+    needed:    usize,
+    available: usize
+  },
+
+  #[error("The {table} table offset 0x{offset:X} is out of range")]
+  InvalidOffset { table: &'static str, offset: u32 },
 
   #[error("Failed to read {count} natives at {offset}: {source}")]
   InvalidNativeInfo {
@@ -133,6 +276,23 @@ pub enum ParseYscError {
   }
 }
 
+/// An anomaly [`parse_ysc_lenient`] worked around.
+#[derive(Error, Debug)]
+pub enum ParseWarning {
+  #[error("The {table} table is truncated: expected {expected} bytes, {available} available")]
+  TableClamped {
+    table:     &'static str,
+    expected:  usize,
+    available: usize
+  },
+
+  #[error("Expected {expected} natives, only {available} are present")]
+  NativeCountMismatch { expected: u32, available: u32 },
+
+  #[error("{source}")]
+  Failed { source: ParseYscError }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseYscFileError {
   #[error("Failed to parse ysc file {path:?}: {source}")]
@@ -148,24 +308,266 @@ pub enum ParseYscFileError {
   }
 }
 
-fn patch_opcodes(version: OpcodeVersion, bytes: &mut [u8]) -> Result<(), ParseYscError> {
-  let mut i = 0;
-  while i < bytes.len() {
-    let byte = &mut bytes[i];
-    if version <= OpcodeVersion::B2802 && *byte >= Opcode::StaticU24.into() {
-      //*byte += 3; // StaticU24, StaticU24Load, StaticU24Store
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    disassembler::opcodes::Opcode,
+    script::{write_ysc, Rsc7Header, WriteYscError}
+  };
+
+  fn fixture() -> Vec<u8> {
+    write_ysc(&Script {
+      header:  ScriptInfo {
+        magic:           0xB3A8,
+        name:            "fixture".to_owned(),
+        name_hash:       0x1234_5678,
+        globals_version: 7,
+        parameter_count: 0,
+        static_count:    2,
+        global_count:    0
+      },
+      code:    vec![0x2E, 0x00, 0x02, 0x00, 0x00, 0x2F, 0x00, 0x00],
+      strings: b"hello\0".to_vec(),
+      natives: vec![0xDEAD_BEEF],
+      statics: vec![1, 2]
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn parses_fixture() {
+    let script = parse_ysc(&fixture()).unwrap();
+
+    assert_eq!(script.header.name, "fixture");
+    assert_eq!(script.code.len(), 8);
+    assert_eq!(script.get_string(0), Some("hello"));
+    assert_eq!(script.statics, vec![1, 2]);
+  }
+
+  #[test]
+  fn table_offsets_include_the_resource_header() {
+    assert_eq!(table_offset("statics", 0x10, None).unwrap(), 0x10);
+    assert_eq!(table_offset("statics", 0x10, Some(0x20)).unwrap(), 0x30);
+  }
+
+  #[test]
+  fn truncated_inputs_are_errors_instead_of_panics() {
+    let bytes = fixture();
+
+    for len in 0..bytes.len() {
+      assert!(parse_ysc(&bytes[..len]).is_err(), "{len} bytes parsed");
     }
+    assert!(matches!(
+      parse_ysc(&[]),
+      Err(ParseYscError::Truncated { offset: 0, .. })
+    ));
+    // The header parser reports through anyhow
+    let Err(ParseYscError::FailedToParseHeader { source }) = parse_ysc(&bytes[..0x40]) else {
+      panic!("a truncated header parsed");
+    };
+    assert!(matches!(
+      source.downcast_ref::<ParseYscError>(),
+      Some(ParseYscError::Truncated {
+        needed: 0x80,
+        available: 0x40,
+        ..
+      })
+    ));
+  }
 
-    let opcode = Opcode::try_from(*byte)
-      .map_err(|_| {
-        ParseYscError::InvalidOpcode {
-          opcode:   *byte,
-          position: i
-        }
+  fn rsc7_wrapped(virtual_flags: u32) -> Vec<u8> {
+    let mut bytes = b"RSC7".to_vec();
+    for word in [7u32, virtual_flags, 0] {
+      bytes.extend(word.to_le_bytes());
+    }
+    bytes.extend(fixture());
+    bytes
+  }
+
+  #[test]
+  fn rsc7_wrapped_scripts_expose_the_resource_flags() {
+    let bytes = rsc7_wrapped(0x10);
+    let header = YscHeaderParserFactory::create(&bytes)
+      .unwrap()
+      .parse(&bytes)
+      .unwrap();
+
+    assert_eq!(header.rsc7_offset, Some(0x10));
+    assert_eq!(
+      header.rsc7,
+      Some(Rsc7Header {
+        version:        7,
+        virtual_flags:  0x10,
+        physical_flags: 0
       })
+    );
+    // 256 pages of 0x200 bytes
+    assert_eq!(header.rsc7.unwrap().virtual_size(), 0x20000);
+    assert!(header.fits_rsc7());
+    assert_eq!(
+      parse_ysc(&bytes).unwrap().code,
+      parse_ysc(&fixture()).unwrap().code
+    );
+
+    let bytes = rsc7_wrapped(0);
+    let header = YscHeaderParserFactory::create(&bytes)
+      .unwrap()
+      .parse(&bytes)
       .unwrap();
-    i += opcode.size(&bytes[i..]) as usize;
+    assert!(!header.fits_rsc7());
   }
 
-  Ok(())
+  #[test]
+  fn version_overrides_decide_the_remapping() {
+    let push_const_1: u8 = Opcode::PushConst1.into();
+    let mut bytes = write_ysc(&Script {
+      code: vec![0x2D, 0, 2, 0, 0, push_const_1, 0x2E, 0, 0],
+      ..parse_ysc(&fixture()).unwrap()
+    })
+    .unwrap();
+
+    // The header says b2802, which needs no remapping
+    assert_eq!(parse_ysc(&bytes).unwrap().code[5], push_const_1);
+    // Before b2802 the byte was `PUSH_CONST_4`'s
+    let remapped = parse_ysc_with_version(&bytes, Some(OpcodeVersion::B2628)).unwrap();
+    assert_eq!(remapped.code[5], u8::from(Opcode::PushConst4));
+
+    // The other way around for a script whose header claims b2628
+    bytes[0..4].copy_from_slice(&0xB0B8u32.to_le_bytes());
+    assert_eq!(
+      parse_ysc(&bytes).unwrap().code[5],
+      u8::from(Opcode::PushConst4)
+    );
+    let kept = parse_ysc_with_version(&bytes, Some(OpcodeVersion::B2802)).unwrap();
+    assert_eq!(kept.code[5], push_const_1);
+  }
+
+  #[test]
+  fn lenient_parsing_clamps_overlong_string_tables() {
+    let mut bytes = fixture();
+    // Claim more strings than the file holds
+    bytes[0x70..0x74].copy_from_slice(&0x100u32.to_le_bytes());
+
+    assert!(matches!(
+      parse_ysc(&bytes),
+      Err(ParseYscError::Truncated { needed: 0x100, .. })
+    ));
+    let (script, warnings) = Script::from_bytes_lenient(&bytes);
+    let script = script.unwrap();
+    assert_eq!(script.get_string(0), Some("hello"));
+    assert!(matches!(
+      warnings[..],
+      [ParseWarning::TableClamped {
+        table: "string",
+        expected: 0x100,
+        ..
+      }]
+    ));
+  }
+
+  #[test]
+  fn native_hashes_are_rotated_by_their_position() {
+    assert_eq!(rotl_native_hash(1, 0), 1);
+    assert_eq!(rotl_native_hash(1, 65), 2);
+  }
+
+  #[test]
+  fn written_scripts_parse_back_unchanged() {
+    let script = Script {
+      header: ScriptInfo {
+        magic: 0x0001_B0B8,
+        ..parse_ysc(&fixture()).unwrap().header
+      },
+      code: vec![
+        0x2D,
+        0,
+        2,
+        0,
+        0,
+        Opcode::PushConst4.into(),
+        Opcode::Drop.into(),
+        0x2E,
+        0,
+        0,
+      ],
+      ..parse_ysc(&fixture()).unwrap()
+    };
+
+    let bytes = write_ysc(&script).unwrap();
+    // b2628 predates `STATIC_U24`, so the code is stored with its numbering
+    assert!(bytes
+      .windows(3)
+      .any(|window| window == [u8::from(Opcode::PushConst4) - 3, Opcode::Drop.into(), 0x2E]));
+
+    let parsed = parse_ysc(&bytes).unwrap();
+    assert_eq!(parsed.header.magic, 0x0001_B0B8);
+    assert_eq!(parsed.code, script.code);
+    assert_eq!(parsed.strings, script.strings);
+    assert_eq!(parsed.natives, script.natives);
+    assert_eq!(parsed.statics, script.statics);
+  }
+
+  #[test]
+  fn opcodes_missing_from_the_target_build_are_not_written() {
+    let script = Script {
+      header: ScriptInfo {
+        magic: 0xB0B8,
+        ..parse_ysc(&fixture()).unwrap().header
+      },
+      code: vec![Opcode::StaticU24.into(), 0, 0, 0],
+      ..parse_ysc(&fixture()).unwrap()
+    };
+
+    assert!(matches!(
+      write_ysc(&script),
+      Err(WriteYscError::InvalidCode {
+        source: DisassembleError::UnsupportedOpcode {
+          opcode: Opcode::StaticU24,
+          pos:    0
+        }
+      })
+    ));
+  }
+
+  #[test]
+  fn parses_little_endian_fields_independently_of_the_host() {
+    // Spelled out byte by byte, so nothing depends on the endianness of the host
+    let mut bytes = vec![0u8; 0xAD];
+    let mut put = |offset: usize, data: &[u8]| {
+      bytes[offset..offset + data.len()].copy_from_slice(data);
+    };
+    put(0x00, &[0xA8, 0xB3, 0x00, 0x00]); // magic
+    put(0x10, &[0x80, 0x00, 0x00, 0x50]); // code blocks
+    put(0x18, &[0x04, 0x03, 0x02, 0x01]); // globals version
+    put(0x1C, &[0x08, 0x00, 0x00, 0x00]); // code size
+    put(0x24, &[0x01, 0x00, 0x00, 0x00]); // statics count
+    put(0x2C, &[0x01, 0x00, 0x00, 0x00]); // natives count
+    put(0x30, &[0x98, 0x00, 0x00, 0x50]); // statics
+    put(0x40, &[0x90, 0x00, 0x00, 0x50]); // natives
+    put(0x58, &[0x78, 0x56, 0x34, 0x12]); // name hash
+    put(0x60, &[0xAB, 0x00, 0x00, 0x50]); // name
+    put(0x68, &[0x88, 0x00, 0x00, 0x50]); // string blocks
+    put(0x70, &[0x03, 0x00, 0x00, 0x00]); // strings size
+    put(0x80, &[0xA0, 0x00, 0x00, 0x50]);
+    put(0x88, &[0xA8, 0x00, 0x00, 0x50]);
+    put(0x90, &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    put(0x98, &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+    put(0xA0, &[0x2D, 0x00, 0x02, 0x00, 0x00, 0x2E, 0x00, 0x00]);
+    put(0xA8, b"hi\0t\0");
+
+    let script = parse_ysc(&bytes).unwrap();
+    assert_eq!(script.header.magic, 0xB3A8);
+    assert_eq!(script.header.name, "t");
+    assert_eq!(script.header.name_hash, 0x1234_5678);
+    assert_eq!(script.header.globals_version, 0x0102_0304);
+    assert_eq!(
+      script.code,
+      [0x2D, 0x00, 0x02, 0x00, 0x00, 0x2E, 0x00, 0x00]
+    );
+    assert_eq!(script.get_string(0), Some("hi"));
+    // Rotated left by the code size
+    assert_eq!(script.natives, vec![0x100]);
+    assert_eq!(script.statics, vec![0x0102_0304_0506_0708]);
+  }
 }
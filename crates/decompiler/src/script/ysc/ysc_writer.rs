@@ -0,0 +1,93 @@
+use thiserror::Error;
+
+use crate::{
+  disassembler::{unmap_opcodes, DisassembleError},
+  script::{OpcodeVersion, Script}
+};
+
+use super::UnknownMagicError;
+
+const BLOCK_SIZE: usize = 0x4000;
+const HEADER_SIZE: usize = 0x80;
+
+/// Serializes `script` as an unwrapped PC ysc file that [`super::parse_ysc`] reads back.
+/// The code is written with the opcode numbering of the build its magic belongs to.
+/// Globals aren't part of [`Script`], so the written script doesn't define any.
+pub fn write_ysc(script: &Script) -> Result<Vec<u8>, WriteYscError> {
+  let magic = script.header.magic;
+  let version = OpcodeVersion::from_magic(magic).ok_or(UnknownMagicError { magic })?;
+  let mut code = script.code.clone();
+  unmap_opcodes(version, &mut code)?;
+
+  let code_blocks = script.code.len().div_ceil(BLOCK_SIZE);
+  let string_blocks = script.strings.len().div_ceil(BLOCK_SIZE);
+
+  let code_table_offset = HEADER_SIZE;
+  let string_table_offset = code_table_offset + code_blocks * 8;
+  let natives_offset = string_table_offset + string_blocks * 8;
+  let statics_offset = natives_offset + script.natives.len() * 8;
+  let code_offset = statics_offset + script.statics.len() * 8;
+  let strings_offset = code_offset + script.code.len();
+  let name_offset = strings_offset + script.strings.len();
+
+  let mut bytes = vec![0u8; HEADER_SIZE];
+  write_u32(&mut bytes, 0x00, magic);
+  write_u32(&mut bytes, 0x10, pointer(code_table_offset));
+  write_u32(&mut bytes, 0x18, script.header.globals_version);
+  write_u32(&mut bytes, 0x1C, script.code.len() as u32);
+  write_u32(&mut bytes, 0x20, script.header.parameter_count);
+  write_u32(&mut bytes, 0x24, script.statics.len() as u32);
+  write_u32(&mut bytes, 0x2C, script.natives.len() as u32);
+  write_u32(&mut bytes, 0x30, pointer(statics_offset));
+  write_u32(&mut bytes, 0x40, pointer(natives_offset));
+  write_u32(&mut bytes, 0x58, script.header.name_hash);
+  write_u32(&mut bytes, 0x60, pointer(name_offset));
+  write_u32(&mut bytes, 0x68, pointer(string_table_offset));
+  write_u32(&mut bytes, 0x70, script.strings.len() as u32);
+
+  for block in 0..code_blocks {
+    bytes.extend((pointer(code_offset + block * BLOCK_SIZE) as u64).to_le_bytes());
+  }
+  for block in 0..string_blocks {
+    bytes.extend((pointer(strings_offset + block * BLOCK_SIZE) as u64).to_le_bytes());
+  }
+
+  // Natives are stored rotated by their index plus the code size
+  for (index, hash) in script.natives.iter().enumerate() {
+    let rotate = (script.code.len() as u32).wrapping_add(index as u32) % 64;
+    bytes.extend(hash.rotate_right(rotate).to_le_bytes());
+  }
+  for value in &script.statics {
+    bytes.extend(value.to_le_bytes());
+  }
+
+  bytes.extend(&code);
+  bytes.extend(&script.strings);
+  bytes.extend(script.header.name.as_bytes());
+  bytes.push(0);
+
+  Ok(bytes)
+}
+
+fn pointer(offset: usize) -> u32 {
+  0x5000_0000 | offset as u32
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+  bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[derive(Error, Debug)]
+pub enum WriteYscError {
+  #[error("{source}")]
+  InvalidMagic {
+    #[from]
+    source: UnknownMagicError
+  },
+
+  #[error("Invalid code: {source}")]
+  InvalidCode {
+    #[from]
+    source: DisassembleError
+  }
+}
@@ -0,0 +1,183 @@
+//! Helpers for tests that decompile hand written bytecode.
+
+use std::collections::HashMap;
+
+use crate::{
+  decompiler::{
+    decompile_all, decompile_all_with_options, decompiled::DecompiledFunction, get_functions,
+    DecompileOptions, DecompileReport, DecompilerDataBuilder
+  },
+  disassembler::{disassemble, opcodes::Opcode},
+  formatters::{CppFormatter, CppFormatterOptions},
+  resources::{CrossMap, Natives},
+  script::ScriptTables
+};
+
+/// Tables of a script without strings or natives.
+pub const NO_TABLES: ScriptTables = ScriptTables {
+  strings: &[],
+  natives: &[]
+};
+
+/// Writes bytecode one opcode and operand at a time, operands are little endian.
+#[derive(Default)]
+pub struct Asm {
+  code:   Vec<u8>,
+  labels: HashMap<&'static str, usize>,
+  /// Jump operands to fill in with the offset to a label once all labels are known
+  jumps:  Vec<(usize, &'static str)>
+}
+
+impl Asm {
+  pub fn op(mut self, opcode: Opcode) -> Self {
+    self.code.push(opcode.into());
+    self
+  }
+
+  pub fn u8(mut self, value: u8) -> Self {
+    self.code.push(value);
+    self
+  }
+
+  pub fn u16(mut self, value: u16) -> Self {
+    self.code.extend(value.to_le_bytes());
+    self
+  }
+
+  pub fn u24(mut self, value: u32) -> Self {
+    self.code.extend(&value.to_le_bytes()[..3]);
+    self
+  }
+
+  pub fn u32(mut self, value: u32) -> Self {
+    self.code.extend(value.to_le_bytes());
+    self
+  }
+
+  pub fn enter(self, arg_count: u8, frame_size: u16) -> Self {
+    self.op(Opcode::Enter).u8(arg_count).u16(frame_size).u8(0)
+  }
+
+  /// An `ENTER` keeping the name of the function, as in debug builds.
+  pub fn enter_named(self, arg_count: u8, frame_size: u16, name: &str) -> Self {
+    name.bytes().fold(
+      self
+        .op(Opcode::Enter)
+        .u8(arg_count)
+        .u16(frame_size)
+        .u8(name.len() as u8),
+      Self::u8
+    )
+  }
+
+  pub fn leave(self, arg_count: u8, return_count: u8) -> Self {
+    self.op(Opcode::Leave).u8(arg_count).u8(return_count)
+  }
+
+  /// Names the position of the next instruction.
+  pub fn label(mut self, name: &'static str) -> Self {
+    self.labels.insert(name, self.code.len());
+    self
+  }
+
+  /// A jump instruction to the position named `label`.
+  pub fn jump(self, opcode: Opcode, label: &'static str) -> Self {
+    self.op(opcode).offset(label)
+  }
+
+  /// A jump operand to the position named `label`.
+  pub fn offset(mut self, label: &'static str) -> Self {
+    self.jumps.push((self.code.len(), label));
+    self.u16(0)
+  }
+
+  pub fn build(mut self) -> Vec<u8> {
+    for (operand, label) in &self.jumps {
+      let target = self.labels[label] as isize;
+      let offset = (target - (*operand as isize + 2)) as i16;
+      self.code[*operand..*operand + 2].copy_from_slice(&offset.to_le_bytes());
+    }
+    self.code
+  }
+}
+
+/// Decompiles every function in `code` without natives or strings.
+pub fn decompile_cpp(code: &[u8], options: CppFormatterOptions) -> String {
+  decompile_cpp_with(code, NO_TABLES, "{}", options)
+}
+
+/// Decompiles every function in `code` and formats them with `options`, functions that fail
+/// to decompile are left out.
+pub fn decompile_cpp_with(
+  code: &[u8],
+  script: ScriptTables,
+  natives_json: &str,
+  options: CppFormatterOptions
+) -> String {
+  decompile_with(
+    code,
+    script,
+    natives_json,
+    options,
+    |formatter, functions| {
+      functions
+        .iter()
+        .map(|function| formatter.format_function(function))
+        .collect()
+    }
+  )
+}
+
+/// Decompiles every function in `code` and passes the ones that succeeded to `cb` together
+/// with a formatter using `options`.
+pub fn decompile_with<R>(
+  code: &[u8],
+  script: ScriptTables,
+  natives_json: &str,
+  options: CppFormatterOptions,
+  cb: impl FnOnce(&CppFormatter, &[&DecompiledFunction]) -> R
+) -> R {
+  decompile_with_data(code, script, natives_json, |builder| builder, options, cb)
+}
+
+/// Like [`decompile_with`], `configure` can add resources to the decompiler data.
+pub fn decompile_with_data<R>(
+  code: &[u8],
+  script: ScriptTables,
+  natives_json: &str,
+  configure: impl for<'f, 'i, 'b> FnOnce(
+    DecompilerDataBuilder<'f, 'i, 'b>
+  ) -> DecompilerDataBuilder<'f, 'i, 'b>,
+  options: CppFormatterOptions,
+  cb: impl FnOnce(&CppFormatter, &[&DecompiledFunction]) -> R
+) -> R {
+  let instructions = disassemble(code).expect("test bytecode disassembles");
+  let functions = get_functions(&instructions);
+  let builder = configure(DecompilerDataBuilder::new(
+    Natives::from_json(natives_json).expect("test natives parse"),
+    CrossMap::identity()
+  ))
+  .functions(&functions);
+  let data = builder.build();
+
+  let report = decompile_all(&functions, script, &data);
+  let formatter = CppFormatter::with_options(data, options);
+  cb(&formatter, &report.succeeded().collect::<Vec<_>>())
+}
+
+/// Decompiles every function in `code` with `options` and passes the report, failed functions
+/// included, to `cb` together with a formatter.
+pub fn decompile_report_with<R>(
+  code: &[u8],
+  options: &DecompileOptions,
+  cb: impl FnOnce(&CppFormatter, &DecompileReport) -> R
+) -> R {
+  let instructions = disassemble(code).expect("test bytecode disassembles");
+  let functions = get_functions(&instructions);
+  let builder = DecompilerDataBuilder::new(Natives::from_json("{}").unwrap(), CrossMap::identity())
+    .functions(&functions);
+  let data = builder.build();
+
+  let report = decompile_all_with_options(&functions, NO_TABLES, &data, options);
+  cb(&CppFormatter::new(data), &report)
+}
@@ -0,0 +1,21 @@
+//! Builds the disassembler without default features, so a dependency on `std` sneaking into
+//! it fails the test suite instead of embedders.
+
+use std::process::Command;
+
+#[test]
+fn disassembler_builds_without_std() {
+  let output = Command::new(env!("CARGO"))
+    .args(["check", "--lib", "--no-default-features", "--manifest-path"])
+    .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+    .arg("--target-dir")
+    .arg(concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"))
+    .output()
+    .expect("cargo runs");
+
+  assert!(
+    output.status.success(),
+    "{}",
+    String::from_utf8_lossy(&output.stderr)
+  );
+}